@@ -1,13 +1,19 @@
-use std::collections::HashMap;
-
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(i64),
     Float(f64),
     String(String),
+    Char(char), // one character, as produced by indexing a string
     Boolean(bool),
     Array(Vec<Value>),
-    Object(HashMap<String, Value>),
+    Set(Vec<Value>), // distinct elements (by `==`) in insertion order
+    Range { start: i64, end: i64, step: i64 }, // the integers from `start` up to but not including `end`
+    Object(ObjectMap),
+    Function(String), // name of a user-defined function
+    Builtin(&'static str), // a native builtin, by name
+    Error { message: String }, // a thrown or caught error
+    File { id: usize, path: String }, // a handle from open(); the interpreter holds the file until close()
+    Null, // an absent value; Void is the result of statements that produce nothing
     Void,
 }
 
@@ -15,45 +21,270 @@ impl Value {
     pub fn to_string(&self) -> String {
         match self {
             Value::Number(n) => n.to_string(),
+            Value::Float(f) if *f == 0.0 => "0".to_string(), // -0.0 prints like 0.0
             Value::Float(f) => f.to_string(),
             Value::String(s) => s.clone(),
+            Value::Char(c) => c.to_string(),
             Value::Boolean(b) => b.to_string(),
             Value::Array(arr) => {
                 let items: Vec<String> = arr.iter().map(|v| v.to_string()).collect();
                 format!("[{}]", items.join(", "))
             }
+            Value::Set(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                format!("{{{}}}", items.join(", "))
+            }
             Value::Object(map) => {
                 let items: Vec<String> = map.iter()
                     .map(|(k, v)| format!("{}: {}", k, v.to_string()))
                     .collect();
                 format!("{{{}}}", items.join(", "))
             }
+            Value::Range { start, end, step: 1 } => format!("range({}, {})", start, end),
+            Value::Range { start, end, step } => format!("range({}, {}, {})", start, end, step),
+            Value::Function(name) => format!("<function {}>", name),
+            Value::Builtin(name) => format!("<builtin {}>", name),
+            Value::Error { message } => format!("Error: {}", message),
+            Value::File { path, .. } => format!("<file {}>", path),
+            Value::Null => "null".to_string(),
             Value::Void => "void".to_string(),
         }
     }
     
+    // Like to_string, but strings are quoted wherever they appear, so `["1", 1]`
+    // doesn't read as `[1, 1]`. Used for REPL output; print keeps the plain form.
+    pub fn debug_string(&self) -> String {
+        match self {
+            Value::String(s) => crate::json::quote(s),
+            Value::Char(c) => format!("'{}'", c.escape_debug()),
+            Value::Array(arr) => {
+                let items: Vec<String> = arr.iter().map(|v| v.debug_string()).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Set(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.debug_string()).collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            Value::Object(map) => {
+                let items: Vec<String> = map.iter()
+                    .map(|(k, v)| format!("{}: {}", k, v.debug_string()))
+                    .collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            other => other.to_string(),
+        }
+    }
+    
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Set(_) => "set",
+            Value::Range { .. } => "range",
+            Value::Object(_) => "object",
+            Value::Function(_) => "function",
+            Value::Builtin(_) => "builtin",
+            Value::Error { .. } => "error",
+            Value::File { .. } => "file",
+            Value::Null => "null",
+            Value::Void => "void",
+        }
+    }
+    
+    pub fn is_builtin(&self) -> bool {
+        matches!(self, Value::Builtin(_))
+    }
+    
+    pub fn is_callable(&self) -> bool {
+        matches!(self, Value::Function(_) | Value::Builtin(_))
+    }
+    
+    // The string an object stores this value under when it's used as a key.
+    // Values that compare equal get the same key, so `1` and `1.0` name one entry.
+    pub fn to_key(&self) -> Result<String, String> {
+        match self {
+            Value::String(s) => Ok(s.clone()),
+            Value::Char(c) => Ok(c.to_string()),
+            Value::Number(n) => Ok(n.to_string()),
+            Value::Float(f) if f.is_nan() => Err("Cannot use NaN as an object key".to_string()),
+            Value::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Ok((*f as i64).to_string()),
+            Value::Float(f) => Ok(f.to_string()),
+            Value::Boolean(b) => Ok(b.to_string()),
+            Value::Null => Ok("null".to_string()),
+            other => Err(format!("Cannot use {} as an object key", other.type_name())),
+        }
+    }
+    
+    /// Roughly how many bytes this value takes up, as reported by `sizeof()`: the value itself
+    /// plus the text and elements it owns. Allocator overhead and spare capacity aren't counted.
+    ///
+    /// ```
+    /// use hiolang::Value;
+    ///
+    /// let small = Value::Array((0..10).map(Value::Number).collect());
+    /// let large = Value::Array((0..1000).map(Value::Number).collect());
+    /// assert!(large.approx_size() > 50 * small.approx_size());
+    /// assert!(Value::String("hello".repeat(100)).approx_size() > Value::String("hello".to_string()).approx_size());
+    /// ```
+    pub fn approx_size(&self) -> usize {
+        let owned = match self {
+            Value::String(s) | Value::Function(s) | Value::Error { message: s } | Value::File { path: s, .. } => s.len(),
+            Value::Array(items) | Value::Set(items) => items.iter().map(Value::approx_size).sum(),
+            Value::Object(map) => map.iter()
+                .map(|(k, v)| std::mem::size_of::<String>() + k.len() + v.approx_size())
+                .sum(),
+            _ => 0,
+        };
+        std::mem::size_of::<Value>() + owned
+    }
+    
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Void => false,
+            Value::Null => false,
             Value::Number(n) => *n != 0,
             Value::String(s) => !s.is_empty(),
-            Value::Array(arr) => !arr.is_empty(),
+            Value::Array(items) | Value::Set(items) => !items.is_empty(),
+            Value::Range { start, end, step } => range_len(*start, *end, *step) > 0,
             Value::Object(map) => !map.is_empty(),
             _ => true,
         }
     }
 }
 
+// How many values a range yields. Arithmetic is widened so extreme bounds can't overflow.
+pub fn range_len(start: i64, end: i64, step: i64) -> usize {
+    let distance = if step > 0 { end as i128 - start as i128 } else { start as i128 - end as i128 };
+    let step = step.unsigned_abs() as i128;
+    if distance <= 0 || step == 0 {
+        0
+    } else {
+        ((distance + step - 1) / step) as usize
+    }
+}
+
+// The value at position `index` of a range, which must be below its length
+pub fn range_nth(start: i64, step: i64, index: usize) -> i64 {
+    (start as i128 + index as i128 * step as i128) as i64
+}
+
+// The values of a range in order, computed one at a time
+pub fn range_values(start: i64, end: i64, step: i64) -> impl Iterator<Item = i64> {
+    (0..range_len(start, end, step)).map(move |i| range_nth(start, step, i))
+}
+
+// Float `==`: values within f64::EPSILON are equal and infinities equal themselves.
+// NaN is unequal to everything, itself included.
+pub fn floats_equal(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() < f64::EPSILON
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => floats_equal(*a, *b),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            // A char equals the one-character string of it, so `s[0] == "a"` works
+            (Value::Char(c), Value::String(s)) | (Value::String(s), Value::Char(c)) => {
+                let mut chars = s.chars();
+                chars.next() == Some(*c) && chars.next().is_none()
+            }
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            // Key order doesn't matter for objects, nor element order for sets
+            (Value::Object(a), Value::Object(b)) => {
+                a.len() == b.len() && a.iter().all(|(k, v)| b.get(k) == Some(v))
+            }
+            (Value::Set(a), Value::Set(b)) => a.len() == b.len() && a.iter().all(|v| b.contains(v)),
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Builtin(a), Value::Builtin(b)) => a == b,
+            (Value::Error { message: a }, Value::Error { message: b }) => a == b,
+            // Two handles are the same file only if they came from the same open()
+            (Value::File { id: a, .. }, Value::File { id: b, .. }) => a == b,
+            // Ranges are equal when they yield the same values
+            (&Value::Range { start: a, end: b, step: c }, &Value::Range { start: x, end: y, step: z }) => {
+                let len = range_len(a, b, c);
+                len == range_len(x, y, z) && (len == 0 || (a == x && (len == 1 || c == z)))
+            }
+            (Value::Null, Value::Null) => true,
+            (Value::Void, Value::Void) => true,
+            _ => false,
+        }
+    }
+}
+
+// Object storage that remembers key insertion order, so printing and iteration are stable.
+// Objects are small in practice, so lookups are a linear scan.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectMap {
+    entries: Vec<(String, Value)>,
+}
+
+impl ObjectMap {
+    pub fn new() -> Self {
+        ObjectMap { entries: Vec::new() }
+    }
+    
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+    
+    // Replacing an existing key keeps its original position
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+    
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+    
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+// A key in an object literal: `name: v` / `"name": v` / `1: v`, or computed as `[expr]: v`
+#[derive(Debug, Clone)]
+pub enum ObjectKey {
+    Name(String),
+    Computed(Expr),
+}
+
 #[derive(Debug, Clone)]
 pub enum Expr {
     Number(i64),
     Float(f64),
     String(String),
+    // `"text ${expr} text"`; the pieces are joined as strings, literal text as Expr::String
+    Interpolated(Vec<Expr>),
     Boolean(bool),
+    Null,
     Identifier(String),
     Array(Vec<Expr>),
-    Object(Vec<(String, Expr)>),
+    Set(Vec<Expr>), // `{a, b}`: an object literal's braces without any `key:`
+    Object(Vec<(ObjectKey, Expr)>),
     Binary {
         left: Box<Expr>,
         op: BinaryOp,
@@ -66,6 +297,7 @@ pub enum Expr {
     Call {
         func: Box<Expr>,
         args: Vec<Expr>,
+        line: usize, // source line of the call site
     },
     Index {
         object: Box<Expr>,
@@ -75,6 +307,27 @@ pub enum Expr {
         object: Box<Expr>,
         member: String,
     },
+    Slice {
+        object: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+    // `a < b < c` with two or more operators; each operand is evaluated once
+    Compare {
+        first: Box<Expr>,
+        rest: Vec<(BinaryOp, Expr)>,
+    },
+    Ternary {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    // `x++`, `++x`, `x--`, `--x`; prefix forms yield the new value, postfix forms the old one
+    Update {
+        target: String,
+        increment: bool,
+        prefix: bool,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -83,6 +336,7 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    IntDivide,
     Modulo,
     Equal,
     NotEqual,
@@ -94,63 +348,158 @@ pub enum BinaryOp {
     Or,
 }
 
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::IntDivide => "//",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Negate,
     Not,
 }
 
+// Statements that evaluate expressions record the source line they start on (the `usize` of
+// the tuple variants), so runtime errors can say where they happened
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    Expression(Expr),
+    Expression(Expr, usize),
     Let {
         name: String,
         value: Expr,
+        line: usize,
+    },
+    Const {
+        name: String,
+        value: Expr,
+        line: usize,
+    },
+    LetArray {
+        names: Vec<String>,
+        rest: Option<String>,
+        value: Expr,
+        line: usize,
     },
     Assign {
         target: String,
         value: Expr,
+        line: usize,
     },
     If {
         condition: Expr,
         then_branch: Vec<Stmt>,
         else_branch: Option<Vec<Stmt>>,
+        line: usize,
     },
     While {
         condition: Expr,
         body: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>, // runs only if the loop was not broken out of
+        line: usize,
+    },
+    DoWhile {
+        body: Vec<Stmt>,
+        condition: Expr,
+        line: usize,
     },
     For {
         init: Option<Box<Stmt>>,
         condition: Option<Expr>,
         increment: Option<Box<Expr>>,
         body: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>, // runs only if the loop was not broken out of
+        line: usize,
+    },
+    ForIn {
+        var: String,
+        iterable: Expr,
+        body: Vec<Stmt>,
+        line: usize,
     },
     FunctionDef {
         name: String,
         params: Vec<String>,
         body: Vec<Stmt>,
+        line: usize, // where the definition starts
+    },
+    Return(Option<Expr>, usize),
+    Break(Option<String>), // optional label of the loop to break out of
+    Continue(Option<String>),
+    Throw(Expr, usize),
+    // `try { body } catch (var) { handler }`
+    Try {
+        body: Vec<Stmt>,
+        var: String,
+        handler: Vec<Stmt>,
+    },
+    // `label: while (...) { ... }`; the body is always a loop
+    Labeled {
+        label: String,
+        body: Box<Stmt>,
     },
-    Return(Option<Expr>),
-    Break,
-    Continue,
     Space {
         name: String,
         body: Vec<Stmt>,
     },
+    // Definitions made directly in the body are exported from the enclosing space
     Pub {
-        name: String,
-        kind: String, // "interpretation" or "compilation"
+        mode: String, // from the optional header, e.g. "interpretation"
         body: Vec<Stmt>,
     },
+    // Definitions made directly in the body are only visible to the space around the enclosing one
     Subpub {
-        name: String,
-        compilation_type: String,
+        mode: String, // from the optional header, e.g. "compilation"
+        body: Vec<Stmt>,
+    },
+    // `inspace Name { ... }`; runs the body with the space's visible names in scope
+    Inspace {
+        space: String,
+        body: Vec<Stmt>,
+    },
+    // `import "path.hio";`; brings in the `pub` names of another file
+    Import(String),
+    // `defer stmt`; runs the statement when the enclosing function call (or program) finishes
+    Defer(Box<Stmt>),
+    // `with (var = value) { body }`; closes the file `value` opened once the body finishes
+    With {
+        var: String,
+        value: Expr,
         body: Vec<Stmt>,
+        line: usize,
     },
     Block(Vec<Stmt>),
 }
 
+impl Stmt {
+    // The source line of a statement that evaluates expressions; None for the others
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Expression(_, line) | Stmt::Return(_, line) | Stmt::Throw(_, line)
+            | Stmt::Let { line, .. } | Stmt::Const { line, .. } | Stmt::LetArray { line, .. }
+            | Stmt::Assign { line, .. } | Stmt::If { line, .. } | Stmt::While { line, .. }
+            | Stmt::DoWhile { line, .. } | Stmt::For { line, .. } | Stmt::ForIn { line, .. }
+            | Stmt::FunctionDef { line, .. } | Stmt::With { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Stmt>,
@@ -161,3 +510,238 @@ impl Program {
         Program { statements }
     }
 }
+
+
+// Renders a program as indented, source-like text. Binary, unary and ternary
+// expressions are fully parenthesized so the parsed precedence is visible.
+pub fn pretty_print(program: &Program) -> String {
+    let mut out = String::new();
+    for stmt in &program.statements {
+        write_stmt(&mut out, stmt, 0);
+    }
+    out
+}
+
+fn write_line(out: &mut String, indent: usize, text: &str) {
+    out.push_str(&"    ".repeat(indent));
+    out.push_str(text);
+    out.push('\n');
+}
+
+fn write_body(out: &mut String, stmts: &[Stmt], indent: usize) {
+    for stmt in stmts {
+        write_stmt(out, stmt, indent + 1);
+    }
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, indent: usize) {
+    match stmt {
+        Stmt::Expression(..) | Stmt::Let { .. } | Stmt::Const { .. } | Stmt::LetArray { .. } | Stmt::Assign { .. } => {
+            write_line(out, indent, &format!("{};", stmt_inline(stmt)));
+        }
+        Stmt::If { condition, then_branch, else_branch, .. } => {
+            write_line(out, indent, &format!("if ({}) {{", format_expr(condition)));
+            write_body(out, then_branch, indent);
+            write_else(out, else_branch, indent);
+        }
+        Stmt::While { condition, body, else_branch, .. } => {
+            write_line(out, indent, &format!("while ({}) {{", format_expr(condition)));
+            write_body(out, body, indent);
+            write_else(out, else_branch, indent);
+        }
+        Stmt::DoWhile { body, condition, .. } => {
+            write_line(out, indent, "do {");
+            write_body(out, body, indent);
+            write_line(out, indent, &format!("}} while ({});", format_expr(condition)));
+        }
+        Stmt::For { init, condition, increment, body, else_branch, .. } => {
+            let init = init.as_ref().map_or(String::new(), |s| stmt_inline(s));
+            let condition = condition.as_ref().map_or(String::new(), format_expr);
+            let increment = increment.as_ref().map_or(String::new(), |e| format_expr(e));
+            write_line(out, indent, &format!("for ({}; {}; {}) {{", init, condition, increment));
+            write_body(out, body, indent);
+            write_else(out, else_branch, indent);
+        }
+        Stmt::ForIn { var, iterable, body, .. } => {
+            write_line(out, indent, &format!("for ({} in {}) {{", var, format_expr(iterable)));
+            write_body(out, body, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::FunctionDef { name, params, body, .. } => {
+            write_line(out, indent, &format!("function {}({}) {{", name, params.join(", ")));
+            write_body(out, body, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::Return(expr, _) => match expr {
+            Some(e) => write_line(out, indent, &format!("return {};", format_expr(e))),
+            None => write_line(out, indent, "return;"),
+        },
+        Stmt::Break(label) => write_line(out, indent, &format!("break{};", label_suffix(label))),
+        Stmt::Continue(label) => write_line(out, indent, &format!("continue{};", label_suffix(label))),
+        Stmt::Throw(expr, _) => write_line(out, indent, &format!("throw {};", format_expr(expr))),
+        Stmt::Try { body, var, handler } => {
+            write_line(out, indent, "try {");
+            write_body(out, body, indent);
+            write_line(out, indent, &format!("}} catch ({}) {{", var));
+            write_body(out, handler, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::Labeled { label, body } => {
+            // Render the loop, then splice the label onto its first line
+            let mut loop_text = String::new();
+            write_stmt(&mut loop_text, body, indent);
+            let prefix = "    ".repeat(indent);
+            out.push_str(&prefix);
+            out.push_str(&format!("{}: ", label));
+            out.push_str(&loop_text[prefix.len()..]);
+        }
+        Stmt::Space { name, body } => {
+            write_line(out, indent, &format!("space {} {{", name));
+            write_body(out, body, indent);
+            write_line(out, indent, "} end make;");
+        }
+        Stmt::Pub { mode, body } => {
+            write_line(out, indent, &format!("pub;{{;com {:?}}};{{", mode));
+            write_body(out, body, indent);
+            write_line(out, indent, "}\u{2014}");
+        }
+        Stmt::Subpub { mode, body } => {
+            write_line(out, indent, &format!("subpub;{{;com {:?}}};{{", mode));
+            write_body(out, body, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::Inspace { space, body } => {
+            write_line(out, indent, &format!("inspace {} {{", space));
+            write_body(out, body, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::Import(path) => write_line(out, indent, &format!("import {:?};", path)),
+        Stmt::Defer(stmt) => {
+            let mut deferred = String::new();
+            write_stmt(&mut deferred, stmt, indent);
+            out.push_str(&"    ".repeat(indent));
+            out.push_str("defer ");
+            out.push_str(deferred.trim_start());
+        }
+        Stmt::With { var, value, body, .. } => {
+            write_line(out, indent, &format!("with ({} = {}) {{", var, format_expr(value)));
+            write_body(out, body, indent);
+            write_line(out, indent, "}");
+        }
+        Stmt::Block(stmts) => {
+            write_line(out, indent, "{");
+            write_body(out, stmts, indent);
+            write_line(out, indent, "}");
+        }
+    }
+}
+
+fn label_suffix(label: &Option<String>) -> String {
+    label.as_ref().map_or(String::new(), |l| format!(" {}", l))
+}
+
+fn write_else(out: &mut String, else_branch: &Option<Vec<Stmt>>, indent: usize) {
+    match else_branch {
+        Some(stmts) => {
+            write_line(out, indent, "} else {");
+            write_body(out, stmts, indent);
+            write_line(out, indent, "}");
+        }
+        None => write_line(out, indent, "}"),
+    }
+}
+
+// Single-line statements without the trailing semicolon, as used in `for` headers
+fn stmt_inline(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Expression(e, _) => format_expr(e),
+        Stmt::Let { name, value, .. } => format!("let {} = {}", name, format_expr(value)),
+        Stmt::Const { name, value, .. } => format!("const {} = {}", name, format_expr(value)),
+        Stmt::LetArray { names, rest, value, .. } => {
+            let mut targets = names.clone();
+            if let Some(r) = rest {
+                targets.push(format!("...{}", r));
+            }
+            format!("let [{}] = {}", targets.join(", "), format_expr(value))
+        }
+        Stmt::Assign { target, value, .. } => format!("{} = {}", target, format_expr(value)),
+        _ => "...".to_string(),
+    }
+}
+
+fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(n) => n.to_string(),
+        Expr::Float(f) => format!("{:?}", f),
+        Expr::String(s) => format!("{:?}", s).replace("${", "\\${"),
+        Expr::Interpolated(parts) => {
+            let parts: Vec<String> = parts.iter()
+                .map(|part| match part {
+                    Expr::String(s) => format!("{:?}", s).trim_matches('"').replace("${", "\\${"),
+                    e => format!("${{{}}}", format_expr(e)),
+                })
+                .collect();
+            format!("\"{}\"", parts.concat())
+        }
+        Expr::Boolean(b) => b.to_string(),
+        Expr::Null => "nil".to_string(),
+        Expr::Identifier(name) => name.clone(),
+        Expr::Array(items) => {
+            let items: Vec<String> = items.iter().map(format_expr).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Expr::Set(items) => {
+            let items: Vec<String> = items.iter().map(format_expr).collect();
+            format!("{{{}}}", items.join(", "))
+        }
+        Expr::Object(pairs) => {
+            let pairs: Vec<String> = pairs.iter()
+                .map(|(k, v)| match k {
+                    ObjectKey::Name(name) => format!("{:?}: {}", name, format_expr(v)),
+                    ObjectKey::Computed(key) => format!("[{}]: {}", format_expr(key), format_expr(v)),
+                })
+                .collect();
+            format!("{{{}}}", pairs.join(", "))
+        }
+        Expr::Binary { left, op, right } => {
+            format!("({} {} {})", format_expr(left), op.symbol(), format_expr(right))
+        }
+        Expr::Unary { op, expr } => {
+            let symbol = match op {
+                UnaryOp::Negate => "-",
+                UnaryOp::Not => "!",
+            };
+            format!("({}{})", symbol, format_expr(expr))
+        }
+        Expr::Call { func, args, .. } => {
+            let args: Vec<String> = args.iter().map(format_expr).collect();
+            format!("{}({})", format_expr(func), args.join(", "))
+        }
+        Expr::Index { object, index } => format!("{}[{}]", format_expr(object), format_expr(index)),
+        Expr::Member { object, member } => format!("{}.{}", format_expr(object), member),
+        Expr::Slice { object, start, end } => {
+            let start = start.as_ref().map_or(String::new(), |e| format_expr(e));
+            let end = end.as_ref().map_or(String::new(), |e| format_expr(e));
+            format!("{}[{}:{}]", format_expr(object), start, end)
+        }
+        Expr::Compare { first, rest } => {
+            let mut text = format!("({}", format_expr(first));
+            for (op, operand) in rest {
+                text.push_str(&format!(" {} {}", op.symbol(), format_expr(operand)));
+            }
+            text.push(')');
+            text
+        }
+        Expr::Ternary { cond, then, otherwise } => {
+            format!("({} ? {} : {})", format_expr(cond), format_expr(then), format_expr(otherwise))
+        }
+        Expr::Update { target, increment, prefix } => {
+            let symbol = if *increment { "++" } else { "--" };
+            if *prefix {
+                format!("({}{})", symbol, target)
+            } else {
+                format!("({}{})", target, symbol)
+            }
+        }
+    }
+}
\ No newline at end of file
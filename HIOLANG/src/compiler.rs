@@ -7,6 +7,7 @@ pub enum BytecodeOp {
     PushFloat(f64),
     PushString(String),
     PushBool(bool),
+    PushNull,
     Pop,
     
     // Arithmetic
@@ -14,6 +15,7 @@ pub enum BytecodeOp {
     Subtract,
     Multiply,
     Divide,
+    IntDivide,
     Modulo,
     
     // Logic
@@ -29,16 +31,24 @@ pub enum BytecodeOp {
     Negate,
     
     // Variables
-    GetLocal(String),
+    GetLocal(usize), // slot in the current function's frame
     GetGlobal(String),
-    SetLocal(String),
+    SetLocal(usize),
     SetGlobal(String),
     
     // Control flow
     JumpIfFalse(usize),
     Jump(usize),
-    Call(String, usize), // function name, arg count
+    Call(String, usize), // function name, arg count (builtins and unresolved names)
+    CallAddr(usize, usize), // linked function entry offset, arg count
+    Enter(usize, usize), // first op of a function: parameter count, slot count; moves the arguments into slots 0..params
     Return,
+    Halt,
+    
+    // Errors
+    TryBegin(usize), // handler offset; a throw pushes the error value and jumps there
+    TryEnd, // drops the innermost handler; returning from a function drops that frame's handlers
+    Throw,
     
     // IO
     Print,
@@ -46,14 +56,61 @@ pub enum BytecodeOp {
     
     // Array/Object
     ArrayCreate(usize),
-    ObjectCreate(usize),
+    SetCreate(usize), // element count; equal elements after the first are dropped
+    ObjectCreate(usize), // entry count; each entry is a key then a value on the stack
     Index,
+    Slice(bool, bool), // has start, has end
+    IterItems, // object -> array of its keys; arrays pass through
     Member(String),
 }
 
+// Jump sites for `break`/`continue` in a loop that is still being compiled
+struct LoopContext {
+    label: Option<String>,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    try_depth: usize, // handlers active outside the loop, which a jump out of it keeps
+}
+
+// Slots of the function being compiled. Parameters take the first slots and every local
+// declared in the body gets a new one; names declared in a block go out of scope with it.
+// Top-level code has a frame with no outer scope: its own declarations are globals, and
+// only those made inside its blocks and loops get slots.
+struct Frame {
+    scopes: Vec<std::collections::HashMap<String, usize>>,
+    slot_count: usize,
+}
+
+// The result of compiling a whole program
+#[derive(Debug, Clone)]
+pub struct CompiledModule {
+    // Entry code ending in Halt, followed by every function body with calls resolved to CallAddr
+    pub main: Vec<BytecodeOp>,
+    // Each function body on its own; its jumps are relative to the body's first instruction.
+    // An overload whose parameter count differs from the first definition is keyed `name/arity`.
+    pub functions: std::collections::HashMap<String, Vec<BytecodeOp>>,
+    // Where each function body starts in `main`
+    pub symbols: std::collections::HashMap<String, usize>,
+}
+
 pub struct Compiler {
     bytecode: Vec<BytecodeOp>,
     functions: std::collections::HashMap<String, Vec<BytecodeOp>>,
+    symbols: std::collections::HashMap<String, usize>,
+    optimize: bool,
+    warnings: Vec<String>,
+    temp_counter: usize,
+    loops: Vec<LoopContext>,
+    constants: std::collections::HashSet<String>,
+    try_depth: usize,
+    frame: Option<Frame>, // None at the top level, where variables are globals
+    spaces: std::collections::HashSet<String>, // spaces declared so far, which `inspace` may name
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Compiler {
@@ -61,68 +118,156 @@ impl Compiler {
         Compiler {
             bytecode: Vec::new(),
             functions: std::collections::HashMap::new(),
+            symbols: std::collections::HashMap::new(),
+            optimize: true,
+            warnings: Vec::new(),
+            temp_counter: 0,
+            loops: Vec::new(),
+            constants: std::collections::HashSet::new(),
+            try_depth: 0,
+            frame: None,
+            spaces: std::collections::HashSet::new(),
         }
     }
     
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.optimize = enabled;
+    }
+    
     pub fn compile(&mut self, program: &Program) -> Result<Vec<BytecodeOp>, String> {
-        for stmt in &program.statements {
-            self.compile_stmt(stmt)?;
-        }
-        Ok(self.bytecode.clone())
+        self.compile_program(program).map(|module| module.main)
     }
     
-    fn compile_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
-        match stmt {
-            Stmt::Expression(expr) => {
-                self.compile_expr(expr)?;
-                self.bytecode.push(BytecodeOp::Pop);
+    pub fn compile_program(&mut self, program: &Program) -> Result<CompiledModule, String> {
+        self.frame = Some(Frame { scopes: Vec::new(), slot_count: 0 });
+        self.bytecode.push(BytecodeOp::Enter(0, 0)); // slot count patched below
+        let result = self.compile_block(&program.statements);
+        let main_frame = self.frame.take().expect("main frame");
+        result?;
+        if main_frame.slot_count > 0 {
+            self.bytecode[0] = BytecodeOp::Enter(0, main_frame.slot_count);
+        } else {
+            // No block-scoped locals, so main needs no frame
+            self.bytecode.remove(0);
+            for op in self.bytecode.iter_mut() {
+                if let BytecodeOp::Jump(addr) | BytecodeOp::JumpIfFalse(addr) | BytecodeOp::TryBegin(addr) = op {
+                    *addr -= 1;
+                }
             }
-            Stmt::Let { name, value } => {
-                self.compile_expr(value)?;
-                self.bytecode.push(BytecodeOp::SetLocal(name.clone()));
+        }
+        // Stop main before it falls through into the linked function bodies
+        self.bytecode.push(BytecodeOp::Halt);
+        if self.optimize {
+            // Each unit still has its own offsets here, so no relocated jump needs fixing up
+            self.bytecode = peephole(&self.bytecode);
+            for body in self.functions.values_mut() {
+                *body = peephole(body);
             }
-            Stmt::Assign { target, value } => {
-                self.compile_expr(value)?;
-                self.bytecode.push(BytecodeOp::SetGlobal(target.clone()));
+        }
+        self.link()?;
+        verify(&self.bytecode).map_err(|e| format!("Internal compiler error: {}", e))?;
+        Ok(CompiledModule {
+            main: self.bytecode.clone(),
+            functions: self.functions.clone(),
+            symbols: self.symbols.clone(),
+        })
+    }
+    
+    fn link(&mut self) -> Result<(), String> {
+        let mut names: Vec<String> = self.functions.keys().cloned().collect();
+        names.sort();
+        
+        for name in names {
+            let base = self.bytecode.len();
+            self.symbols.insert(name.clone(), base);
+            for op in &self.functions[&name] {
+                // Function bodies were compiled from offset 0, so relocate their jumps
+                let relocated = match op {
+                    BytecodeOp::Jump(addr) => BytecodeOp::Jump(addr + base),
+                    BytecodeOp::JumpIfFalse(addr) => BytecodeOp::JumpIfFalse(addr + base),
+                    BytecodeOp::TryBegin(addr) => BytecodeOp::TryBegin(addr + base),
+                    other => other.clone(),
+                };
+                self.bytecode.push(relocated);
             }
-            Stmt::If { condition, then_branch, else_branch } => {
-                self.compile_expr(condition)?;
-                let jump_if_false_idx = self.bytecode.len();
-                self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
-                
-                for s in then_branch {
-                    self.compile_stmt(s)?;
-                }
-                
-                let jump_idx = self.bytecode.len();
-                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
-                
-                let false_target = self.bytecode.len();
-                if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
-                    *addr = false_target;
-                }
-                
-                if let Some(else_stmts) = else_branch {
-                    for s in else_stmts {
-                        self.compile_stmt(s)?;
+        }
+        
+        for i in 0..self.bytecode.len() {
+            if let BytecodeOp::Call(name, argc) = &self.bytecode[i] {
+                let overload = format!("{}/{}", name, argc);
+                let addr = match self.symbols.get(&overload) {
+                    Some(&addr) => addr,
+                    None => match self.symbols.get(name) {
+                        Some(&addr) => addr,
+                        None => continue,
+                    },
+                };
+                // Every body starts with Enter, so check the call against its parameter count
+                if let BytecodeOp::Enter(params, _) = self.bytecode[addr] {
+                    if params != *argc {
+                        let arities = self.arities(name);
+                        if arities.len() == 1 {
+                            return Err(format!("{}() expects {} arguments, got {}", name, params, argc));
+                        }
+                        let arities: Vec<String> = arities.iter().map(|n| n.to_string()).collect();
+                        return Err(format!("{}() has no overload taking {} arguments (defined for {})", name, argc, arities.join(", ")));
                     }
                 }
-                
-                let end_target = self.bytecode.len();
-                if let BytecodeOp::Jump(ref mut addr) = &mut self.bytecode[jump_idx] {
-                    *addr = end_target;
-                }
+                self.bytecode[i] = BytecodeOp::CallAddr(addr, *argc);
             }
-            Stmt::While { condition, body } => {
+        }
+        Ok(())
+    }
+    
+    // The parameter count of a compiled function body
+    fn arity_of(body: &[BytecodeOp]) -> Option<usize> {
+        match body.first() {
+            Some(BytecodeOp::Enter(params, _)) => Some(*params),
+            _ => None,
+        }
+    }
+    
+    // The parameter counts `name` is defined for, in ascending order
+    fn arities(&self, name: &str) -> Vec<usize> {
+        let prefix = format!("{}/", name);
+        let mut arities: Vec<usize> = self.functions.iter()
+            .filter(|(key, _)| key.as_str() == name || key.starts_with(&prefix))
+            .filter_map(|(_, body)| Self::arity_of(body))
+            .collect();
+        arities.sort();
+        arities
+    }
+    
+    // Where a definition of `name` with `arity` parameters goes in the function table: under the
+    // plain name unless that already holds a different overload
+    fn function_key(&self, name: &str, arity: usize) -> String {
+        match self.functions.get(name).and_then(|body| Self::arity_of(body)) {
+            Some(existing) if existing != arity => format!("{}/{}", name, arity),
+            _ => name.to_string(),
+        }
+    }
+    
+    fn begin_loop(&mut self, label: Option<&str>) {
+        self.loops.push(LoopContext {
+            label: label.map(str::to_string),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            try_depth: self.try_depth,
+        });
+    }
+    
+    // Compiles a loop statement; `label` is set when it was written as `label: while (...)`
+    fn compile_loop(&mut self, stmt: &Stmt, label: Option<&str>) -> Result<(), String> {
+        match stmt {
+            Stmt::While { condition, body, else_branch, .. } => {
                 let loop_start = self.bytecode.len();
                 
                 self.compile_expr(condition)?;
                 let jump_if_false_idx = self.bytecode.len();
                 self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
                 
-                for s in body {
-                    self.compile_stmt(s)?;
-                }
+                self.begin_loop(label);
+                self.compile_scope(body)?;
                 
                 self.bytecode.push(BytecodeOp::Jump(loop_start));
                 
@@ -130,8 +275,31 @@ impl Compiler {
                 if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
                     *addr = loop_end;
                 }
+                
+                // The else branch follows a natural exit; `break` jumps past it
+                if let Some(else_stmts) = else_branch {
+                    self.compile_scope(else_stmts)?;
+                }
+                let break_target = self.bytecode.len();
+                self.end_loop(loop_start, break_target);
             }
-            Stmt::For { init, condition, increment, body } => {
+            Stmt::DoWhile { body, condition, .. } => {
+                let loop_start = self.bytecode.len();
+                self.begin_loop(label);
+                self.compile_scope(body)?;
+                
+                // Loop back while the condition holds: jump when `!condition` is false
+                let condition_start = self.bytecode.len();
+                self.compile_expr(condition)?;
+                self.bytecode.push(BytecodeOp::Not);
+                self.bytecode.push(BytecodeOp::JumpIfFalse(loop_start));
+                
+                let break_target = self.bytecode.len();
+                self.end_loop(condition_start, break_target);
+            }
+            Stmt::For { init, condition, increment, body, else_branch, .. } => {
+                // The loop variable lives in its own scope around the whole loop
+                self.enter_scope();
                 if let Some(init_stmt) = init {
                     self.compile_stmt(init_stmt)?;
                 }
@@ -147,10 +315,11 @@ impl Compiler {
                 let jump_if_false_idx = self.bytecode.len();
                 self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
                 
-                for s in body {
-                    self.compile_stmt(s)?;
-                }
+                self.begin_loop(label);
+                self.compile_scope(body)?;
                 
+                // `continue` still runs the increment
+                let increment_start = self.bytecode.len();
                 if let Some(inc) = increment {
                     self.compile_expr(inc)?;
                     self.bytecode.push(BytecodeOp::Pop);
@@ -162,61 +331,420 @@ impl Compiler {
                 if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
                     *addr = loop_end;
                 }
+                
+                // The else branch follows a natural exit; `break` jumps past it
+                if let Some(else_stmts) = else_branch {
+                    self.compile_scope(else_stmts)?;
+                }
+                let break_target = self.bytecode.len();
+                self.end_loop(increment_start, break_target);
+                self.exit_scope();
+            }
+            Stmt::ForIn { var, iterable, body, .. } => {
+                // Lowered to an index loop over the items array
+                let items = format!("$items{}", self.temp_counter);
+                let index = format!("$index{}", self.temp_counter);
+                self.temp_counter += 1;
+                
+                self.compile_expr(iterable)?;
+                self.enter_scope();
+                let set_items = self.declare(&items);
+                let set_index = self.declare(&index);
+                let set_var = self.declare(var);
+                self.bytecode.push(BytecodeOp::IterItems);
+                self.bytecode.push(set_items);
+                self.bytecode.push(BytecodeOp::PushNumber(0));
+                self.bytecode.push(set_index.clone());
+                
+                let loop_start = self.bytecode.len();
+                self.bytecode.push(self.load(&index));
+                self.bytecode.push(self.load(&items));
+                self.bytecode.push(BytecodeOp::Call("len".to_string(), 1));
+                self.bytecode.push(BytecodeOp::Less);
+                let jump_if_false_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
+                
+                self.bytecode.push(self.load(&items));
+                self.bytecode.push(self.load(&index));
+                self.bytecode.push(BytecodeOp::Index);
+                self.bytecode.push(set_var);
+                
+                self.begin_loop(label);
+                self.compile_scope(body)?;
+                
+                let increment_start = self.bytecode.len();
+                self.bytecode.push(self.load(&index));
+                self.bytecode.push(BytecodeOp::PushNumber(1));
+                self.bytecode.push(BytecodeOp::Add);
+                self.bytecode.push(set_index);
+                self.bytecode.push(BytecodeOp::Jump(loop_start));
+                
+                let loop_end = self.bytecode.len();
+                if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
+                    *addr = loop_end;
+                }
+                self.end_loop(increment_start, loop_end);
+                self.exit_scope();
+            }
+            _ => return self.compile_stmt(stmt),
+        }
+        Ok(())
+    }
+    
+    // Backpatches the innermost loop's `continue` and `break` placeholders
+    fn end_loop(&mut self, continue_target: usize, break_target: usize) {
+        let ctx = self.loops.pop().expect("end_loop without begin_loop");
+        for idx in ctx.continues {
+            self.bytecode[idx] = BytecodeOp::Jump(continue_target);
+        }
+        for idx in ctx.breaks {
+            self.bytecode[idx] = BytecodeOp::Jump(break_target);
+        }
+    }
+    
+    // The store for a new variable: a fresh slot in the current block inside a function or a
+    // top-level block, or a global directly at the top level. Redeclaring a name in the same block reuses its slot.
+    fn declare(&mut self, name: &str) -> BytecodeOp {
+        match &mut self.frame {
+            Some(frame) if !frame.scopes.is_empty() => {
+                let next = frame.slot_count;
+                let scope = frame.scopes.last_mut().expect("frame without a scope");
+                let slot = *scope.entry(name.to_string()).or_insert(next);
+                if slot == next {
+                    frame.slot_count += 1;
+                }
+                BytecodeOp::SetLocal(slot)
+            }
+            _ => BytecodeOp::SetGlobal(name.to_string()),
+        }
+    }
+    
+    // The innermost slot holding `name`; names a function doesn't declare are globals
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.frame.as_ref()?.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+    
+    fn load(&self, name: &str) -> BytecodeOp {
+        match self.resolve(name) {
+            Some(slot) => BytecodeOp::GetLocal(slot),
+            None => BytecodeOp::GetGlobal(name.to_string()),
+        }
+    }
+    
+    fn store(&self, name: &str) -> BytecodeOp {
+        match self.resolve(name) {
+            Some(slot) => BytecodeOp::SetLocal(slot),
+            None => BytecodeOp::SetGlobal(name.to_string()),
+        }
+    }
+    
+    fn enter_scope(&mut self) {
+        if let Some(frame) = &mut self.frame {
+            frame.scopes.push(std::collections::HashMap::new());
+        }
+    }
+    
+    fn exit_scope(&mut self) {
+        if let Some(frame) = &mut self.frame {
+            frame.scopes.pop();
+        }
+    }
+    
+    fn check_assignable(&self, target: &str) -> Result<(), String> {
+        if self.constants.contains(target) {
+            return Err(format!("Cannot assign to constant '{}'", target));
+        }
+        Ok(())
+    }
+    
+    // Like compile_block, but a `let` inside doesn't outlive the block's scope
+    fn compile_scope(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        let outer_constants = self.constants.clone();
+        self.enter_scope();
+        let result = self.compile_block(stmts);
+        self.exit_scope();
+        self.constants = outer_constants;
+        result
+    }
+    
+    // Compiles a statement list, dropping anything after a statement that always exits
+    fn compile_block(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.compile_stmt(stmt)?;
+            if self.optimize && always_exits(stmt) {
+                if i + 1 < stmts.len() {
+                    self.warnings.push(format!(
+                        "Unreachable code: {} statement(s) after {} removed",
+                        stmts.len() - i - 1,
+                        exit_kind(stmt)
+                    ));
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+    
+    fn compile_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expression(expr, _) => {
+                self.compile_expr(expr)?;
+                self.bytecode.push(BytecodeOp::Pop);
+            }
+            Stmt::Let { name, value, .. } => {
+                self.compile_expr(value)?;
+                let set = self.declare(name);
+                self.bytecode.push(set);
+                // A `let` shadows any constant of the same name
+                self.constants.remove(name);
+            }
+            Stmt::Const { name, value, .. } => {
+                self.compile_expr(value)?;
+                let set = self.declare(name);
+                self.bytecode.push(set);
+                self.constants.insert(name.clone());
+            }
+            Stmt::LetArray { names, rest, value, .. } => {
+                // Keep the array in a temporary and index into it once per name
+                let temp = format!("$destructure{}", self.temp_counter);
+                self.temp_counter += 1;
+                self.compile_expr(value)?;
+                let set_temp = self.declare(&temp);
+                self.bytecode.push(set_temp);
+                
+                for (i, name) in names.iter().enumerate() {
+                    self.bytecode.push(self.load(&temp));
+                    self.bytecode.push(BytecodeOp::PushNumber(i as i64));
+                    self.bytecode.push(BytecodeOp::Index);
+                    let set = self.declare(name);
+                    self.bytecode.push(set);
+                }
+                if let Some(r) = rest {
+                    self.bytecode.push(self.load(&temp));
+                    self.bytecode.push(BytecodeOp::PushNumber(names.len() as i64));
+                    self.bytecode.push(BytecodeOp::Slice(true, false));
+                    let set = self.declare(r);
+                    self.bytecode.push(set);
+                }
+            }
+            Stmt::Assign { target, value, .. } => {
+                self.check_assignable(target)?;
+                self.compile_expr(value)?;
+                self.bytecode.push(self.store(target));
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.compile_expr(condition)?;
+                let jump_if_false_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
+                
+                self.compile_scope(then_branch)?;
+                
+                let jump_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
+                
+                let false_target = self.bytecode.len();
+                if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
+                    *addr = false_target;
+                }
+                
+                if let Some(else_stmts) = else_branch {
+                    self.compile_scope(else_stmts)?;
+                }
+                
+                let end_target = self.bytecode.len();
+                if let BytecodeOp::Jump(ref mut addr) = &mut self.bytecode[jump_idx] {
+                    *addr = end_target;
+                }
+            }
+            Stmt::While { .. } | Stmt::DoWhile { .. } | Stmt::For { .. } | Stmt::ForIn { .. } => {
+                self.compile_loop(stmt, None)?;
             }
-            Stmt::FunctionDef { name, params, body } => {
+            Stmt::Labeled { label, body } => {
+                self.compile_loop(body, Some(label))?;
+            }
+            Stmt::FunctionDef { name, params, body, .. } => {
                 let mut func_bytecode = Vec::new();
                 std::mem::swap(&mut self.bytecode, &mut func_bytecode);
-                
-                for s in body {
-                    self.compile_stmt(s)?;
+                // Loops around the definition don't apply inside the body
+                let outer_loops = std::mem::take(&mut self.loops);
+                let outer_try_depth = std::mem::take(&mut self.try_depth);
+                // Parameters shadow constants; the body's own consts end with it
+                let outer_constants = self.constants.clone();
+                for param in params {
+                    self.constants.remove(param);
                 }
                 
+                // Parameter i is slot i; a repeated name refers to its last slot, as in the interpreter
+                let param_slots = params.iter().enumerate().map(|(i, p)| (p.clone(), i)).collect();
+                let outer_frame = self.frame.replace(Frame { scopes: vec![param_slots], slot_count: params.len() });
+                self.bytecode.push(BytecodeOp::Enter(params.len(), 0)); // slot count patched below
+                
+                let result = self.compile_block(body);
+                let frame = std::mem::replace(&mut self.frame, outer_frame).expect("function frame");
+                result?;
+                
                 self.bytecode.push(BytecodeOp::Return);
+                self.bytecode[0] = BytecodeOp::Enter(params.len(), frame.slot_count);
                 let func = self.bytecode.clone();
                 
                 std::mem::swap(&mut self.bytecode, &mut func_bytecode);
-                self.functions.insert(name.clone(), func);
+                self.loops = outer_loops;
+                self.try_depth = outer_try_depth;
+                self.constants = outer_constants;
+                let key = self.function_key(name, params.len());
+                self.functions.insert(key, func);
             }
-            Stmt::Return(expr) => {
+            Stmt::Return(expr, _) => {
                 if let Some(e) = expr {
                     self.compile_expr(e)?;
                 }
                 self.bytecode.push(BytecodeOp::Return);
             }
-            Stmt::Break | Stmt::Continue => {
-                // Handle in runtime for now
+            Stmt::Break(label) | Stmt::Continue(label) => {
+                // A labeled jump targets the nearest loop with that label, otherwise the innermost
+                let ctx_idx = match label {
+                    Some(l) => self.loops.iter().rposition(|ctx| ctx.label.as_ref() == Some(l)),
+                    None => self.loops.len().checked_sub(1),
+                }.ok_or_else(|| format!("'{}' outside of a loop", exit_kind(stmt)))?;
+                // Leave the handlers of any `try` between here and the loop
+                for _ in self.loops[ctx_idx].try_depth..self.try_depth {
+                    self.bytecode.push(BytecodeOp::TryEnd);
+                }
+                let idx = self.bytecode.len();
+                let ctx = &mut self.loops[ctx_idx];
+                if matches!(stmt, Stmt::Break(_)) {
+                    ctx.breaks.push(idx);
+                } else {
+                    ctx.continues.push(idx);
+                }
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder, patched by end_loop
+            }
+            Stmt::Throw(expr, _) => {
+                self.compile_expr(expr)?;
+                self.bytecode.push(BytecodeOp::Throw);
+            }
+            Stmt::Try { body, var, handler } => {
+                let try_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::TryBegin(0)); // placeholder
+                self.try_depth += 1;
+                let result = self.compile_scope(body);
+                self.try_depth -= 1;
+                result?;
+                self.bytecode.push(BytecodeOp::TryEnd);
+                let jump_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
+                
+                let handler_target = self.bytecode.len();
+                self.bytecode[try_idx] = BytecodeOp::TryBegin(handler_target);
+                // The error value is on the stack when the handler starts
+                let outer_constants = self.constants.clone();
+                self.enter_scope();
+                let set = self.declare(var);
+                self.bytecode.push(set);
+                self.constants.remove(var);
+                self.compile_block(handler)?;
+                self.exit_scope();
+                self.constants = outer_constants;
+                
+                let end_target = self.bytecode.len();
+                self.bytecode[jump_idx] = BytecodeOp::Jump(end_target);
+            }
+            Stmt::Space { name, body } => {
+                // Names stay globals so `inspace` can still reach them after the space ends
+                let outer_constants = self.constants.clone();
+                let result = self.compile_block(body);
+                self.constants = outer_constants;
+                result?;
+                self.spaces.insert(name.clone());
             }
-            Stmt::Space { body, .. } => {
-                for s in body {
-                    self.compile_stmt(s)?;
+            Stmt::Inspace { space, body } => {
+                if space != "writeutil" && !self.spaces.contains(space) {
+                    return Err(format!("Unknown space '{}'", space));
                 }
+                self.compile_scope(body)?;
             }
             Stmt::Pub { body, .. } => {
-                for s in body {
-                    self.compile_stmt(s)?;
-                }
+                self.compile_block(body)?;
             }
             Stmt::Subpub { body, .. } => {
-                for s in body {
-                    self.compile_stmt(s)?;
-                }
+                self.compile_block(body)?;
+            }
+            Stmt::Import(path) => {
+                return Err(format!("Cannot compile import of '{}'; imports are only supported by 'hiolang run'", path));
+            }
+            Stmt::Defer(_) => {
+                return Err("Cannot compile 'defer'; it is only supported by 'hiolang run'".to_string());
+            }
+            Stmt::With { .. } => {
+                return Err("Cannot compile 'with'; it is only supported by 'hiolang run'".to_string());
             }
             Stmt::Block(stmts) => {
-                for s in stmts {
-                    self.compile_stmt(s)?;
-                }
+                self.compile_scope(stmts)?;
             }
         }
         Ok(())
     }
     
     fn compile_expr(&mut self, expr: &Expr) -> Result<(), String> {
+        if self.optimize && matches!(expr, Expr::Binary { .. } | Expr::Unary { .. }) {
+            if let Some(folded) = fold_constant(expr) {
+                return self.compile_expr(&folded);
+            }
+        }
+        
         match expr {
             Expr::Number(n) => self.bytecode.push(BytecodeOp::PushNumber(*n)),
             Expr::Float(f) => self.bytecode.push(BytecodeOp::PushFloat(*f)),
             Expr::String(s) => self.bytecode.push(BytecodeOp::PushString(s.clone())),
             Expr::Boolean(b) => self.bytecode.push(BytecodeOp::PushBool(*b)),
-            Expr::Identifier(name) => self.bytecode.push(BytecodeOp::GetGlobal(name.clone())),
+            Expr::Null => self.bytecode.push(BytecodeOp::PushNull),
+            Expr::Identifier(name) => self.bytecode.push(self.load(name)),
+            Expr::Update { target, increment, prefix } => {
+                self.check_assignable(target)?;
+                // Postfix leaves the old value underneath; prefix reloads the new one
+                self.bytecode.push(self.load(target));
+                if !*prefix {
+                    self.bytecode.push(self.load(target));
+                }
+                self.bytecode.push(BytecodeOp::PushNumber(1));
+                self.bytecode.push(if *increment { BytecodeOp::Add } else { BytecodeOp::Subtract });
+                self.bytecode.push(self.store(target));
+                if *prefix {
+                    self.bytecode.push(self.load(target));
+                }
+            }
+            Expr::Binary { left, op: op @ (BinaryOp::And | BinaryOp::Or), right } => {
+                // The right operand only runs when the left one doesn't decide the result.
+                // `||` negates each operand, so its JumpIfFalse is taken on a truthy one.
+                let is_or = matches!(op, BinaryOp::Or);
+                let mut decided = Vec::new();
+                for operand in [left, right] {
+                    self.compile_expr(operand)?;
+                    if is_or {
+                        self.bytecode.push(BytecodeOp::Not);
+                    }
+                    decided.push(self.bytecode.len());
+                    self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
+                }
+                
+                // Neither operand decided it: true for `&&`, false for `||`
+                self.bytecode.push(BytecodeOp::PushBool(!is_or));
+                let jump_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
+                
+                let decided_target = self.bytecode.len();
+                for idx in decided {
+                    if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[idx] {
+                        *addr = decided_target;
+                    }
+                }
+                self.bytecode.push(BytecodeOp::PushBool(is_or));
+                
+                let end_target = self.bytecode.len();
+                if let BytecodeOp::Jump(ref mut addr) = &mut self.bytecode[jump_idx] {
+                    *addr = end_target;
+                }
+            }
             Expr::Binary { left, op, right } => {
                 self.compile_expr(left)?;
                 self.compile_expr(right)?;
@@ -225,6 +753,7 @@ impl Compiler {
                     BinaryOp::Subtract => BytecodeOp::Subtract,
                     BinaryOp::Multiply => BytecodeOp::Multiply,
                     BinaryOp::Divide => BytecodeOp::Divide,
+                    BinaryOp::IntDivide => BytecodeOp::IntDivide,
                     BinaryOp::Modulo => BytecodeOp::Modulo,
                     BinaryOp::Equal => BytecodeOp::Equal,
                     BinaryOp::NotEqual => BytecodeOp::NotEqual,
@@ -238,20 +767,40 @@ impl Compiler {
                 self.bytecode.push(bytecode_op);
             }
             Expr::Unary { op, expr } => {
-                self.compile_expr(expr)?;
-                let bytecode_op = match op {
-                    UnaryOp::Negate => BytecodeOp::Negate,
-                    UnaryOp::Not => BytecodeOp::Not,
-                };
-                self.bytecode.push(bytecode_op);
+                match (op, &**expr) {
+                    // Negative literals are a single push even without optimizations
+                    (UnaryOp::Negate, Expr::Number(n)) if n.checked_neg().is_some() => {
+                        self.bytecode.push(BytecodeOp::PushNumber(-n));
+                    }
+                    (UnaryOp::Negate, Expr::Float(f)) => self.bytecode.push(BytecodeOp::PushFloat(-f)),
+                    _ => {
+                        self.compile_expr(expr)?;
+                        let bytecode_op = match op {
+                            UnaryOp::Negate => BytecodeOp::Negate,
+                            UnaryOp::Not => BytecodeOp::Not,
+                        };
+                        self.bytecode.push(bytecode_op);
+                    }
+                }
             }
-            Expr::Call { func, args } => {
+            Expr::Call { func, args, line } => {
+                let name = match &**func {
+                    Expr::Identifier(name) => name.clone(),
+                    Expr::Member { object, member } if member == "text"
+                        && matches!(&**object, Expr::Identifier(ns) if ns == "writeutil") => {
+                        for arg in args {
+                            self.compile_expr(arg)?;
+                            self.bytecode.push(BytecodeOp::WriteUtil);
+                        }
+                        self.bytecode.push(BytecodeOp::PushNull);
+                        return Ok(());
+                    }
+                    _ => return Err(format!("Cannot compile call of a non-identifier expression at line {}", line)),
+                };
                 for arg in args {
                     self.compile_expr(arg)?;
                 }
-                if let Expr::Identifier(name) = &**func {
-                    self.bytecode.push(BytecodeOp::Call(name.clone(), args.len()));
-                }
+                self.bytecode.push(BytecodeOp::Call(name, args.len()));
             }
             Expr::Array(elements) => {
                 for elem in elements {
@@ -259,8 +808,31 @@ impl Compiler {
                 }
                 self.bytecode.push(BytecodeOp::ArrayCreate(elements.len()));
             }
+            Expr::Set(elements) => {
+                for elem in elements {
+                    self.compile_expr(elem)?;
+                }
+                self.bytecode.push(BytecodeOp::SetCreate(elements.len()));
+            }
+            Expr::Interpolated(parts) => {
+                // Each embedded value goes through to_string, then the pieces are added in order
+                for (i, part) in parts.iter().enumerate() {
+                    self.compile_expr(part)?;
+                    if !matches!(part, Expr::String(_)) {
+                        self.bytecode.push(BytecodeOp::Call("to_string".to_string(), 1));
+                    }
+                    if i > 0 {
+                        self.bytecode.push(BytecodeOp::Add);
+                    }
+                }
+            }
             Expr::Object(pairs) => {
-                for (_, val_expr) in pairs {
+                // Each entry pushes its key, then its value
+                for (key, val_expr) in pairs {
+                    match key {
+                        ObjectKey::Name(name) => self.bytecode.push(BytecodeOp::PushString(name.clone())),
+                        ObjectKey::Computed(expr) => self.compile_expr(expr)?,
+                    }
                     self.compile_expr(val_expr)?;
                 }
                 self.bytecode.push(BytecodeOp::ObjectCreate(pairs.len()));
@@ -274,6 +846,83 @@ impl Compiler {
                 self.compile_expr(object)?;
                 self.bytecode.push(BytecodeOp::Member(member.clone()));
             }
+            Expr::Slice { object, start, end } => {
+                self.compile_expr(object)?;
+                if let Some(s) = start {
+                    self.compile_expr(s)?;
+                }
+                if let Some(e) = end {
+                    self.compile_expr(e)?;
+                }
+                self.bytecode.push(BytecodeOp::Slice(start.is_some(), end.is_some()));
+            }
+            Expr::Compare { first, rest } => {
+                // Each middle operand is stored in a temporary so it is evaluated once
+                self.compile_expr(first)?;
+                let mut false_jumps = Vec::new();
+                for (i, (op, operand)) in rest.iter().enumerate() {
+                    let temp = if i + 1 < rest.len() {
+                        self.temp_counter += 1;
+                        Some(format!("$cmp{}", self.temp_counter - 1))
+                    } else {
+                        None
+                    };
+                    
+                    self.compile_expr(operand)?;
+                    if let Some(temp) = &temp {
+                        let set = self.declare(temp);
+                        self.bytecode.push(set);
+                        self.bytecode.push(self.load(temp));
+                    }
+                    self.bytecode.push(match op {
+                        BinaryOp::Less => BytecodeOp::Less,
+                        BinaryOp::LessEqual => BytecodeOp::LessEqual,
+                        BinaryOp::Greater => BytecodeOp::Greater,
+                        _ => BytecodeOp::GreaterEqual,
+                    });
+                    if let Some(temp) = temp {
+                        false_jumps.push(self.bytecode.len());
+                        self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
+                        self.bytecode.push(self.load(&temp));
+                    }
+                }
+                
+                let jump_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
+                let false_target = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::PushBool(false));
+                let end_target = self.bytecode.len();
+                
+                for idx in false_jumps {
+                    if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[idx] {
+                        *addr = false_target;
+                    }
+                }
+                if let BytecodeOp::Jump(ref mut addr) = &mut self.bytecode[jump_idx] {
+                    *addr = end_target;
+                }
+            }
+            Expr::Ternary { cond, then, otherwise } => {
+                self.compile_expr(cond)?;
+                let jump_if_false_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::JumpIfFalse(0)); // placeholder
+                
+                self.compile_expr(then)?;
+                let jump_idx = self.bytecode.len();
+                self.bytecode.push(BytecodeOp::Jump(0)); // placeholder
+                
+                let false_target = self.bytecode.len();
+                if let BytecodeOp::JumpIfFalse(ref mut addr) = &mut self.bytecode[jump_if_false_idx] {
+                    *addr = false_target;
+                }
+                
+                self.compile_expr(otherwise)?;
+                
+                let end_target = self.bytecode.len();
+                if let BytecodeOp::Jump(ref mut addr) = &mut self.bytecode[jump_idx] {
+                    *addr = end_target;
+                }
+            }
         }
         Ok(())
     }
@@ -285,4 +934,348 @@ impl Compiler {
     pub fn get_functions(&self) -> &std::collections::HashMap<String, Vec<BytecodeOp>> {
         &self.functions
     }
+    
+    pub fn get_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+    
+    pub fn get_symbols(&self) -> &std::collections::HashMap<String, usize> {
+        &self.symbols
+    }
 }
+
+
+// True if control never falls through to the statement after `stmt`
+fn always_exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(..) | Stmt::Break(_) | Stmt::Continue(_) | Stmt::Throw(..) => true,
+        Stmt::If { then_branch, else_branch: Some(else_stmts), .. } => {
+            then_branch.iter().any(always_exits) && else_stmts.iter().any(always_exits)
+        }
+        Stmt::Block(stmts) => stmts.iter().any(always_exits),
+        _ => false,
+    }
+}
+
+fn exit_kind(stmt: &Stmt) -> &'static str {
+    match stmt {
+        Stmt::Return(..) => "return",
+        Stmt::Break(_) => "break",
+        Stmt::Continue(_) => "continue",
+        Stmt::Throw(..) => "throw",
+        Stmt::If { .. } => "if/else where every branch exits",
+        _ => "block that always exits",
+    }
+}
+
+// Folds operations whose operands are all literals into a single literal.
+// Returns None when the expression is not constant or must be left to fail at runtime.
+fn fold_constant(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::Number(_) | Expr::Float(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Null => Some(expr.clone()),
+        Expr::Unary { op, expr } => {
+            match (op, fold_constant(expr)?) {
+                (UnaryOp::Negate, Expr::Number(n)) => n.checked_neg().map(Expr::Number),
+                (UnaryOp::Negate, Expr::Float(f)) => Some(Expr::Float(-f)),
+                (UnaryOp::Not, lit) => Some(Expr::Boolean(!literal_truthy(&lit))),
+                _ => None,
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = fold_constant(left)?;
+            let right = fold_constant(right)?;
+            fold_binary(&left, op, &right)
+        }
+        _ => None,
+    }
+}
+
+fn fold_binary(left: &Expr, op: &BinaryOp, right: &Expr) -> Option<Expr> {
+    match (left, op, right) {
+        (l, BinaryOp::And, r) => Some(Expr::Boolean(literal_truthy(l) && literal_truthy(r))),
+        (l, BinaryOp::Or, r) => Some(Expr::Boolean(literal_truthy(l) || literal_truthy(r))),
+        (Expr::Number(a), op, Expr::Number(b)) => {
+            let (a, b) = (*a, *b);
+            match op {
+                BinaryOp::Add => a.checked_add(b).map(Expr::Number),
+                BinaryOp::Subtract => a.checked_sub(b).map(Expr::Number),
+                BinaryOp::Multiply => a.checked_mul(b).map(Expr::Number),
+                BinaryOp::Divide if b != 0 => {
+                    if a.wrapping_rem(b) == 0 { a.checked_div(b).map(Expr::Number) } else { Some(Expr::Float(a as f64 / b as f64)) }
+                }
+                BinaryOp::IntDivide if b != 0 => a.checked_div(b).map(Expr::Number),
+                BinaryOp::Modulo if b != 0 => a.checked_rem(b).map(Expr::Number),
+                BinaryOp::Equal => Some(Expr::Boolean(a == b)),
+                BinaryOp::NotEqual => Some(Expr::Boolean(a != b)),
+                BinaryOp::Less => Some(Expr::Boolean(a < b)),
+                BinaryOp::LessEqual => Some(Expr::Boolean(a <= b)),
+                BinaryOp::Greater => Some(Expr::Boolean(a > b)),
+                BinaryOp::GreaterEqual => Some(Expr::Boolean(a >= b)),
+                _ => None,
+            }
+        }
+        (Expr::Number(_) | Expr::Float(_), op, Expr::Number(_) | Expr::Float(_)) => {
+            let (a, b) = (literal_float(left), literal_float(right));
+            match op {
+                BinaryOp::Add => Some(Expr::Float(a + b)),
+                BinaryOp::Subtract => Some(Expr::Float(a - b)),
+                BinaryOp::Multiply => Some(Expr::Float(a * b)),
+                BinaryOp::Divide if b != 0.0 => Some(Expr::Float(a / b)),
+                BinaryOp::IntDivide if b != 0.0 => Some(Expr::Float((a / b).trunc())),
+                BinaryOp::Modulo if b != 0.0 => Some(Expr::Float(a % b)),
+                // The same epsilon equality the interpreter uses
+                BinaryOp::Equal => Some(Expr::Boolean(floats_equal(a, b))),
+                BinaryOp::NotEqual => Some(Expr::Boolean(!floats_equal(a, b))),
+                BinaryOp::Less => Some(Expr::Boolean(a < b)),
+                BinaryOp::LessEqual => Some(Expr::Boolean(a <= b)),
+                BinaryOp::Greater => Some(Expr::Boolean(a > b)),
+                BinaryOp::GreaterEqual => Some(Expr::Boolean(a >= b)),
+                _ => None,
+            }
+        }
+        (Expr::String(a), BinaryOp::Add, Expr::String(b)) => Some(Expr::String(format!("{}{}", a, b))),
+        (Expr::String(a), BinaryOp::Equal, Expr::String(b)) => Some(Expr::Boolean(a == b)),
+        (Expr::String(a), BinaryOp::NotEqual, Expr::String(b)) => Some(Expr::Boolean(a != b)),
+        _ => None,
+    }
+}
+
+fn literal_float(expr: &Expr) -> f64 {
+    match expr {
+        Expr::Number(n) => *n as f64,
+        Expr::Float(f) => *f,
+        _ => 0.0,
+    }
+}
+
+// Removes pushes that are popped straight away and jumps to the next instruction,
+// repeating until nothing changes. Instructions that are jump targets are kept.
+fn peephole(ops: &[BytecodeOp]) -> Vec<BytecodeOp> {
+    let mut ops = ops.to_vec();
+    loop {
+        let mut targets = vec![false; ops.len() + 1];
+        for op in &ops {
+            if let BytecodeOp::Jump(t) | BytecodeOp::JumpIfFalse(t) | BytecodeOp::TryBegin(t) = op {
+                targets[*t] = true;
+            }
+        }
+        
+        let mut keep = vec![true; ops.len()];
+        let mut i = 0;
+        while i < ops.len() {
+            let pushed_then_popped = is_pure_push(&ops[i])
+                && matches!(ops.get(i + 1), Some(BytecodeOp::Pop))
+                && !targets[i]
+                && !targets[i + 1];
+            if pushed_then_popped {
+                keep[i] = false;
+                keep[i + 1] = false;
+                i += 2;
+                continue;
+            }
+            if matches!(ops[i], BytecodeOp::Jump(t) if t == i + 1) && !targets[i] {
+                keep[i] = false;
+            }
+            i += 1;
+        }
+        if keep.iter().all(|&k| k) {
+            return ops;
+        }
+        
+        // A jump to a removed instruction lands on the next one that survives
+        let mut new_index = Vec::with_capacity(ops.len() + 1);
+        let mut kept = 0;
+        for &k in &keep {
+            new_index.push(kept);
+            if k {
+                kept += 1;
+            }
+        }
+        new_index.push(kept);
+        
+        ops = ops.into_iter()
+            .zip(keep)
+            .filter(|(_, k)| *k)
+            .map(|(op, _)| match op {
+                BytecodeOp::Jump(t) => BytecodeOp::Jump(new_index[t]),
+                BytecodeOp::JumpIfFalse(t) => BytecodeOp::JumpIfFalse(new_index[t]),
+                BytecodeOp::TryBegin(t) => BytecodeOp::TryBegin(new_index[t]),
+                other => other,
+            })
+            .collect();
+    }
+}
+
+// First bytes of every compiled file
+pub const BYTECODE_MAGIC: &[u8; 4] = b"HIOB";
+// Bump whenever BytecodeOp gains, loses or changes an instruction, so old files are refused
+pub const BYTECODE_VERSION: u32 = 1;
+// Magic, version, instruction count and function count
+pub const BYTECODE_HEADER_LEN: usize = 4 + 4 + 8 + 8;
+
+// What the header at the start of a compiled file says about the module after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytecodeHeader {
+    pub version: u32,
+    pub instructions: u64, // length of the linked `main` code
+    pub functions: u64,
+}
+
+// The header for `module`; integers are little-endian
+pub fn write_header(module: &CompiledModule) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(BYTECODE_HEADER_LEN);
+    bytes.extend_from_slice(BYTECODE_MAGIC);
+    bytes.extend_from_slice(&BYTECODE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(module.main.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(module.functions.len() as u64).to_le_bytes());
+    bytes
+}
+
+/// Reads the header a compiled file starts with, returning it and the bytes after it. A file
+/// that isn't bytecode, is cut short, or was written for another instruction set is refused.
+///
+/// ```
+/// use hiolang::compiler::{read_header, write_header, Compiler, BYTECODE_VERSION};
+///
+/// let module = Compiler::new().compile_program(&hiolang::parse("function f() {} f();").unwrap()).unwrap();
+/// let mut file = write_header(&module);
+/// file.extend_from_slice(b"code");
+///
+/// let (header, rest) = read_header(&file).unwrap();
+/// assert_eq!((header.version, header.instructions, header.functions), (BYTECODE_VERSION, module.main.len() as u64, 1));
+/// assert_eq!(rest, b"code");
+///
+/// assert!(read_header(&file[..10]).unwrap_err().contains("truncated"));
+/// assert!(read_header(b"").unwrap_err().contains("truncated"));
+/// assert!(read_header(b"symbols: {}\ncode: []").unwrap_err().contains("magic"));
+/// file[4] += 1;
+/// assert!(read_header(&file).unwrap_err().contains("version 2"));
+/// ```
+pub fn read_header(bytes: &[u8]) -> Result<(BytecodeHeader, &[u8]), String> {
+    let magic_len = bytes.len().min(BYTECODE_MAGIC.len());
+    if bytes[..magic_len] != BYTECODE_MAGIC[..magic_len] {
+        return Err("Not a Hiolang bytecode file: bad magic bytes".to_string());
+    }
+    if bytes.len() < BYTECODE_HEADER_LEN {
+        return Err(format!("Bytecode file is truncated: the header needs {} bytes, got {}", BYTECODE_HEADER_LEN, bytes.len()));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().expect("4 bytes"));
+    if version != BYTECODE_VERSION {
+        return Err(format!("Unsupported bytecode format version {} (this build reads version {})", version, BYTECODE_VERSION));
+    }
+    let header = BytecodeHeader {
+        version,
+        instructions: u64::from_le_bytes(bytes[8..16].try_into().expect("8 bytes")),
+        functions: u64::from_le_bytes(bytes[16..24].try_into().expect("8 bytes")),
+    };
+    Ok((header, &bytes[BYTECODE_HEADER_LEN..]))
+}
+
+// Checks that linked bytecode is well-formed: every jump lands inside the code, and every
+// path through it keeps a consistent, non-negative stack depth. Main starts with an empty
+// stack and must leave it empty at Halt; a function starts with its arguments on the stack
+// and may leave at most its return value behind.
+pub fn verify(code: &[BytecodeOp]) -> Result<(), String> {
+    let fail = |idx: usize, msg: String| Err(format!("Invalid bytecode at instruction {} ({:?}): {}", idx, code[idx], msg));
+    
+    let mut entries = vec![(0, 0)];
+    for (idx, op) in code.iter().enumerate() {
+        match op {
+            BytecodeOp::Jump(t) | BytecodeOp::JumpIfFalse(t) | BytecodeOp::TryBegin(t) | BytecodeOp::CallAddr(t, _)
+                if *t >= code.len() =>
+            {
+                return fail(idx, format!("target {} is outside the code (length {})", t, code.len()));
+            }
+            BytecodeOp::CallAddr(addr, argc) => entries.push((*addr, *argc)),
+            _ => {}
+        }
+    }
+    if code.is_empty() {
+        return Err("Invalid bytecode: no instructions".to_string());
+    }
+    
+    // Depth before each instruction, filled in as paths reach it
+    let mut depth_at: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = entries;
+    while let Some((idx, depth)) = pending.pop() {
+        if idx >= code.len() {
+            return fail(code.len() - 1, "execution runs past the last instruction".to_string());
+        }
+        match depth_at[idx] {
+            Some(seen) if seen == depth => continue,
+            Some(seen) => return fail(idx, format!("reached with stack depth {} and {}", seen, depth)),
+            None => depth_at[idx] = Some(depth),
+        }
+        
+        let op = &code[idx];
+        let (pops, pushes) = stack_effect(op);
+        if depth < pops {
+            return fail(idx, format!("needs {} value(s) but the stack holds {}", pops, depth));
+        }
+        let after = depth - pops + pushes;
+        match op {
+            BytecodeOp::Halt if after != 0 => {
+                return fail(idx, format!("program ends with {} value(s) left on the stack", after));
+            }
+            BytecodeOp::Return if after > 1 => {
+                return fail(idx, format!("function returns with {} values on the stack", after));
+            }
+            BytecodeOp::Halt | BytecodeOp::Return | BytecodeOp::Throw => {}
+            BytecodeOp::Jump(t) => pending.push((*t, after)),
+            BytecodeOp::JumpIfFalse(t) => {
+                pending.push((*t, after));
+                pending.push((idx + 1, after));
+            }
+            // The handler starts with the stack as it was here, plus the error value
+            BytecodeOp::TryBegin(handler) => {
+                pending.push((*handler, after + 1));
+                pending.push((idx + 1, after));
+            }
+            _ => pending.push((idx + 1, after)),
+        }
+    }
+    Ok(())
+}
+
+// How many values an instruction pops and then pushes
+fn stack_effect(op: &BytecodeOp) -> (usize, usize) {
+    match op {
+        BytecodeOp::PushNumber(_) | BytecodeOp::PushFloat(_) | BytecodeOp::PushString(_)
+        | BytecodeOp::PushBool(_) | BytecodeOp::PushNull
+        | BytecodeOp::GetLocal(_) | BytecodeOp::GetGlobal(_) => (0, 1),
+        BytecodeOp::Pop | BytecodeOp::SetLocal(_) | BytecodeOp::SetGlobal(_)
+        | BytecodeOp::JumpIfFalse(_) | BytecodeOp::Print | BytecodeOp::WriteUtil | BytecodeOp::Throw => (1, 0),
+        BytecodeOp::Add | BytecodeOp::Subtract | BytecodeOp::Multiply | BytecodeOp::Divide
+        | BytecodeOp::IntDivide | BytecodeOp::Modulo
+        | BytecodeOp::Equal | BytecodeOp::NotEqual | BytecodeOp::Less | BytecodeOp::LessEqual
+        | BytecodeOp::Greater | BytecodeOp::GreaterEqual | BytecodeOp::And | BytecodeOp::Or
+        | BytecodeOp::Index => (2, 1),
+        BytecodeOp::Not | BytecodeOp::Negate | BytecodeOp::IterItems | BytecodeOp::Member(_) => (1, 1),
+        BytecodeOp::Call(_, argc) | BytecodeOp::CallAddr(_, argc) => (*argc, 1),
+        BytecodeOp::Enter(params, _) => (*params, 0),
+        BytecodeOp::ArrayCreate(n) | BytecodeOp::SetCreate(n) => (*n, 1),
+        BytecodeOp::ObjectCreate(n) => (n * 2, 1),
+        BytecodeOp::Slice(start, end) => (1 + *start as usize + *end as usize, 1),
+        BytecodeOp::Jump(_) | BytecodeOp::Return | BytecodeOp::Halt
+        | BytecodeOp::TryBegin(_) | BytecodeOp::TryEnd => (0, 0),
+    }
+}
+
+// Instructions that only push a value, so dropping them together with a Pop changes nothing
+fn is_pure_push(op: &BytecodeOp) -> bool {
+    matches!(op,
+        BytecodeOp::PushNumber(_) | BytecodeOp::PushFloat(_) | BytecodeOp::PushString(_)
+        | BytecodeOp::PushBool(_) | BytecodeOp::PushNull
+        | BytecodeOp::GetLocal(_) | BytecodeOp::GetGlobal(_))
+}
+
+// Mirrors Value::is_truthy for literal expressions
+fn literal_truthy(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(n) => *n != 0,
+        Expr::String(s) => !s.is_empty(),
+        Expr::Boolean(b) => *b,
+        Expr::Null => false,
+        _ => true,
+    }
+}
\ No newline at end of file
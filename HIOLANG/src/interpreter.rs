@@ -1,150 +1,437 @@
 use crate::ast::*;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::io::{Read, Write};
+
+const BUILTINS: &[&str] = &[
+    "print", "println", "printf", "eprint", "len", "type", "deep_merge", "read_line", "read_number",
+    "map", "filter", "reduce", "format", "get", "assert", "keys", "values", "text", "clone", "args", "env",
+    "abs", "min", "max", "sqrt", "pow", "floor", "ceil", "round", "format_float", "to_json", "from_json",
+    "sort", "reverse", "contains", "index_of",
+    "to_number", "to_bool", "to_string", "is_nan",
+    "to_hex", "to_bin", "to_oct", "parse_radix", "range", "to_array", "exit", "panic",
+    "char_code", "from_char_code", "sizeof", "union", "intersect", "difference",
+    "zip", "enumerate", "wrapping_add", "wrapping_sub", "wrapping_mul", "set_int_mode",
+    "open", "read", "write", "close",
+];
+
+// Functions written in Hiolang that every interpreter defines before running a program
+const PRELUDE: &str = include_str!("prelude.hio");
+
+// A pending `break`/`continue`, with the label of the loop it targets (None for the innermost)
+enum LoopSignal {
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+// A variable slot; `constant` marks names declared with `const`
+#[derive(Debug, Clone)]
+struct Binding {
+    value: Value,
+    constant: bool,
+}
+
+impl Binding {
+    fn new(value: Value) -> Self {
+        Binding { value, constant: false }
+    }
+}
+
+// One level of variable bindings
+type Scope = Rc<RefCell<HashMap<String, Binding>>>;
+
+// One definition in the function table; a name may have one per arity
+#[derive(Clone)]
+struct UserFunction {
+    params: Vec<String>,
+    body: Rc<Vec<Stmt>>,
+    file_scope: Option<Scope>, // top-level scope of the file it was imported from
+    line: usize, // where it was defined
+}
+
+// What a finished space left visible to `inspace`
+struct SpaceExports {
+    parent: Option<String>, // the space it was declared in; None at the top level
+    public: HashMap<String, Binding>, // defined in its `pub` blocks
+    internal: HashMap<String, Binding>, // defined in its `subpub` blocks
+}
+
+// A `defer`red statement and the scopes it was written in, which it runs in
+struct Deferred {
+    stmt: Stmt,
+    locals: Vec<Scope>,
+}
+
+// Names a space's `pub` and `subpub` blocks have defined while its body runs
+struct SpaceFrame {
+    name: String,
+    public: Vec<String>,
+    internal: Vec<String>,
+}
 
 pub struct Interpreter {
-    globals: Rc<RefCell<HashMap<String, Value>>>,
-    locals: Vec<Rc<RefCell<HashMap<String, Value>>>>,
+    globals: Rc<RefCell<HashMap<String, Binding>>>,
+    locals: Vec<Rc<RefCell<HashMap<String, Binding>>>>,
+    functions: HashMap<String, Vec<UserFunction>>, // overloads by arity, in definition order
     return_value: Option<Value>,
-    break_flag: bool,
-    continue_flag: bool,
+    loop_signal: Option<LoopSignal>,
+    thrown: Option<Value>, // the value of a `throw` while its error unwinds to a `catch`
+    string_coercion: bool, // lets `+` stringify the other operand when one side is a string
+    wrapping: bool, // integer operators wrap around at 64 bits instead of reporting overflow; set_int_mode("wrap")
+    script_args: Vec<String>, // command-line arguments after the script name, returned by args()
+    current_line: usize, // line of the call being dispatched, recorded in stack traces
+    stmt_line: usize, // line of the statement running, named when an error starts inside a function
+    statements_executed: u64,
+    call_depth: usize, // user function calls currently running
+    max_call_depth: usize,
+    stack_base: usize, // address of the outermost running call's frame, to measure native stack use from
+    max_stack_size: usize, // native stack bytes nested calls may use
+    profile: Option<HashMap<String, u64>>, // calls per user function, only while profiling
+    spaces: HashMap<String, SpaceExports>,
+    space_stack: Vec<SpaceFrame>, // spaces whose bodies are running, innermost last
+    source_path: Option<PathBuf>, // file being run, which relative imports resolve against
+    imported: HashMap<PathBuf, HashMap<String, Binding>>, // exports of each imported file, which runs only once
+    import_stack: Vec<PathBuf>, // the running file and the imports in progress, to report cycles
+    file_scope: Option<Scope>, // top-level scope of the file being imported
+    deferred: Vec<Vec<Deferred>>, // statements to run when each running call, import or program ends
+    exit_code: Option<i32>, // set by exit() or panic(); the error it raises can't be caught
+    files: HashMap<usize, std::fs::File>, // files opened by open() and not yet closed, by handle id
+    next_file_id: usize,
+    output: Box<dyn Write>, // where print, println, printf and text write; stdout unless redirected
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        let mut interpreter = Self::without_prelude();
+        interpreter.load_prelude();
+        interpreter
+    }
+    
+    // Only the native builtins, without the functions the prelude defines
+    pub fn without_prelude() -> Self {
         let mut globals = HashMap::new();
         
         // Add built-in functions
-        globals.insert("print".to_string(), Value::String("builtin:print".to_string()));
-        globals.insert("len".to_string(), Value::String("builtin:len".to_string()));
-        globals.insert("type".to_string(), Value::String("builtin:type".to_string()));
+        for name in BUILTINS {
+            globals.insert(name.to_string(), Binding::new(Value::Builtin(name)));
+        }
         
         Interpreter {
             globals: Rc::new(RefCell::new(globals)),
             locals: Vec::new(),
+            functions: HashMap::new(),
             return_value: None,
-            break_flag: false,
-            continue_flag: false,
+            loop_signal: None,
+            thrown: None,
+            string_coercion: true,
+            wrapping: false,
+            script_args: Vec::new(),
+            current_line: 0,
+            stmt_line: 0,
+            statements_executed: 0,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_base: 0,
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            profile: None,
+            spaces: HashMap::new(),
+            space_stack: Vec::new(),
+            source_path: None,
+            imported: HashMap::new(),
+            import_stack: Vec::new(),
+            file_scope: None,
+            deferred: Vec::new(),
+            exit_code: None,
+            files: HashMap::new(),
+            next_file_id: 0,
+            output: Box::new(std::io::stdout()),
         }
     }
     
+    /// An interpreter whose `print`, `println`, `printf` and `text` output goes to `writer`
+    /// instead of stdout. `eprint` and `panic` still write to stderr.
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::io::Write;
+    /// use std::rc::Rc;
+    /// use hiolang::Interpreter;
+    ///
+    /// // A writer the test keeps a handle to after the interpreter owns it
+    /// #[derive(Clone, Default)]
+    /// struct Capture(Rc<RefCell<Vec<u8>>>);
+    ///
+    /// impl Write for Capture {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.borrow_mut().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let capture = Capture::default();
+    /// let mut interpreter = Interpreter::with_output(capture.clone());
+    /// let program = hiolang::parse(r#"println("a", 1); writeutil.text("b"); printf("{}!", 2);"#).unwrap();
+    /// interpreter.interpret(&program).unwrap();
+    /// assert_eq!(String::from_utf8(capture.0.take()).unwrap(), "a 1\nb2!");
+    /// ```
+    pub fn with_output(writer: impl Write + 'static) -> Self {
+        let mut interpreter = Self::new();
+        interpreter.output = Box::new(writer);
+        interpreter
+    }
+    
+    /// Defines the functions of the prelude, which is written in Hiolang, as globals: `square`,
+    /// `clamp`, `is_even` and `is_odd`. `new` does this already; an interpreter from
+    /// `without_prelude` has only the native builtins until it's called.
+    ///
+    /// ```
+    /// use hiolang::{Interpreter, Value};
+    ///
+    /// let program = hiolang::parse("square(4) + clamp(15, 0, 10);").unwrap();
+    /// assert_eq!(Interpreter::new().interpret(&program), Ok(Value::Number(26)));
+    ///
+    /// let mut bare = Interpreter::without_prelude();
+    /// assert!(bare.interpret(&program).is_err());
+    /// bare.load_prelude();
+    /// assert_eq!(bare.interpret(&program), Ok(Value::Number(26)));
+    /// ```
+    pub fn load_prelude(&mut self) {
+        let program = crate::parse(PRELUDE).expect("the prelude should parse");
+        self.interpret(&program).expect("the prelude should run");
+        // Its definitions don't count towards statements_executed()
+        self.statements_executed = 0;
+    }
+    
+    pub fn set_string_coercion(&mut self, enabled: bool) {
+        self.string_coercion = enabled;
+    }
+    
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+    
+    // The file the program was read from. Without one, imports resolve against the working directory.
+    pub fn set_source_path(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.import_stack = std::fs::canonicalize(&path).into_iter().collect();
+        self.source_path = Some(path);
+    }
+    
+    // The status the program asked to exit with through exit() or panic(). The interpreter
+    // only stops running; ending the process is up to the caller.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+    
+    // Statements run so far, counting each loop iteration's statements again
+    pub fn statements_executed(&self) -> u64 {
+        self.statements_executed
+    }
+    
+    // Starts counting calls to each user function for profile_report
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(HashMap::new);
+    }
+    
+    // Each called function with its call count, most called first (ties by name).
+    // Empty unless profiling is enabled.
+    pub fn profile_report(&self) -> Vec<(String, u64)> {
+        let mut report: Vec<(String, u64)> = self.profile.iter()
+            .flat_map(|counts| counts.iter().map(|(name, count)| (name.clone(), *count)))
+            .collect();
+        report.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+    
+    /// How many files opened by `open()` are still open. A `with` statement closes its file
+    /// when the body ends, whether it finished or failed.
+    ///
+    /// ```
+    /// use hiolang::Interpreter;
+    ///
+    /// let path = std::env::temp_dir().join("hiolang_open_files_doc.txt");
+    /// let source = format!(
+    ///     "with (f = call.open({:?}, \"w\")) {{ call.write(f, \"hi\"); throw \"stop\"; }}",
+    ///     path.display().to_string(),
+    /// );
+    /// let mut interpreter = Interpreter::new();
+    /// assert!(interpreter.interpret(&hiolang::parse(&source).unwrap()).is_err());
+    /// assert_eq!(interpreter.open_files(), 0);
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi");
+    /// std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn open_files(&self) -> usize {
+        self.files.len()
+    }
+    
+    // How deeply user functions may nest before a call fails with a stack overflow error
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+    
+    // How many bytes of native stack nested calls may use before failing the same way. The
+    // default suits a 2 MiB thread; embedders running on a larger stack can raise it.
+    pub fn set_max_stack_size(&mut self, bytes: usize) {
+        self.max_stack_size = bytes;
+    }
+    
+    pub fn globals_snapshot(&self) -> Vec<(String, Value)> {
+        let mut vars: Vec<(String, Value)> = self.globals.borrow()
+            .iter()
+            .map(|(k, b)| (k.clone(), b.value.clone()))
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        vars
+    }
+    
+    // The parameter lists of each overload of `name`
+    pub fn function_overloads(&self, name: &str) -> Vec<&[String]> {
+        self.functions.get(name)
+            .map(|overloads| overloads.iter().map(|f| f.params.as_slice()).collect())
+            .unwrap_or_default()
+    }
+    
     pub fn interpret(&mut self, program: &Program) -> Result<Value, String> {
-        let mut last_value = Value::Void;
+        // An uncaught throw from an earlier REPL line must not leak into this one
+        self.thrown = None;
+        self.exit_code = None;
         
+        self.deferred.push(Vec::new());
+        let mut result = Ok(Value::Void);
         for stmt in &program.statements {
-            last_value = self.execute_stmt(stmt)?;
-            if self.return_value.is_some() {
-                return Ok(self.return_value.take().unwrap_or(Value::Void));
+            result = self.execute_stmt(stmt);
+            if result.is_err() {
+                break;
             }
+            if let Some(value) = self.return_value.take() {
+                result = Ok(value);
+                break;
+            }
+        }
+        self.run_deferred(result)
+    }
+    
+    // Ends the innermost deferral frame, running its statements most recent first. Each runs even
+    // if an earlier one failed; the first error becomes the frame's result unless it had already failed.
+    fn run_deferred(&mut self, mut result: Result<Value, String>) -> Result<Value, String> {
+        let deferred = self.deferred.pop().unwrap_or_default();
+        // Exiting skips deferred statements, like an immediate process exit would
+        if deferred.is_empty() || self.exit_code.is_some() {
+            return result;
         }
         
-        Ok(last_value)
+        // A pending return or break belongs to the frame, not to the deferred statements
+        let return_value = self.return_value.take();
+        let loop_signal = self.loop_signal.take();
+        let mut thrown = self.thrown.take();
+        for Deferred { stmt, locals } in deferred.into_iter().rev() {
+            let frame_locals = std::mem::replace(&mut self.locals, locals);
+            let outcome = self.execute_stmt(&stmt);
+            self.locals = frame_locals;
+            self.return_value = None;
+            self.loop_signal = None;
+            if let Err(e) = outcome {
+                if result.is_ok() {
+                    result = Err(e);
+                    thrown = self.thrown.take();
+                }
+            }
+            self.thrown = None;
+        }
+        
+        self.thrown = thrown;
+        if result.is_ok() {
+            self.return_value = return_value;
+            self.loop_signal = loop_signal;
+        }
+        result
     }
     
     fn execute_stmt(&mut self, stmt: &Stmt) -> Result<Value, String> {
+        self.statements_executed += 1;
+        if let Some(line) = stmt.line() {
+            self.stmt_line = line;
+        }
         match stmt {
-            Stmt::Expression(expr) => self.evaluate_expr(expr),
-            Stmt::Let { name, value } => {
+            Stmt::Expression(expr, _) => self.evaluate_expr(expr),
+            Stmt::Let { name, value, .. } => {
+                let val = self.evaluate_expr(value)?;
+                self.define_variable(name.clone(), val.clone());
+                Ok(Value::Void)
+            }
+            Stmt::Const { name, value, .. } => {
                 let val = self.evaluate_expr(value)?;
-                self.set_variable(name.clone(), val.clone());
+                self.define_binding(name.clone(), Binding { value: val, constant: true });
+                Ok(Value::Void)
+            }
+            Stmt::LetArray { names, rest, value, .. } => {
+                let items = match self.evaluate_expr(value)? {
+                    Value::Array(items) => items,
+                    other => return Err(format!("Cannot destructure {} as an array", other.type_name())),
+                };
+                
+                let length_ok = if rest.is_some() { items.len() >= names.len() } else { items.len() == names.len() };
+                if !length_ok {
+                    return Err(format!("Cannot destructure array of length {} into {} names", items.len(), names.len()));
+                }
+                
+                let mut items = items.into_iter();
+                for name in names {
+                    let item = items.next().unwrap_or(Value::Null);
+                    self.define_variable(name.clone(), item);
+                }
+                if let Some(r) = rest {
+                    self.define_variable(r.clone(), Value::Array(items.collect()));
+                }
                 Ok(Value::Void)
             }
-            Stmt::Assign { target, value } => {
+            Stmt::Assign { target, value, .. } => {
                 let val = self.evaluate_expr(value)?;
-                self.set_variable(target.clone(), val.clone());
+                self.set_variable(target.clone(), val.clone())?;
                 Ok(val)
             }
-            Stmt::If { condition, then_branch, else_branch } => {
+            Stmt::If { condition, then_branch, else_branch, .. } => {
                 let cond_value = self.evaluate_expr(condition)?;
                 if cond_value.is_truthy() {
-                    let mut result = Value::Void;
-                    for s in then_branch {
-                        result = self.execute_stmt(s)?;
-                        if self.return_value.is_some() || self.break_flag || self.continue_flag {
-                            break;
-                        }
-                    }
-                    Ok(result)
+                    self.execute_block(then_branch)
                 } else if let Some(else_stmts) = else_branch {
-                    let mut result = Value::Void;
-                    for s in else_stmts {
-                        result = self.execute_stmt(s)?;
-                        if self.return_value.is_some() || self.break_flag || self.continue_flag {
-                            break;
-                        }
-                    }
-                    Ok(result)
+                    self.execute_block(else_stmts)
                 } else {
                     Ok(Value::Void)
                 }
             }
-            Stmt::While { condition, body } => {
-                let mut result = Value::Void;
-                while self.evaluate_expr(condition)?.is_truthy() {
-                    for s in body {
-                        result = self.execute_stmt(s)?;
-                        if self.return_value.is_some() || self.break_flag || self.continue_flag {
-                            break;
-                        }
-                    }
-                    if self.break_flag {
-                        self.break_flag = false;
-                        break;
-                    }
-                    if self.continue_flag {
-                        self.continue_flag = false;
-                        continue;
-                    }
-                    if self.return_value.is_some() {
-                        break;
-                    }
-                }
-                Ok(result)
+            Stmt::While { .. } | Stmt::DoWhile { .. } | Stmt::For { .. } | Stmt::ForIn { .. } => {
+                self.execute_loop(stmt, None)
             }
-            Stmt::For { init, condition, increment, body } => {
-                if let Some(init_stmt) = init {
-                    self.execute_stmt(init_stmt)?;
-                }
-                
-                let mut result = Value::Void;
-                loop {
-                    if let Some(cond) = condition {
-                        if !self.evaluate_expr(cond)?.is_truthy() {
-                            break;
-                        }
-                    }
-                    
-                    for s in body {
-                        result = self.execute_stmt(s)?;
-                        if self.return_value.is_some() || self.break_flag || self.continue_flag {
-                            break;
-                        }
-                    }
-                    
-                    if self.break_flag {
-                        self.break_flag = false;
-                        break;
-                    }
-                    if self.continue_flag {
-                        self.continue_flag = false;
-                    } else if self.return_value.is_some() {
-                        break;
-                    }
-                    
-                    if let Some(inc) = increment {
-                        self.evaluate_expr(inc)?;
-                    }
+            Stmt::Labeled { label, body } => self.execute_loop(body, Some(label)),
+            Stmt::FunctionDef { name, params, body, line } => {
+                // The variable holds a reference into the function table. Defining a name again
+                // with a new parameter count adds an overload; the same count replaces it.
+                let func = UserFunction {
+                    params: params.clone(),
+                    body: Rc::new(body.clone()),
+                    file_scope: self.file_scope.clone(),
+                    line: *line,
+                };
+                let overloads = self.functions.entry(name.clone()).or_default();
+                match overloads.iter_mut().find(|f| f.params.len() == params.len()) {
+                    Some(existing) => *existing = func,
+                    None => overloads.push(func),
                 }
-                Ok(result)
-            }
-            Stmt::FunctionDef { name, params, body } => {
-                // Store function definition as a special value
-                let func_def = format!("function:{}:{}", name, params.join(","));
-                self.set_variable(name.clone(), Value::String(func_def));
+                self.define_variable(name.clone(), Value::Function(name.clone()));
                 Ok(Value::Void)
             }
-            Stmt::Return(expr) => {
+            Stmt::Return(expr, _) => {
                 let val = if let Some(e) = expr {
                     self.evaluate_expr(e)?
                 } else {
@@ -153,48 +440,340 @@ impl Interpreter {
                 self.return_value = Some(val.clone());
                 Ok(val)
             }
-            Stmt::Break => {
-                self.break_flag = true;
+            Stmt::Break(label) => {
+                self.loop_signal = Some(LoopSignal::Break(label.clone()));
                 Ok(Value::Void)
             }
-            Stmt::Continue => {
-                self.continue_flag = true;
+            Stmt::Continue(label) => {
+                self.loop_signal = Some(LoopSignal::Continue(label.clone()));
                 Ok(Value::Void)
             }
+            Stmt::Throw(expr, _) => {
+                let message = match self.evaluate_expr(expr)? {
+                    Value::Error { message } => message,
+                    other => other.to_string(),
+                };
+                // The error travels as Err like any runtime failure; `thrown` keeps the value for `catch`
+                self.thrown = Some(Value::Error { message: message.clone() });
+                Err(message)
+            }
+            Stmt::Try { body, var, handler } => {
+                let depth = self.locals.len();
+                match self.execute_block(body) {
+                    Ok(val) => Ok(val),
+                    Err(message) if self.exit_code.is_some() => Err(message),
+                    Err(message) => {
+                        // Scopes entered by the failed statements were never popped
+                        self.locals.truncate(depth);
+                        let error = self.thrown.take().unwrap_or_else(|| Value::Error {
+                            message: strip_trace(&message).to_string(),
+                        });
+                        self.push_scope();
+                        self.define_variable(var.clone(), error);
+                        let result = self.execute_stmts(handler);
+                        self.pop_scope();
+                        result
+                    }
+                }
+            }
             Stmt::Space { name, body } => {
-                let mut result = Value::Void;
+                self.space_stack.push(SpaceFrame { name: name.clone(), public: Vec::new(), internal: Vec::new() });
                 self.locals.push(Rc::new(RefCell::new(HashMap::new())));
+                let mut result = Ok(Value::Void);
                 for s in body {
-                    result = self.execute_stmt(s)?;
+                    result = self.execute_stmt(s);
+                    if result.is_err() {
+                        break;
+                    }
                 }
-                self.locals.pop();
-                Ok(result)
+                let scope = self.locals.pop().expect("space scope");
+                let frame = self.space_stack.pop().expect("space frame");
+                
+                // Keep what the pub and subpub blocks defined, as it was when the space ended
+                let scope = scope.borrow();
+                let collect = |names: &[String]| -> HashMap<String, Binding> {
+                    names.iter().filter_map(|n| scope.get(n).map(|b| (n.clone(), b.clone()))).collect()
+                };
+                let exports = SpaceExports {
+                    parent: self.space_stack.last().map(|f| f.name.clone()),
+                    public: collect(&frame.public),
+                    internal: collect(&frame.internal),
+                };
+                self.spaces.insert(name.clone(), exports);
+                result
             }
-            Stmt::Pub { name, kind, body } => {
+            Stmt::Pub { body, .. } | Stmt::Subpub { body, .. } => {
                 let mut result = Value::Void;
                 for s in body {
                     result = self.execute_stmt(s)?;
                 }
+                if let Some(frame) = self.space_stack.last_mut() {
+                    let names = declared_names(body);
+                    if matches!(stmt, Stmt::Pub { .. }) {
+                        frame.public.extend(names);
+                    } else {
+                        frame.internal.extend(names);
+                    }
+                }
                 Ok(result)
             }
-            Stmt::Subpub { name, compilation_type, body } => {
+            Stmt::Inspace { space, body } => {
+                // `writeutil` is the built-in output namespace and has nothing to bring in
+                if space == "writeutil" {
+                    return self.execute_block(body);
+                }
+                let exports = self.spaces.get(space).ok_or_else(|| format!("Unknown space '{}'", space))?;
+                let mut visible = exports.public.clone();
+                // subpub names are only visible from inside the space that contains this one
+                let from_parent = match &exports.parent {
+                    Some(parent) => self.space_stack.iter().any(|f| &f.name == parent),
+                    None => true,
+                };
+                if from_parent {
+                    visible.extend(exports.internal.clone());
+                }
+                
+                self.locals.push(Rc::new(RefCell::new(visible)));
+                let result = self.execute_block(body);
+                self.locals.pop();
+                result
+            }
+            Stmt::Import(path) => self.import_file(path),
+            Stmt::Defer(stmt) => {
+                let locals = self.locals.clone();
+                match self.deferred.last_mut() {
+                    Some(frame) => frame.push(Deferred { stmt: (**stmt).clone(), locals }),
+                    None => return Err("'defer' used outside a running program".to_string()),
+                }
+                Ok(Value::Void)
+            }
+            Stmt::With { var, value, body, .. } => {
+                let (id, path) = match self.evaluate_expr(value)? {
+                    Value::File { id, path } => (id, path),
+                    other => return Err(format!("'with' requires a file, got {}", other.type_name())),
+                };
+                self.push_scope();
+                self.define_variable(var.clone(), Value::File { id, path });
+                let result = self.execute_stmts(body);
+                self.pop_scope();
+                // Closed however the body ended; closing a file the body already closed does nothing
+                self.files.remove(&id);
+                result
+            }
+            Stmt::Block(stmts) => self.execute_block(stmts),
+        }
+    }
+    
+    // Runs another file once, in a scope of its own, then defines the names its top-level
+    // `pub` blocks declared in the current scope. Spaces it declares become usable with `inspace`.
+    fn import_file(&mut self, path: &str) -> Result<Value, String> {
+        let base = self.source_path.as_deref().and_then(Path::parent).unwrap_or(Path::new(""));
+        let resolved = std::fs::canonicalize(base.join(path))
+            .map_err(|e| format!("Cannot import '{}': {}", path, e))?;
+        if let Some(start) = self.import_stack.iter().position(|p| *p == resolved) {
+            let cycle: Vec<String> = self.import_stack[start..].iter()
+                .chain(std::iter::once(&resolved))
+                .map(|p| p.file_name().unwrap_or(p.as_os_str()).to_string_lossy().into_owned())
+                .collect();
+            return Err(format!("Circular import: {}", cycle.join(" -> ")));
+        }
+        if let Some(exports) = self.imported.get(&resolved) {
+            for (name, binding) in exports.clone() {
+                self.define_binding(name, binding);
+            }
+            return Ok(Value::Void);
+        }
+        
+        let code = std::fs::read_to_string(&resolved)
+            .map_err(|e| format!("Cannot import '{}': {}", path, e))?;
+        let program = crate::parse(&code)
+            .map_err(|e| format!("{}\n  at import of '{}'", e, path))?;
+        
+        // The file sees globals but not the importer's locals or spaces, and imports relative to itself.
+        // Functions it defines keep its scope, so they can use its private names when called later.
+        let scope = Rc::new(RefCell::new(HashMap::new()));
+        let caller_file_scope = self.file_scope.replace(scope.clone());
+        let caller_locals = std::mem::replace(&mut self.locals, vec![scope]);
+        let caller_spaces = std::mem::replace(&mut self.space_stack, vec![SpaceFrame {
+            name: path.to_string(),
+            public: Vec::new(),
+            internal: Vec::new(),
+        }]);
+        let caller_path = self.source_path.replace(resolved.clone());
+        self.import_stack.push(resolved.clone());
+        
+        self.deferred.push(Vec::new());
+        let mut result = Ok(Value::Void);
+        for stmt in &program.statements {
+            result = self.execute_stmt(stmt);
+            if result.is_err() || self.return_value.take().is_some() {
+                break;
+            }
+        }
+        let result = self.run_deferred(result);
+        
+        self.import_stack.pop();
+        self.source_path = caller_path;
+        self.file_scope = caller_file_scope;
+        let frame = std::mem::replace(&mut self.space_stack, caller_spaces).pop().expect("import frame");
+        let scope = std::mem::replace(&mut self.locals, caller_locals).pop().expect("import scope");
+        result.map_err(|e| format!("{}\n  at import of '{}'", e, path))?;
+        
+        let scope = scope.borrow();
+        let exports: HashMap<String, Binding> = frame.public.into_iter()
+            .filter_map(|name| scope.get(&name).map(|b| (name, b.clone())))
+            .collect();
+        for (name, binding) in &exports {
+            self.define_binding(name.clone(), binding.clone());
+        }
+        self.imported.insert(resolved, exports);
+        Ok(Value::Void)
+    }
+    
+    // Runs a loop statement; `label` is set when it was written as `label: while (...)`
+    fn execute_loop(&mut self, stmt: &Stmt, label: Option<&str>) -> Result<Value, String> {
+        match stmt {
+            Stmt::While { condition, body, else_branch, line } => {
                 let mut result = Value::Void;
-                for s in body {
-                    result = self.execute_stmt(s)?;
+                let mut stopped = false;
+                while self.evaluate_at(condition, *line)?.is_truthy() {
+                    result = self.execute_block(body)?;
+                    if !self.loop_continues(label) {
+                        stopped = true;
+                        break;
+                    }
+                }
+                if !stopped {
+                    if let Some(else_stmts) = else_branch {
+                        result = self.execute_block(else_stmts)?;
+                    }
                 }
                 Ok(result)
             }
-            Stmt::Block(stmts) => {
-                let mut result = Value::Void;
-                for s in stmts {
-                    result = self.execute_stmt(s)?;
-                    if self.return_value.is_some() || self.break_flag || self.continue_flag {
+            Stmt::DoWhile { body, condition, line } => {
+                // The body always runs once before the condition is checked
+                let mut result;
+                loop {
+                    result = self.execute_block(body)?;
+                    if !self.loop_continues(label) || !self.evaluate_at(condition, *line)?.is_truthy() {
                         break;
                     }
                 }
                 Ok(result)
             }
+            Stmt::For { init, condition, increment, body, else_branch, line } => {
+                // The loop variable lives in its own scope around the whole loop
+                self.push_scope();
+                let result = match init {
+                    Some(init_stmt) => self.execute_stmt(init_stmt),
+                    None => Ok(Value::Void),
+                };
+                let result = result.and_then(|_| self.execute_for(condition, increment, body, else_branch, label, *line));
+                self.pop_scope();
+                result
+            }
+            Stmt::ForIn { var, iterable, body, .. } => {
+                // Ranges are stepped through without building an array
+                let items: Box<dyn Iterator<Item = Value>> = match self.evaluate_expr(iterable)? {
+                    Value::Array(items) | Value::Set(items) => Box::new(items.into_iter()),
+                    Value::Range { start, end, step } => Box::new(range_values(start, end, step).map(Value::Number)),
+                    Value::Object(map) => Box::new(map.keys().cloned().map(Value::String).collect::<Vec<_>>().into_iter()),
+                    other => return Err(format!("Cannot iterate over {}", other.type_name())),
+                };
+                
+                self.push_scope();
+                let mut result = Ok(Value::Void);
+                for item in items {
+                    self.define_variable(var.clone(), item);
+                    result = self.execute_block(body);
+                    if result.is_err() || !self.loop_continues(label) {
+                        break;
+                    }
+                }
+                self.pop_scope();
+                result
+            }
+            _ => self.execute_stmt(stmt),
+        }
+    }
+    
+    // Consumes a pending break/continue aimed at this loop. Returns false when the loop
+    // must stop: after `break`, a `return`, or a signal for an enclosing labeled loop.
+    fn loop_continues(&mut self, label: Option<&str>) -> bool {
+        if self.return_value.is_some() {
+            return false;
         }
+        let (is_break, target) = match &self.loop_signal {
+            None => return true,
+            Some(LoopSignal::Break(target)) => (true, target),
+            Some(LoopSignal::Continue(target)) => (false, target),
+        };
+        if target.is_some() && target.as_deref() != label {
+            return false;
+        }
+        self.loop_signal = None;
+        !is_break
+    }
+    
+    // The rest of a `for` loop once its init statement has run
+    fn execute_for(
+        &mut self,
+        condition: &Option<Expr>,
+        increment: &Option<Box<Expr>>,
+        body: &[Stmt],
+        else_branch: &Option<Vec<Stmt>>,
+        label: Option<&str>,
+        line: usize,
+    ) -> Result<Value, String> {
+        let mut result = Value::Void;
+        let mut stopped = false;
+        loop {
+            if let Some(cond) = condition {
+                if !self.evaluate_at(cond, line)?.is_truthy() {
+                    break;
+                }
+            }
+            
+            result = self.execute_block(body)?;
+            if !self.loop_continues(label) {
+                stopped = true;
+                break;
+            }
+            
+            if let Some(inc) = increment {
+                self.evaluate_at(inc, line)?;
+            }
+        }
+        if !stopped {
+            if let Some(else_stmts) = else_branch {
+                result = self.execute_block(else_stmts)?;
+            }
+        }
+        Ok(result)
+    }
+    
+    // Evaluates a loop condition or increment, which runs again after the body's statements
+    fn evaluate_at(&mut self, expr: &Expr, line: usize) -> Result<Value, String> {
+        self.stmt_line = line;
+        self.evaluate_expr(expr)
+    }
+    
+    fn execute_block(&mut self, stmts: &[Stmt]) -> Result<Value, String> {
+        self.push_scope();
+        let result = self.execute_stmts(stmts);
+        self.pop_scope();
+        result
+    }
+    
+    fn execute_stmts(&mut self, stmts: &[Stmt]) -> Result<Value, String> {
+        let mut result = Value::Void;
+        for s in stmts {
+            result = self.execute_stmt(s)?;
+            if self.return_value.is_some() || self.loop_signal.is_some() {
+                break;
+            }
+        }
+        Ok(result)
     }
     
     fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, String> {
@@ -202,7 +781,15 @@ impl Interpreter {
             Expr::Number(n) => Ok(Value::Number(*n)),
             Expr::Float(f) => Ok(Value::Float(*f)),
             Expr::String(s) => Ok(Value::String(s.clone())),
+            Expr::Interpolated(parts) => {
+                let mut result = String::new();
+                for part in parts {
+                    result.push_str(&self.evaluate_expr(part)?.to_string());
+                }
+                Ok(Value::String(result))
+            }
             Expr::Boolean(b) => Ok(Value::Boolean(*b)),
+            Expr::Null => Ok(Value::Null),
             Expr::Array(elements) => {
                 let mut values = Vec::new();
                 for elem in elements {
@@ -210,16 +797,50 @@ impl Interpreter {
                 }
                 Ok(Value::Array(values))
             }
+            Expr::Set(elements) => {
+                let mut items = Vec::new();
+                for elem in elements {
+                    set_insert(&mut items, self.evaluate_expr(elem)?);
+                }
+                Ok(Value::Set(items))
+            }
             Expr::Object(pairs) => {
-                let mut map = HashMap::new();
+                let mut map = ObjectMap::new();
                 for (key, val_expr) in pairs {
-                    map.insert(key.clone(), self.evaluate_expr(val_expr)?);
+                    let key = match key {
+                        ObjectKey::Name(name) => name.clone(),
+                        ObjectKey::Computed(expr) => self.evaluate_expr(expr)?.to_key()?,
+                    };
+                    map.insert(key, self.evaluate_expr(val_expr)?);
                 }
                 Ok(Value::Object(map))
             }
             Expr::Identifier(name) => {
                 self.get_variable(name).ok_or_else(|| format!("Undefined variable: {}", name))
             }
+            Expr::Update { target, increment, prefix } => {
+                let old = self.get_variable(target).ok_or_else(|| format!("Undefined variable: {}", target))?;
+                let new = match &old {
+                    Value::Number(n) if self.wrapping => Value::Number(if *increment { n.wrapping_add(1) } else { n.wrapping_sub(1) }),
+                    Value::Number(n) if *increment => Value::Number(n.checked_add(1).ok_or_else(|| overflow(*n, "+", 1))?),
+                    Value::Number(n) => Value::Number(n.checked_sub(1).ok_or_else(|| overflow(*n, "-", 1))?),
+                    Value::Float(f) => Value::Float(if *increment { f + 1.0 } else { f - 1.0 }),
+                    other => {
+                        let symbol = if *increment { "++" } else { "--" };
+                        return Err(format!("Operator {} requires a number or float, got {}", symbol, other.type_name()));
+                    }
+                };
+                self.set_variable(target.clone(), new.clone())?;
+                Ok(if *prefix { new } else { old })
+            }
+            Expr::Binary { left, op: op @ (BinaryOp::And | BinaryOp::Or), right } => {
+                // The right operand only runs when the left one doesn't decide the result
+                let left_truthy = self.evaluate_expr(left)?.is_truthy();
+                if left_truthy == matches!(op, BinaryOp::Or) {
+                    return Ok(Value::Boolean(left_truthy));
+                }
+                Ok(Value::Boolean(self.evaluate_expr(right)?.is_truthy()))
+            }
             Expr::Binary { left, op, right } => {
                 let left_val = self.evaluate_expr(left)?;
                 let right_val = self.evaluate_expr(right)?;
@@ -229,24 +850,38 @@ impl Interpreter {
                 let val = self.evaluate_expr(expr)?;
                 self.apply_unary_op(op, &val)
             }
-            Expr::Call { func, args } => {
-                self.evaluate_call(func, args)
+            Expr::Call { func, args, line } => {
+                self.evaluate_call(func, args, *line)
             }
             Expr::Index { object, index } => {
                 let obj_val = self.evaluate_expr(object)?;
                 let idx_val = self.evaluate_expr(index)?;
                 match (obj_val, idx_val) {
                     (Value::Array(arr), Value::Number(idx)) => {
-                        let idx = idx as usize;
-                        arr.get(idx).cloned().ok_or_else(|| "Index out of bounds".to_string())
+                        usize::try_from(idx).ok()
+                            .and_then(|i| arr.get(i).cloned())
+                            .ok_or_else(|| format!("Index {} out of bounds for array of length {}", idx, arr.len()))
+                    }
+                    (Value::Range { start, end, step }, Value::Number(idx)) => {
+                        let len = range_len(start, end, step);
+                        usize::try_from(idx).ok()
+                            .filter(|i| *i < len)
+                            .map(|i| Value::Number(range_nth(start, step, i)))
+                            .ok_or_else(|| format!("Index {} out of bounds for range of length {}", idx, len))
                     }
                     (Value::String(s), Value::Number(idx)) => {
-                        let idx = idx as usize;
-                        if idx < s.len() {
-                            Ok(Value::String(s.chars().nth(idx).unwrap().to_string()))
-                        } else {
-                            Err("Index out of bounds".to_string())
-                        }
+                        let len = s.chars().count();
+                        usize::try_from(idx).ok()
+                            .and_then(|i| s.chars().nth(i))
+                            .map(Value::Char)
+                            .ok_or_else(|| format!("Index {} out of bounds for string of length {}", idx, len))
+                    }
+                    (Value::Array(_) | Value::String(_) | Value::Range { .. }, Value::Float(f)) => {
+                        Err(format!("Index must be an integer, got float {}", f))
+                    }
+                    (Value::Object(map), key) => {
+                        let key = key.to_key()?;
+                        map.get(&key).cloned().ok_or_else(|| format!("Key not found: {}", key))
                     }
                     _ => Err("Invalid index operation".to_string()),
                 }
@@ -257,120 +892,1184 @@ impl Interpreter {
                     Value::Object(map) => {
                         map.get(member).cloned().ok_or_else(|| format!("Member not found: {}", member))
                     }
+                    Value::Error { message } if member == "message" => Ok(Value::String(message)),
                     _ => Err("Cannot access member on non-object".to_string()),
                 }
             }
+            Expr::Slice { object, start, end } => {
+                let obj_val = self.evaluate_expr(object)?;
+                let start = match start {
+                    Some(e) => Some(self.evaluate_expr(e)?),
+                    None => None,
+                };
+                let end = match end {
+                    Some(e) => Some(self.evaluate_expr(e)?),
+                    None => None,
+                };
+                match obj_val {
+                    Value::Array(arr) => {
+                        let (from, to) = slice_bounds(start, end, arr.len())?;
+                        Ok(Value::Array(arr[from..to].to_vec()))
+                    }
+                    Value::String(s) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        let (from, to) = slice_bounds(start, end, chars.len())?;
+                        Ok(Value::String(chars[from..to].iter().collect()))
+                    }
+                    _ => Err("Invalid slice operation".to_string()),
+                }
+            }
+            Expr::Compare { first, rest } => {
+                // Equivalent to `a < b && b < c`, but `b` is only evaluated once
+                let mut left = self.evaluate_expr(first)?;
+                for (op, operand) in rest {
+                    let right = self.evaluate_expr(operand)?;
+                    if !self.apply_binary_op(&left, op, &right)?.is_truthy() {
+                        return Ok(Value::Boolean(false));
+                    }
+                    left = right;
+                }
+                Ok(Value::Boolean(true))
+            }
+            Expr::Ternary { cond, then, otherwise } => {
+                // Only the taken branch is evaluated
+                if self.evaluate_expr(cond)?.is_truthy() {
+                    self.evaluate_expr(then)
+                } else {
+                    self.evaluate_expr(otherwise)
+                }
+            }
         }
     }
     
     fn apply_binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
         match (left, op, right) {
-            (Value::Number(a), BinaryOp::Add, Value::Number(b)) => Ok(Value::Number(a + b)),
-            (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) => Ok(Value::Number(a - b)),
-            (Value::Number(a), BinaryOp::Multiply, Value::Number(b)) => Ok(Value::Number(a * b)),
+            (Value::Number(a), BinaryOp::Add, Value::Number(b)) if self.wrapping => Ok(Value::Number(a.wrapping_add(*b))),
+            (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) if self.wrapping => Ok(Value::Number(a.wrapping_sub(*b))),
+            (Value::Number(a), BinaryOp::Multiply, Value::Number(b)) if self.wrapping => Ok(Value::Number(a.wrapping_mul(*b))),
+            (Value::Number(a), BinaryOp::Add, Value::Number(b)) => {
+                a.checked_add(*b).map(Value::Number).ok_or_else(|| overflow(*a, "+", *b))
+            }
+            (Value::Number(a), BinaryOp::Subtract, Value::Number(b)) => {
+                a.checked_sub(*b).map(Value::Number).ok_or_else(|| overflow(*a, "-", *b))
+            }
+            (Value::Number(a), BinaryOp::Multiply, Value::Number(b)) => {
+                a.checked_mul(*b).map(Value::Number).ok_or_else(|| overflow(*a, "*", *b))
+            }
             (Value::Number(a), BinaryOp::Divide, Value::Number(b)) => {
-                if *b == 0 { Err("Division by zero".to_string()) } else { Ok(Value::Number(a / b)) }
+                // `/` promotes to float when the division is not exact; `//` keeps integer division
+                if *b == 0 {
+                    Err("Division by zero".to_string())
+                } else if a.wrapping_rem(*b) == 0 && self.wrapping {
+                    Ok(Value::Number(a.wrapping_div(*b)))
+                } else if a.wrapping_rem(*b) == 0 {
+                    a.checked_div(*b).map(Value::Number).ok_or_else(|| overflow(*a, "/", *b))
+                } else {
+                    Ok(Value::Float(*a as f64 / *b as f64))
+                }
+            }
+            (Value::Number(a), BinaryOp::IntDivide, Value::Number(b)) => {
+                if *b == 0 {
+                    Err("Integer division by zero".to_string())
+                } else if self.wrapping {
+                    Ok(Value::Number(a.wrapping_div(*b)))
+                } else {
+                    a.checked_div(*b).map(Value::Number).ok_or_else(|| overflow(*a, "//", *b))
+                }
             }
             (Value::Number(a), BinaryOp::Modulo, Value::Number(b)) => {
-                if *b == 0 { Err("Modulo by zero".to_string()) } else { Ok(Value::Number(a % b)) }
+                // The remainder always fits, even for i64::MIN % -1
+                if *b == 0 { Err("Modulo by zero".to_string()) } else { Ok(Value::Number(a.wrapping_rem(*b))) }
             }
             (Value::String(a), BinaryOp::Add, Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+            // Chars join with strings and each other into strings, even without string coercion
+            (Value::Char(_) | Value::String(_), BinaryOp::Add, Value::Char(_) | Value::String(_)) => {
+                Ok(Value::String(format!("{}{}", left.to_string(), right.to_string())))
+            }
+            (Value::Char(a), BinaryOp::Less, Value::Char(b)) => Ok(Value::Boolean(a < b)),
+            (Value::Char(a), BinaryOp::LessEqual, Value::Char(b)) => Ok(Value::Boolean(a <= b)),
+            (Value::Char(a), BinaryOp::Greater, Value::Char(b)) => Ok(Value::Boolean(a > b)),
+            (Value::Char(a), BinaryOp::GreaterEqual, Value::Char(b)) => Ok(Value::Boolean(a >= b)),
+            (Value::String(a), BinaryOp::Add, r) if self.string_coercion => Ok(Value::String(format!("{}{}", a, r.to_string()))),
+            (l, BinaryOp::Add, Value::String(b)) if self.string_coercion => Ok(Value::String(format!("{}{}", l.to_string(), b))),
+            (Value::String(s), BinaryOp::Multiply, Value::Number(n))
+            | (Value::Number(n), BinaryOp::Multiply, Value::String(s)) => {
+                Ok(Value::String(s.repeat(repeat_count(*n, s.len())?)))
+            }
+            (Value::Array(items), BinaryOp::Multiply, Value::Number(n))
+            | (Value::Number(n), BinaryOp::Multiply, Value::Array(items)) => {
+                let count = repeat_count(*n, items.len())?;
+                Ok(Value::Array(items.iter().cloned().cycle().take(items.len() * count).collect()))
+            }
+            (Value::Array(a), BinaryOp::Add, Value::Array(b)) => {
+                Ok(Value::Array(a.iter().chain(b).cloned().collect()))
+            }
             (Value::Number(a), BinaryOp::Equal, Value::Number(b)) => Ok(Value::Boolean(a == b)),
             (Value::Number(a), BinaryOp::NotEqual, Value::Number(b)) => Ok(Value::Boolean(a != b)),
             (Value::Number(a), BinaryOp::Less, Value::Number(b)) => Ok(Value::Boolean(a < b)),
             (Value::Number(a), BinaryOp::LessEqual, Value::Number(b)) => Ok(Value::Boolean(a <= b)),
             (Value::Number(a), BinaryOp::Greater, Value::Number(b)) => Ok(Value::Boolean(a > b)),
             (Value::Number(a), BinaryOp::GreaterEqual, Value::Number(b)) => Ok(Value::Boolean(a >= b)),
+            (Value::Number(_) | Value::Float(_), _, Value::Number(_) | Value::Float(_)) if self.is_numeric_op(op) => {
+                self.apply_float_op(as_float(left), op, as_float(right))
+            }
+            // Booleans compare equal only to booleans, through values_equal below, but have no order
+            (Value::Boolean(_), BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual, Value::Boolean(_)) => {
+                Err(format!("Cannot compare booleans with '{}'", op.symbol()))
+            }
+            (l, BinaryOp::Equal, r) => Ok(Value::Boolean(values_equal(l, r))),
+            (l, BinaryOp::NotEqual, r) => Ok(Value::Boolean(!values_equal(l, r))),
             (l, BinaryOp::And, r) => Ok(Value::Boolean(l.is_truthy() && r.is_truthy())),
             (l, BinaryOp::Or, r) => Ok(Value::Boolean(l.is_truthy() || r.is_truthy())),
             _ => Err(format!("Invalid binary operation: {:?} {:?} {:?}", left, op, right)),
         }
     }
     
+    fn is_numeric_op(&self, op: &BinaryOp) -> bool {
+        !matches!(op, BinaryOp::And | BinaryOp::Or)
+    }
+    
+    // Mixed number/float operands are computed as floats. A zero divisor is an
+    // error here too, so `/`, `//` and `%` behave the same whatever the operand types
+    fn apply_float_op(&self, a: f64, op: &BinaryOp, b: f64) -> Result<Value, String> {
+        match op {
+            BinaryOp::Divide if b == 0.0 => Err("Division by zero".to_string()),
+            BinaryOp::IntDivide if b == 0.0 => Err("Integer division by zero".to_string()),
+            BinaryOp::Modulo if b == 0.0 => Err("Modulo by zero".to_string()),
+            BinaryOp::Add => Ok(Value::Float(a + b)),
+            BinaryOp::Subtract => Ok(Value::Float(a - b)),
+            BinaryOp::Multiply => Ok(Value::Float(a * b)),
+            BinaryOp::Divide => Ok(Value::Float(a / b)),
+            BinaryOp::IntDivide => Ok(Value::Float((a / b).trunc())),
+            BinaryOp::Modulo => Ok(Value::Float(a % b)),
+            BinaryOp::Equal => Ok(Value::Boolean(floats_equal(a, b))),
+            BinaryOp::NotEqual => Ok(Value::Boolean(!floats_equal(a, b))),
+            // Every ordering comparison involving NaN is false
+            BinaryOp::Less => Ok(Value::Boolean(a < b)),
+            BinaryOp::LessEqual => Ok(Value::Boolean(a <= b)),
+            BinaryOp::Greater => Ok(Value::Boolean(a > b)),
+            BinaryOp::GreaterEqual => Ok(Value::Boolean(a >= b)),
+            BinaryOp::And | BinaryOp::Or => Err(format!("Invalid float operation: {:?}", op)),
+        }
+    }
+    
     fn apply_unary_op(&self, op: &UnaryOp, val: &Value) -> Result<Value, String> {
         match (op, val) {
-            (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+            (UnaryOp::Negate, Value::Number(n)) if self.wrapping => Ok(Value::Number(n.wrapping_neg())),
+            (UnaryOp::Negate, Value::Number(n)) => {
+                n.checked_neg().map(Value::Number).ok_or_else(|| format!("Arithmetic overflow: -({})", n))
+            }
             (UnaryOp::Negate, Value::Float(f)) => Ok(Value::Float(-f)),
             (UnaryOp::Not, v) => Ok(Value::Boolean(!v.is_truthy())),
             _ => Err(format!("Invalid unary operation: {:?} {:?}", op, val)),
         }
     }
     
-    fn evaluate_call(&mut self, func: &Expr, args: &[Expr]) -> Result<Value, String> {
+    fn evaluate_call(&mut self, func: &Expr, args: &[Expr], line: usize) -> Result<Value, String> {
         match func {
             Expr::Identifier(name) => {
                 let mut arg_vals = Vec::new();
                 for arg in args {
                     arg_vals.push(self.evaluate_expr(arg)?);
                 }
+                self.current_line = line;
                 
-                match name.as_str() {
-                    "print" => {
-                        let output = arg_vals.iter()
-                            .map(|v| v.to_string())
-                            .collect::<Vec<_>>()
-                            .join(" ");
-                        println!("{}", output);
+                // A variable holding a function or builtin calls that, whatever the variable is named.
+                // Other values leave the builtin of that name reachable, so `text` can be a variable.
+                match self.get_variable(name) {
+                    Some(callee) if callee.is_callable() => self.call_value(&callee, arg_vals),
+                    _ => self.call_builtin(name, arg_vals),
+                }
+            }
+            Expr::Member { object, member } => {
+                if let Expr::Identifier(obj_name) = &**object {
+                    if obj_name == "writeutil" && member == "text" {
+                        let mut arg_vals = Vec::new();
+                        for arg in args {
+                            arg_vals.push(self.evaluate_expr(arg)?);
+                        }
+                        return self.call_builtin("text", arg_vals);
+                    }
+                }
+                Err("Unknown method call".to_string())
+            }
+            _ => {
+                let callee = self.evaluate_expr(func)?;
+                let mut arg_vals = Vec::new();
+                for arg in args {
+                    arg_vals.push(self.evaluate_expr(arg)?);
+                }
+                self.current_line = line;
+                self.call_value(&callee, arg_vals)
+            }
+        }
+    }
+    
+    fn call_value(&mut self, callee: &Value, arg_vals: Vec<Value>) -> Result<Value, String> {
+        match callee {
+            Value::Function(func_name) => self.call_function(func_name, arg_vals),
+            Value::Builtin(name) => self.call_builtin(name, arg_vals),
+            _ => Err(format!("Value is not callable: {}", callee.to_string())),
+        }
+    }
+    
+    fn call_function(&mut self, name: &str, arg_vals: Vec<Value>) -> Result<Value, String> {
+        let overloads = self.functions.get(name)
+            .ok_or_else(|| format!("Unknown function: {}", name))?;
+        let UserFunction { params, body, file_scope, .. } = match overloads.iter().find(|f| f.params.len() == arg_vals.len()) {
+            Some(func) => func.clone(),
+            None if overloads.len() == 1 => {
+                return Err(format!("{}() expects {} arguments, got {} (defined at line {})", name, overloads[0].params.len(), arg_vals.len(), overloads[0].line));
+            }
+            None => {
+                let mut arities: Vec<usize> = overloads.iter().map(|f| f.params.len()).collect();
+                arities.sort();
+                let arities: Vec<String> = arities.iter().map(|n| n.to_string()).collect();
+                return Err(format!("{}() has no overload taking {} arguments (defined for {})", name, arg_vals.len(), arities.join(", ")));
+            }
+        };
+        
+        // Fail cleanly before runaway recursion exhausts the native stack. Each call takes tens
+        // of kilobytes of it in a debug build, so the stack usually runs short before the depth does.
+        let here = stack_address();
+        if self.call_depth == 0 {
+            self.stack_base = here;
+        }
+        if self.call_depth >= self.max_call_depth || self.stack_base.abs_diff(here) > self.max_stack_size {
+            return Err(format!("Stack overflow: recursion limit exceeded ({} nested calls)", self.call_depth));
+        }
+        
+        if let Some(counts) = &mut self.profile {
+            *counts.entry(name.to_string()).or_insert(0) += 1;
+        }
+        
+        // Functions see globals, the file they were imported from, and their own frame,
+        // not the caller's locals
+        let call_line = self.current_line;
+        let caller_stmt_line = self.stmt_line;
+        let frame: HashMap<String, Binding> = params.into_iter()
+            .zip(arg_vals.into_iter().map(Binding::new))
+            .collect();
+        let scopes = file_scope.into_iter().chain(std::iter::once(Rc::new(RefCell::new(frame)))).collect();
+        let caller_locals = std::mem::replace(&mut self.locals, scopes);
+        self.call_depth += 1;
+        self.deferred.push(Vec::new());
+        let result = self.execute_stmts(&body);
+        let body_failed = result.is_err();
+        let failed_line = self.stmt_line;
+        let result = self.run_deferred(result);
+        // A deferred statement that failed is the one to point at
+        let failed_line = if body_failed { failed_line } else { self.stmt_line };
+        self.stmt_line = caller_stmt_line;
+        self.call_depth -= 1;
+        self.locals = caller_locals;
+        // Each frame adds itself while the error unwinds, so the trace reads innermost first. The
+        // frame the error started in also names the line that failed. Deep recursion keeps only
+        // the innermost frames.
+        result.map_err(|e| match e.matches("\n  at ").count() {
+            0 => format!("{}\n  at {}() line {}, called from line {}", e, name, failed_line, call_line),
+            n if n < MAX_TRACE_FRAMES => format!("{}\n  at {}() called from line {}", e, name, call_line),
+            _ if e.ends_with("\n  ...") => e,
+            _ => format!("{}\n  ...", e),
+        })?;
+        
+        self.loop_signal = None;
+        Ok(self.return_value.take().unwrap_or(Value::Void))
+    }
+    
+    // Shared by every printing builtin: joins the values with `sep` and writes `end` after them
+    fn write_values(&mut self, values: &[Value], sep: &str, end: &str, to_stderr: bool) -> Result<(), String> {
+        let output = values.iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(sep);
+        let text = format!("{}{}", output, end);
+        // stdout is line buffered, so output without a newline has to be flushed by hand
+        let written = if to_stderr {
+            std::io::stderr().write_all(text.as_bytes())
+        } else {
+            self.output.write_all(text.as_bytes()).and_then(|_| self.output.flush())
+        };
+        written.map_err(|e| format!("IO error: {}", e))
+    }
+    
+    fn call_builtin(&mut self, name: &str, arg_vals: Vec<Value>) -> Result<Value, String> {
+        match name {
+            // `print` leaves the line open; `println` and `eprint` end it (see take_print_options)
+            "print" | "println" | "eprint" => {
+                let mut arg_vals = arg_vals;
+                let (sep, end) = take_print_options(name, &mut arg_vals)?;
+                self.write_values(&arg_vals, &sep, &end, name == "eprint")?;
+                Ok(Value::Void)
+            }
+            "printf" => {
+                // Like format(), but written out with no trailing newline
+                let output = match arg_vals.first() {
+                    Some(Value::String(template)) => format_template(template, &arg_vals[1..])?,
+                    _ => return Err("printf() requires a template string".to_string()),
+                };
+                self.write_values(&[Value::String(output)], "", "", false)?;
+                Ok(Value::Void)
+            }
+            "text" => {
+                // Raw output: no separator between values and no trailing newline
+                self.write_values(&arg_vals, "", "", false)?;
+                Ok(Value::Void)
+            }
+            "len" => {
+                if arg_vals.is_empty() {
+                    return Err("len() requires 1 argument".to_string());
+                }
+                match &arg_vals[0] {
+                    // Counted in characters, matching indexing and slicing
+                    Value::String(s) => Ok(Value::Number(s.chars().count() as i64)),
+                    Value::Array(items) | Value::Set(items) => Ok(Value::Number(items.len() as i64)),
+                    Value::Object(map) => Ok(Value::Number(map.len() as i64)),
+                    Value::Range { start, end, step } => Ok(Value::Number(range_len(*start, *end, *step) as i64)),
+                    _ => Err("len() requires string, array, set, range or object".to_string()),
+                }
+            }
+            "type" => {
+                if arg_vals.is_empty() {
+                    return Err("type() requires 1 argument".to_string());
+                }
+                let type_name = arg_vals[0].type_name();
+                Ok(Value::String(type_name.to_string()))
+            }
+            "read_line" => {
+                match read_stdin_line()? {
+                    Some(line) => Ok(Value::String(line)),
+                    None => Ok(Value::Void),
+                }
+            }
+            "read_number" => {
+                match read_stdin_line()? {
+                    Some(line) => {
+                        let text = line.trim();
+                        if let Ok(n) = text.parse::<i64>() {
+                            Ok(Value::Number(n))
+                        } else if let Some(f) = parse_finite(text) {
+                            Ok(Value::Float(f))
+                        } else {
+                            Err(format!("read_number() could not parse '{}'", text))
+                        }
+                    }
+                    None => Ok(Value::Void),
+                }
+            }
+            "to_number" => {
+                match arg_vals.as_slice() {
+                    [Value::Number(n)] => Ok(Value::Number(*n)),
+                    [Value::Float(f)] => {
+                        let t = f.trunc();
+                        // i64::MAX as f64 rounds up to 2^63, which is already out of range
+                        if t >= i64::MIN as f64 && t < i64::MAX as f64 {
+                            Ok(Value::Number(t as i64))
+                        } else {
+                            Err(format!("to_number() cannot convert {} to an integer", f))
+                        }
+                    }
+                    [Value::Boolean(b)] => Ok(Value::Number(*b as i64)),
+                    [Value::String(s)] => {
+                        let text = s.trim();
+                        if let Ok(n) = text.parse::<i64>() {
+                            Ok(Value::Number(n))
+                        } else if let Some(f) = parse_finite(text) {
+                            Ok(Value::Float(f))
+                        } else {
+                            Err(format!("to_number() could not parse '{}'", s))
+                        }
+                    }
+                    [other] => Err(format!("to_number() cannot convert {}", other.type_name())),
+                    _ => Err("to_number() requires 1 argument".to_string()),
+                }
+            }
+            "to_hex" | "to_bin" | "to_oct" => {
+                let n = match arg_vals.as_slice() {
+                    [Value::Number(n)] => *n,
+                    [other] => return Err(format!("{}() requires an integer, got {}", name, other.type_name())),
+                    _ => return Err(format!("{}() requires 1 argument", name)),
+                };
+                // Negative numbers keep their sign rather than showing two's complement
+                let sign = if n < 0 { "-" } else { "" };
+                let digits = match name {
+                    "to_hex" => format!("{:x}", n.unsigned_abs()),
+                    "to_bin" => format!("{:b}", n.unsigned_abs()),
+                    _ => format!("{:o}", n.unsigned_abs()),
+                };
+                Ok(Value::String(format!("{}{}", sign, digits)))
+            }
+            "parse_radix" => {
+                match arg_vals.as_slice() {
+                    [Value::String(s), Value::Number(base)] => {
+                        if !(2..=36).contains(base) {
+                            return Err(format!("parse_radix() base must be between 2 and 36, got {}", base));
+                        }
+                        i64::from_str_radix(s.trim(), *base as u32)
+                            .map(Value::Number)
+                            .map_err(|_| format!("parse_radix() could not parse '{}' in base {}", s, base))
+                    }
+                    _ => Err("parse_radix() requires a string and an integer base".to_string()),
+                }
+            }
+            "is_nan" => {
+                match arg_vals.as_slice() {
+                    [v] => Ok(Value::Boolean(matches!(v, Value::Float(f) if f.is_nan()))),
+                    _ => Err("is_nan() requires 1 argument".to_string()),
+                }
+            }
+            "range" => {
+                let mut bounds = Vec::new();
+                for arg in &arg_vals {
+                    match arg {
+                        Value::Number(n) => bounds.push(*n),
+                        other => return Err(format!("range() requires integers, got {}", other.type_name())),
+                    }
+                }
+                let (start, end, step) = match bounds.as_slice() {
+                    [end] => (0, *end, 1),
+                    [start, end] => (*start, *end, 1),
+                    [_, _, 0] => return Err("range() step cannot be 0".to_string()),
+                    [start, end, step] => (*start, *end, *step),
+                    _ => return Err("range() requires 1 to 3 arguments".to_string()),
+                };
+                Ok(Value::Range { start, end, step })
+            }
+            "wrapping_add" | "wrapping_sub" | "wrapping_mul" => {
+                // Modular arithmetic on demand, whatever set_int_mode() says
+                match arg_vals.as_slice() {
+                    [Value::Number(a), Value::Number(b)] => Ok(Value::Number(match name {
+                        "wrapping_add" => a.wrapping_add(*b),
+                        "wrapping_sub" => a.wrapping_sub(*b),
+                        _ => a.wrapping_mul(*b),
+                    })),
+                    [Value::Number(_), other] | [other, _] => Err(format!("{}() requires two numbers, got {}", name, other.type_name())),
+                    _ => Err(format!("{}() requires 2 arguments", name)),
+                }
+            }
+            "set_int_mode" => {
+                match arg_vals.as_slice() {
+                    [Value::String(mode)] if mode == "wrap" || mode == "checked" => {
+                        self.wrapping = mode == "wrap";
                         Ok(Value::Void)
                     }
-                    "len" => {
-                        if arg_vals.is_empty() {
-                            return Err("len() requires 1 argument".to_string());
+                    [Value::String(mode)] => Err(format!("set_int_mode() mode must be \"wrap\" or \"checked\", got \"{}\"", mode)),
+                    [other] => Err(format!("set_int_mode() requires a string, got {}", other.type_name())),
+                    _ => Err("set_int_mode() requires 1 argument".to_string()),
+                }
+            }
+            "exit" => {
+                let code = match arg_vals.as_slice() {
+                    [] => 0,
+                    [Value::Number(n)] => i32::try_from(*n).map_err(|_| format!("exit() code {} is out of range", n))?,
+                    [other] => return Err(format!("exit() requires an integer code, got {}", other.type_name())),
+                    _ => return Err("exit() takes at most 1 argument".to_string()),
+                };
+                self.exit_code = Some(code);
+                Err(format!("exit({})", code))
+            }
+            "panic" => {
+                let message = match arg_vals.as_slice() {
+                    [] => "explicit panic".to_string(),
+                    [message] => message.to_string(),
+                    _ => return Err("panic() takes at most 1 argument".to_string()),
+                };
+                eprintln!("panic: {}", message);
+                self.exit_code = Some(1);
+                Err(format!("panic: {}", message))
+            }
+            "char_code" => {
+                // A one-character string is accepted too, for code written before chars existed
+                match arg_vals.as_slice() {
+                    [Value::Char(c)] => Ok(Value::Number(*c as i64)),
+                    [Value::String(s)] if s.chars().count() == 1 => Ok(Value::Number(s.chars().next().unwrap_or_default() as i64)),
+                    [other] => Err(format!("char_code() requires a char, got {}", other.type_name())),
+                    _ => Err("char_code() requires 1 argument".to_string()),
+                }
+            }
+            "from_char_code" => {
+                match arg_vals.as_slice() {
+                    [Value::Number(n)] => u32::try_from(*n).ok()
+                        .and_then(char::from_u32)
+                        .map(Value::Char)
+                        .ok_or_else(|| format!("from_char_code() got {}, which is not a character code", n)),
+                    [other] => Err(format!("from_char_code() requires a number, got {}", other.type_name())),
+                    _ => Err("from_char_code() requires 1 argument".to_string()),
+                }
+            }
+            "to_array" => {
+                match arg_vals.as_slice() {
+                    [Value::Range { start, end, step }] => Ok(Value::Array(range_values(*start, *end, *step).map(Value::Number).collect())),
+                    [Value::Array(items) | Value::Set(items)] => Ok(Value::Array(items.clone())),
+                    [other] => Err(format!("to_array() requires a range, array or set, got {}", other.type_name())),
+                    _ => Err("to_array() requires 1 argument".to_string()),
+                }
+            }
+            "to_bool" | "to_string" => {
+                match arg_vals.as_slice() {
+                    [v] if name == "to_bool" => Ok(Value::Boolean(v.is_truthy())),
+                    [v] => Ok(Value::String(v.to_string())),
+                    _ => Err(format!("{}() requires 1 argument", name)),
+                }
+            }
+            "map" | "filter" | "reduce" => {
+                let expected = if name == "reduce" { 3 } else { 2 };
+                if arg_vals.len() < expected {
+                    return Err(format!("{}() requires {} arguments", name, expected));
+                }
+                let items = match &arg_vals[0] {
+                    Value::Array(arr) => arr.clone(),
+                    _ => return Err(format!("{}() requires an array as first argument", name)),
+                };
+                let callback = &arg_vals[1];
+                if !callback.is_callable() {
+                    return Err(format!("{}() requires a function as second argument", name));
+                }
+                
+                match name {
+                    "map" => {
+                        let mut mapped = Vec::new();
+                        for item in items {
+                            mapped.push(self.call_value(callback, vec![item])?);
                         }
-                        match &arg_vals[0] {
-                            Value::String(s) => Ok(Value::Number(s.len() as i64)),
-                            Value::Array(arr) => Ok(Value::Number(arr.len() as i64)),
-                            _ => Err("len() requires string or array".to_string()),
+                        Ok(Value::Array(mapped))
+                    }
+                    "filter" => {
+                        let mut kept = Vec::new();
+                        for item in items {
+                            if self.call_value(callback, vec![item.clone()])?.is_truthy() {
+                                kept.push(item);
+                            }
                         }
+                        Ok(Value::Array(kept))
                     }
-                    "type" => {
-                        if arg_vals.is_empty() {
-                            return Err("type() requires 1 argument".to_string());
+                    _ => {
+                        let mut acc = arg_vals[2].clone();
+                        for item in items {
+                            acc = self.call_value(callback, vec![acc, item])?;
                         }
-                        let type_name = match &arg_vals[0] {
-                            Value::Number(_) => "number",
-                            Value::Float(_) => "float",
-                            Value::String(_) => "string",
-                            Value::Boolean(_) => "boolean",
-                            Value::Array(_) => "array",
-                            Value::Object(_) => "object",
-                            Value::Void => "void",
-                        };
-                        Ok(Value::String(type_name.to_string()))
+                        Ok(acc)
                     }
-                    _ => Err(format!("Unknown function: {}", name)),
                 }
             }
-            Expr::Member { object, member } => {
-                if let Expr::Identifier(obj_name) = &**object {
-                    if obj_name == "writeutil" && member == "text" {
-                        if args.len() == 1 {
-                            let val = self.evaluate_expr(&args[0])?;
-                            print!("{}", val.to_string());
-                            return Ok(Value::Void);
+            "sort" => {
+                let items = match arg_vals.first() {
+                    Some(Value::Array(arr)) => arr.clone(),
+                    _ => return Err("sort() requires an array as first argument".to_string()),
+                };
+                match arg_vals.get(1) {
+                    None => merge_sort(items, &mut |a: &Value, b: &Value| natural_order(a, b)).map(Value::Array),
+                    Some(cmp) if cmp.is_callable() => {
+                        // The comparator returns a negative number, zero or a positive number, like a - b
+                        let cmp = cmp.clone();
+                        merge_sort(items, &mut |a: &Value, b: &Value| {
+                            match self.call_value(&cmp, vec![a.clone(), b.clone()])? {
+                                Value::Number(n) => Ok(n.cmp(&0)),
+                                Value::Float(f) => f.partial_cmp(&0.0).ok_or_else(|| "sort() comparator returned NaN".to_string()),
+                                other => Err(format!("sort() comparator must return a number, got {}", other.type_name())),
+                            }
+                        }).map(Value::Array)
+                    }
+                    Some(_) => Err("sort() requires a function as second argument".to_string()),
+                }
+            }
+            "zip" => {
+                // Pairs stop at the end of the shorter array
+                match arg_vals.as_slice() {
+                    [Value::Array(a), Value::Array(b)] => Ok(Value::Array(
+                        a.iter().zip(b).map(|(x, y)| Value::Array(vec![x.clone(), y.clone()])).collect()
+                    )),
+                    [Value::Array(_), other] | [other, _] => Err(format!("zip() requires two arrays, got {}", other.type_name())),
+                    _ => Err("zip() requires 2 arguments".to_string()),
+                }
+            }
+            "enumerate" => {
+                match arg_vals.as_slice() {
+                    [Value::Array(arr)] => Ok(Value::Array(
+                        arr.iter().enumerate().map(|(i, item)| Value::Array(vec![Value::Number(i as i64), item.clone()])).collect()
+                    )),
+                    [other] => Err(format!("enumerate() requires an array, got {}", other.type_name())),
+                    _ => Err("enumerate() requires 1 argument".to_string()),
+                }
+            }
+            "reverse" => {
+                match arg_vals.as_slice() {
+                    [Value::Array(arr)] => Ok(Value::Array(arr.iter().rev().cloned().collect())),
+                    [Value::String(s)] => Ok(Value::String(s.chars().rev().collect())),
+                    _ => Err("reverse() requires an array or a string".to_string()),
+                }
+            }
+            "contains" | "index_of" => {
+                // Substring search in a string; in an array or set, the first element that is `==` to the needle
+                let found = match arg_vals.as_slice() {
+                    [Value::String(s), Value::String(needle)] => {
+                        s.find(needle.as_str()).map(|byte_idx| s[..byte_idx].chars().count())
+                    }
+                    [Value::String(s), Value::Char(c)] => s.chars().position(|ch| ch == *c),
+                    [Value::String(_), other] => {
+                        return Err(format!("{}() on a string needs a string to search for, got {}", name, other.type_name()));
+                    }
+                    [Value::Array(arr), needle] => arr.iter().position(|item| values_equal(item, needle)),
+                    // Sets are unordered, so they have no index to give
+                    [Value::Set(items), needle] if name == "contains" => items.iter().position(|item| values_equal(item, needle)),
+                    [_, _] => return Err(format!("{}() requires a string or an array to search in", name)),
+                    _ => return Err(format!("{}() requires 2 arguments", name)),
+                };
+                if name == "contains" {
+                    Ok(Value::Boolean(found.is_some()))
+                } else {
+                    Ok(Value::Number(found.map_or(-1, |i| i as i64)))
+                }
+            }
+            "get" => {
+                // Like member/index access, but a missing key or index yields null (or the default)
+                if arg_vals.len() < 2 {
+                    return Err("get() requires at least 2 arguments".to_string());
+                }
+                let found = match (&arg_vals[0], &arg_vals[1]) {
+                    (Value::Object(map), key) => map.get(&key.to_key()?).cloned(),
+                    (Value::Array(arr), Value::Number(idx)) => {
+                        usize::try_from(*idx).ok().and_then(|i| arr.get(i).cloned())
+                    }
+                    _ => return Err("get() requires an object with a key or an array with a number index".to_string()),
+                };
+                Ok(found.unwrap_or_else(|| arg_vals.get(2).cloned().unwrap_or(Value::Null)))
+            }
+            "keys" | "values" => {
+                let map = match arg_vals.first() {
+                    Some(Value::Object(map)) => map,
+                    _ => return Err(format!("{}() requires an object", name)),
+                };
+                if name == "keys" {
+                    Ok(Value::Array(map.keys().cloned().map(Value::String).collect()))
+                } else {
+                    Ok(Value::Array(map.values().cloned().collect()))
+                }
+            }
+            "assert" => {
+                if arg_vals.is_empty() {
+                    return Err("assert() requires at least 1 argument".to_string());
+                }
+                if arg_vals[0].is_truthy() {
+                    return Ok(Value::Void);
+                }
+                match arg_vals.get(1) {
+                    Some(message) => Err(format!("Assertion failed: {}", message.to_string())),
+                    None => Err("Assertion failed".to_string()),
+                }
+            }
+            "format" => {
+                match arg_vals.first() {
+                    Some(Value::String(template)) => format_template(template, &arg_vals[1..]).map(Value::String),
+                    _ => Err("format() requires a template string".to_string()),
+                }
+            }
+            "abs" => {
+                match arg_vals.first() {
+                    Some(Value::Number(n)) => n.checked_abs().map(Value::Number)
+                        .ok_or_else(|| "abs() overflow".to_string()),
+                    Some(Value::Float(f)) => Ok(Value::Float(f.abs())),
+                    _ => Err("abs() requires a number".to_string()),
+                }
+            }
+            "min" | "max" => {
+                // Either two scalars or a single array of numbers
+                let candidates = match arg_vals.as_slice() {
+                    [Value::Array(items)] => items.clone(),
+                    [a, b] => vec![a.clone(), b.clone()],
+                    _ => return Err(format!("{}() requires two numbers or an array", name)),
+                };
+                let mut best: Option<(f64, Value)> = None;
+                for v in candidates {
+                    let x = number_arg(name, &v)?;
+                    if x.is_nan() {
+                        return Err(format!("{}() cannot compare NaN", name));
+                    }
+                    let better = match &best {
+                        None => true,
+                        Some((b, _)) => if name == "min" { x < *b } else { x > *b },
+                    };
+                    if better {
+                        best = Some((x, v));
+                    }
+                }
+                best.map(|(_, v)| v).ok_or_else(|| format!("{}() of empty array", name))
+            }
+            "sqrt" => {
+                let x = number_arg(name, arg_vals.first().unwrap_or(&Value::Void))?;
+                if x < 0.0 {
+                    return Err(format!("sqrt() of negative number {}", x));
+                }
+                Ok(Value::Float(x.sqrt()))
+            }
+            "pow" => {
+                if arg_vals.len() < 2 {
+                    return Err("pow() requires 2 arguments".to_string());
+                }
+                match (&arg_vals[0], &arg_vals[1]) {
+                    (Value::Number(base), Value::Number(exp)) if *exp >= 0 => {
+                        u32::try_from(*exp).ok()
+                            .and_then(|e| base.checked_pow(e))
+                            .map(Value::Number)
+                            .ok_or_else(|| "pow() overflow".to_string())
+                    }
+                    (base, exp) => Ok(Value::Float(number_arg(name, base)?.powf(number_arg(name, exp)?))),
+                }
+            }
+            "floor" | "ceil" | "round" => {
+                match arg_vals.first() {
+                    Some(Value::Number(n)) => Ok(Value::Number(*n)),
+                    Some(Value::Float(f)) => {
+                        let rounded = match name {
+                            "floor" => f.floor(),
+                            "ceil" => f.ceil(),
+                            _ => f.round(),
+                        };
+                        // NaN fails both checks; i64::MAX as f64 is 2^63, which doesn't fit
+                        if rounded >= i64::MIN as f64 && rounded < i64::MAX as f64 {
+                            Ok(Value::Number(rounded as i64))
+                        } else {
+                            Err(format!("{}() result {:e} does not fit in a number", name, rounded))
                         }
                     }
+                    _ => Err(format!("{}() requires a number", name)),
                 }
-                Err("Unknown method call".to_string())
             }
-            _ => Err("Invalid function call".to_string()),
+            "format_float" => {
+                let (value, digits) = match arg_vals.as_slice() {
+                    [v @ (Value::Number(_) | Value::Float(_)), Value::Number(d)] => (as_float(v), *d),
+                    _ => return Err("format_float() requires a number and a digit count".to_string()),
+                };
+                match usize::try_from(digits) {
+                    Ok(d) if d <= MAX_FLOAT_DIGITS => Ok(Value::String(format!("{:.*}", d, value))),
+                    _ => Err(format!("format_float() digit count must be between 0 and {}, got {}", MAX_FLOAT_DIGITS, digits)),
+                }
+            }
+            "args" => {
+                Ok(Value::Array(self.script_args.iter().cloned().map(Value::String).collect()))
+            }
+            "env" => {
+                // An unset variable gives void rather than an error so scripts can test for it
+                match arg_vals.as_slice() {
+                    [Value::String(var)] => Ok(std::env::var(var).map(Value::String).unwrap_or(Value::Void)),
+                    _ => Err("env() requires a variable name".to_string()),
+                }
+            }
+            "open" => {
+                let (path, mode) = match arg_vals.as_slice() {
+                    [Value::String(path)] => (path, "r"),
+                    [Value::String(path), Value::String(mode)] => (path, mode.as_str()),
+                    _ => return Err("open() requires a path and an optional mode string".to_string()),
+                };
+                let mut options = std::fs::OpenOptions::new();
+                match mode {
+                    "r" => options.read(true),
+                    "w" => options.write(true).create(true).truncate(true),
+                    "a" => options.append(true).create(true),
+                    _ => return Err(format!("open() mode must be \"r\", \"w\" or \"a\", got \"{}\"", mode)),
+                };
+                let file = options.open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+                let id = self.next_file_id;
+                self.next_file_id += 1;
+                self.files.insert(id, file);
+                Ok(Value::File { id, path: path.clone() })
+            }
+            "read" => {
+                let (id, path) = match arg_vals.as_slice() {
+                    [Value::File { id, path }] => (id, path),
+                    _ => return Err("read() requires a file".to_string()),
+                };
+                let file = self.files.get_mut(id).ok_or_else(|| format!("File is closed: {}", path))?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                Ok(Value::String(contents))
+            }
+            "write" => {
+                let (id, path, value) = match arg_vals.as_slice() {
+                    [Value::File { id, path }, value] => (id, path, value),
+                    _ => return Err("write() requires a file and a value".to_string()),
+                };
+                let file = self.files.get_mut(id).ok_or_else(|| format!("File is closed: {}", path))?;
+                file.write_all(value.to_string().as_bytes()).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+                Ok(Value::Void)
+            }
+            "close" => {
+                match arg_vals.as_slice() {
+                    [Value::File { id, .. }] => {
+                        self.files.remove(id);
+                        Ok(Value::Void)
+                    }
+                    _ => Err("close() requires a file".to_string()),
+                }
+            }
+            "to_json" => {
+                match arg_vals.as_slice() {
+                    [value] => crate::json::to_json(value).map(Value::String),
+                    _ => Err(format!("to_json() expects 1 argument, got {}", arg_vals.len())),
+                }
+            }
+            "from_json" => {
+                match arg_vals.as_slice() {
+                    [Value::String(text)] => crate::json::from_json(text),
+                    _ => Err("from_json() requires a JSON string".to_string()),
+                }
+            }
+            "clone" => {
+                // Variables already hold their own copy, so this is the explicit spelling of that copy
+                match arg_vals.as_slice() {
+                    [value] => Ok(value.clone()),
+                    _ => Err(format!("clone() expects 1 argument, got {}", arg_vals.len())),
+                }
+            }
+            "union" | "intersect" | "difference" => {
+                // The result keeps the first set's order, with union's new elements after it
+                match arg_vals.as_slice() {
+                    [Value::Set(a), Value::Set(b)] => {
+                        let in_b = |item: &&Value| b.iter().any(|other| values_equal(item, other));
+                        let items = match name {
+                            "union" => {
+                                let mut items = a.clone();
+                                for item in b {
+                                    set_insert(&mut items, item.clone());
+                                }
+                                items
+                            }
+                            "intersect" => a.iter().filter(in_b).cloned().collect(),
+                            _ => a.iter().filter(|item| !in_b(item)).cloned().collect(),
+                        };
+                        Ok(Value::Set(items))
+                    }
+                    [Value::Set(_), other] | [other, _] => Err(format!("{}() requires two sets, got {}", name, other.type_name())),
+                    _ => Err(format!("{}() requires 2 arguments", name)),
+                }
+            }
+            "sizeof" => {
+                match arg_vals.as_slice() {
+                    [value] => Ok(Value::Number(value.approx_size() as i64)),
+                    _ => Err(format!("sizeof() expects 1 argument, got {}", arg_vals.len())),
+                }
+            }
+            "deep_merge" => {
+                if arg_vals.len() < 2 {
+                    return Err("deep_merge() requires 2 arguments".to_string());
+                }
+                match (&arg_vals[0], &arg_vals[1]) {
+                    (Value::Object(_), Value::Object(_)) => {
+                        // Optional third argument concatenates arrays instead of replacing them
+                        let concat_arrays = arg_vals.get(2).is_some_and(Value::is_truthy);
+                        Ok(deep_merge(&arg_vals[0], &arg_vals[1], concat_arrays))
+                    }
+                    _ => Err("deep_merge() requires two objects".to_string()),
+                }
+            }
+            _ => Err(format!("Unknown function: {}", name)),
         }
     }
     
     fn get_variable(&self, name: &str) -> Option<Value> {
         for scope in self.locals.iter().rev() {
-            if let Some(val) = scope.borrow().get(name) {
-                return Some(val.clone());
+            if let Some(binding) = scope.borrow().get(name) {
+                return Some(binding.value.clone());
             }
         }
-        self.globals.borrow().get(name).cloned()
+        self.globals.borrow().get(name).map(|b| b.value.clone())
+    }
+    
+    fn push_scope(&mut self) {
+        self.locals.push(Rc::new(RefCell::new(HashMap::new())));
+    }
+    
+    fn pop_scope(&mut self) {
+        self.locals.pop();
+    }
+    
+    fn define_variable(&mut self, name: String, value: Value) {
+        self.define_binding(name, Binding::new(value));
     }
     
-    fn set_variable(&mut self, name: String, value: Value) {
+    fn define_binding(&mut self, name: String, binding: Binding) {
         if let Some(scope) = self.locals.last() {
-            scope.borrow_mut().insert(name, value);
+            scope.borrow_mut().insert(name, binding);
         } else {
-            self.globals.borrow_mut().insert(name, value);
+            self.globals.borrow_mut().insert(name, binding);
         }
     }
+    
+    fn set_variable(&mut self, name: String, value: Value) -> Result<(), String> {
+        // Assignment updates the nearest existing binding instead of shadowing it
+        let scopes = self.locals.iter().rev().chain(std::iter::once(&self.globals));
+        for scope in scopes {
+            if let Some(slot) = scope.borrow_mut().get_mut(&name) {
+                if slot.constant {
+                    return Err(format!("Cannot assign to constant '{}'", name));
+                }
+                slot.value = value;
+                return Ok(());
+            }
+        }
+        self.define_variable(name, value);
+        Ok(())
+    }
+}
+
+// Pops a trailing `{sep: ..., end: ...}` argument off a print call. An object only counts as
+// options when it has one of those keys; any other key in it is an error so typos don't print.
+fn take_print_options(name: &str, args: &mut Vec<Value>) -> Result<(String, String), String> {
+    let mut sep = " ".to_string();
+    // print leaves the line open; println and eprint finish it
+    let mut end = if name == "print" { String::new() } else { "\n".to_string() };
+    
+    let options = match args.last() {
+        Some(Value::Object(map)) if map.get("sep").is_some() || map.get("end").is_some() => map.clone(),
+        _ => return Ok((sep, end)),
+    };
+    args.pop();
+    for (key, value) in options.iter() {
+        let slot = match key.as_str() {
+            "sep" => &mut sep,
+            "end" => &mut end,
+            other => return Err(format!("Unknown {}() option '{}' (expected 'sep' or 'end')", name, other)),
+        };
+        match value {
+            Value::String(s) => *slot = s.clone(),
+            other => return Err(format!("{}() option '{}' must be a string, got {}", name, key, other.type_name())),
+        }
+    }
+    Ok((sep, end))
 }
+
+
+// The order sort() uses without a comparator: numbers and floats by value, strings and chars
+// by character code. Anything else, or a mix of these kinds, can't be ordered.
+fn natural_order(a: &Value, b: &Value) -> Result<std::cmp::Ordering, String> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => Ok(x.cmp(y)),
+        (Value::Number(_) | Value::Float(_), Value::Number(_) | Value::Float(_)) => {
+            as_float(a).partial_cmp(&as_float(b)).ok_or_else(|| "sort() cannot order NaN".to_string())
+        }
+        (Value::String(x), Value::String(y)) => Ok(x.cmp(y)),
+        (Value::Char(x), Value::Char(y)) => Ok(x.cmp(y)),
+        (Value::Number(_) | Value::Float(_) | Value::String(_) | Value::Char(_), Value::Number(_) | Value::Float(_) | Value::String(_) | Value::Char(_)) => {
+            Err(format!("sort() cannot compare {} with {}", a.type_name(), b.type_name()))
+        }
+        _ => {
+            let bad = if matches!(a, Value::Number(_) | Value::Float(_) | Value::String(_) | Value::Char(_)) { b } else { a };
+            Err(format!("sort() can only order numbers, strings or chars, got {}", bad.type_name()))
+        }
+    }
+}
+
+// A stable merge sort whose comparison may fail. Unlike slice::sort_by it tolerates a
+// comparator that isn't a consistent ordering, which a user function might not be.
+fn merge_sort(
+    mut items: Vec<Value>,
+    cmp: &mut dyn FnMut(&Value, &Value) -> Result<std::cmp::Ordering, String>,
+) -> Result<Vec<Value>, String> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, cmp)?;
+    let right = merge_sort(right, cmp)?;
+    
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut right = right.into_iter().peekable();
+    for item in left {
+        // Take from the right only when strictly smaller, so equal items keep their order
+        while let Some(next) = right.peek() {
+            if cmp(next, &item)? == std::cmp::Ordering::Less {
+                merged.push(right.next().expect("peeked"));
+            } else {
+                break;
+            }
+        }
+        merged.push(item);
+    }
+    merged.extend(right);
+    Ok(merged)
+}
+
+fn overflow(a: i64, op: &str, b: i64) -> String {
+    format!("Arithmetic overflow: {} {} {}", a, op, b)
+}
+
+// Drops the `at f() ... called from line N` frames that call_function appends to a message
+fn strip_trace(message: &str) -> &str {
+    message.split("\n  at ").next().unwrap_or(message)
+}
+
+// Names the statements of a `pub`/`subpub` body define in its scope
+fn declared_names(stmts: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let { name, .. } | Stmt::Const { name, .. } | Stmt::FunctionDef { name, .. } => names.push(name.clone()),
+            Stmt::LetArray { names: parts, rest, .. } => {
+                names.extend(parts.iter().cloned());
+                names.extend(rest.iter().cloned());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+// Nested user function calls allowed by default
+const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+// Native stack nested calls may use by default: Rust gives spawned threads 2 MiB, and the rest
+// is left for the statements and builtins that run between calls
+const DEFAULT_MAX_STACK_SIZE: usize = 1536 * 1024;
+
+// Roughly where the caller's stack frame is
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+// Stack trace lines kept in an error before the rest are elided
+const MAX_TRACE_FRAMES: usize = 20;
+
+// More digits than an f64 can meaningfully show after the decimal point
+const MAX_FLOAT_DIGITS: usize = 20;
+
+// Largest string (in bytes) or array `*` may build, so a typo can't exhaust memory
+const MAX_REPEAT_LEN: usize = 10_000_000;
+
+fn repeat_count(count: i64, unit_len: usize) -> Result<usize, String> {
+    let count = usize::try_from(count)
+        .map_err(|_| format!("Repetition count must not be negative, got {}", count))?;
+    match unit_len.checked_mul(count) {
+        Some(total) if total <= MAX_REPEAT_LEN => Ok(count),
+        _ => Err(format!("Repetition result too large: {} x {} exceeds the limit of {}", unit_len, count, MAX_REPEAT_LEN)),
+    }
+}
+
+fn deep_merge(base: &Value, over: &Value, concat_arrays: bool) -> Value {
+    match (base, over) {
+        (Value::Object(base_map), Value::Object(over_map)) => {
+            let mut merged = base_map.clone();
+            for (key, over_val) in over_map.iter() {
+                let val = match merged.get(key) {
+                    Some(base_val) => deep_merge(base_val, over_val, concat_arrays),
+                    None => over_val.clone(),
+                };
+                merged.insert(key.clone(), val);
+            }
+            Value::Object(merged)
+        }
+        (Value::Array(base_arr), Value::Array(over_arr)) if concat_arrays => {
+            let mut items = base_arr.clone();
+            items.extend(over_arr.iter().cloned());
+            Value::Array(items)
+        }
+        _ => over.clone(),
+    }
+}
+
+// Replaces `{}` placeholders left to right; `{{` and `}}` produce literal braces
+fn format_template(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    let mut next_arg = 0;
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) => {
+                chars.next();
+                out.push('{');
+            }
+            ('}', Some('}')) => {
+                chars.next();
+                out.push('}');
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                let arg = args.get(next_arg).ok_or_else(|| format!(
+                    "format() has more placeholders than arguments ({} given)", args.len()
+                ))?;
+                out.push_str(&arg.to_string());
+                next_arg += 1;
+            }
+            ('{', _) => return Err("format() found '{' without a matching '}'".to_string()),
+            ('}', _) => return Err("format() found '}' without a matching '{'".to_string()),
+            (c, _) => out.push(c),
+        }
+    }
+    if next_arg < args.len() {
+        return Err(format!("format() got {} arguments but only {} placeholders", args.len(), next_arg));
+    }
+    Ok(out)
+}
+
+fn number_arg(func: &str, val: &Value) -> Result<f64, String> {
+    match val {
+        Value::Number(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        _ => Err(format!("{}() requires numbers, got {}", func, val.type_name())),
+    }
+}
+
+// `==` on values: arrays match element by element, and objects key by key and sets element by
+// element in any order, with numbers and floats compared by value at every level
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(_), Value::Float(_)) | (Value::Float(_), Value::Number(_)) => floats_equal(as_float(a), as_float(b)),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(p, q)| values_equal(p, q))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| values_equal(v, w)))
+        }
+        (Value::Set(x), Value::Set(y)) => {
+            x.len() == y.len() && x.iter().all(|p| y.iter().any(|q| values_equal(p, q)))
+        }
+        _ => a == b,
+    }
+}
+
+// Adds `value` to a set's elements unless an equal one is already there
+fn set_insert(items: &mut Vec<Value>, value: Value) {
+    if !items.iter().any(|item| values_equal(item, &value)) {
+        items.push(value);
+    }
+}
+
+// A float written in `text`. Rust's parser also accepts "inf" and "NaN" and overflows huge
+// exponents to infinity, none of which a Hiolang program can write as a literal.
+fn parse_finite(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+fn as_float(val: &Value) -> f64 {
+    match val {
+        Value::Number(n) => *n as f64,
+        Value::Float(f) => *f,
+        _ => 0.0,
+    }
+}
+
+// Resolves optional slice bounds, clamping out-of-range values instead of erroring
+fn slice_bounds(start: Option<Value>, end: Option<Value>, len: usize) -> Result<(usize, usize), String> {
+    let clamp = |bound: Option<Value>, default: usize| match bound {
+        None => Ok(default),
+        Some(Value::Number(n)) => Ok(n.clamp(0, len as i64) as usize),
+        Some(other) => Err(format!("Slice bounds must be numbers, got {}", other.to_string())),
+    };
+    let from = clamp(start, 0)?;
+    let to = clamp(end, len)?;
+    Ok((from, to.max(from)))
+}
+
+// Returns None at EOF, otherwise the line without its trailing newline
+fn read_stdin_line() -> Result<Option<String>, String> {
+    let mut line = String::new();
+    let bytes = std::io::stdin().read_line(&mut line)
+        .map_err(|e| format!("Failed to read input: {}", e))?;
+    if bytes == 0 {
+        return Ok(None);
+    }
+    let trimmed_len = line.trim_end_matches(&['\n', '\r'][..]).len();
+    line.truncate(trimmed_len);
+    Ok(Some(line))
+}
\ No newline at end of file
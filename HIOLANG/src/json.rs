@@ -0,0 +1,263 @@
+// JSON text for Hiolang values, used by the to_json/from_json builtins and library export
+use crate::ast::{ObjectMap, Value};
+
+// `s` as a JSON string literal, quotes included
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Compact JSON for a value. Floats keep a decimal point so they read back as floats.
+pub fn to_json(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Float(f) if f.is_finite() => Ok(format!("{:?}", f)),
+        Value::Float(f) => Err(format!("Cannot convert {} to JSON", f)),
+        Value::String(s) => Ok(quote(s)),
+        Value::Char(c) => Ok(quote(&c.to_string())),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Null | Value::Void => Ok("null".to_string()),
+        Value::Array(items) | Value::Set(items) => {
+            let items = items.iter().map(to_json).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("[{}]", items.join(",")))
+        }
+        Value::Range { start, end, step } => {
+            let items: Vec<String> = crate::ast::range_values(*start, *end, *step).map(|n| n.to_string()).collect();
+            Ok(format!("[{}]", items.join(",")))
+        }
+        Value::Object(map) => {
+            let entries = map.iter()
+                .map(|(k, v)| Ok(format!("{}:{}", quote(k), to_json(v)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("{{{}}}", entries.join(",")))
+        }
+        Value::Function(_) | Value::Builtin(_) | Value::Error { .. } | Value::File { .. } => Err(format!("Cannot convert {} to JSON", value.type_name())),
+    }
+}
+
+// Parses one JSON document. Integers that fit in an i64 become numbers, other numbers floats.
+pub fn from_json(text: &str) -> Result<Value, String> {
+    let mut reader = JsonReader { chars: text.chars().collect(), pos: 0 };
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    if reader.pos < reader.chars.len() {
+        return Err(reader.error("end of input"));
+    }
+    Ok(value)
+}
+
+struct JsonReader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonReader {
+    fn error(&self, expected: &str) -> String {
+        match self.chars.get(self.pos) {
+            Some(c) => format!("Invalid JSON at position {}: expected {}, got '{}'", self.pos, expected, c),
+            None => format!("Invalid JSON: expected {}, got end of input", expected),
+        }
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.get(self.pos), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+    
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if self.chars.get(self.pos) != Some(&expected) {
+                return Err(self.error(&format!("'{}'", word)));
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+    
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Value::String),
+            Some('t') => self.keyword("true", Value::Boolean(true)),
+            Some('f') => self.keyword("false", Value::Boolean(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("a value")),
+        }
+    }
+    
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        if self.eat(']') {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(',') {
+                return Err(self.error("',' or ']'"));
+            }
+        }
+    }
+    
+    fn object(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut map = ObjectMap::new();
+        if self.eat('}') {
+            return Ok(Value::Object(map));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.chars.get(self.pos) != Some(&'"') {
+                return Err(self.error("a string key"));
+            }
+            let key = self.string()?;
+            if !self.eat(':') {
+                return Err(self.error("':'"));
+            }
+            map.insert(key, self.value()?);
+            if self.eat('}') {
+                return Ok(Value::Object(map));
+            }
+            if !self.eat(',') {
+                return Err(self.error("',' or '}'"));
+            }
+        }
+    }
+    
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or_else(|| self.error("'\"'"))?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escape = *self.chars.get(self.pos).ok_or_else(|| self.error("an escape"))?;
+                    self.pos += 1;
+                    match escape {
+                        '"' | '\\' | '/' => out.push(escape),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => out.push(self.unicode_escape()?),
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("an escape"));
+                        }
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    self.pos -= 1;
+                    return Err(self.error("an escaped control character"));
+                }
+                c => out.push(c),
+            }
+        }
+    }
+    
+    // The XXXX of `\uXXXX`, combining a surrogate pair into one character
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            if self.chars.get(self.pos) == Some(&'\\') && self.chars.get(self.pos + 1) == Some(&'u') {
+                self.pos += 2;
+                let low = self.hex4()?;
+                if (0xDC00..0xE000).contains(&low) {
+                    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                    return char::from_u32(code).ok_or_else(|| self.error("a valid code point"));
+                }
+            }
+            return Err(self.error("a low surrogate"));
+        }
+        char::from_u32(high).ok_or_else(|| self.error("a valid code point"))
+    }
+    
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("4 hex digits"));
+        }
+        self.pos += 4;
+        Ok(u32::from_str_radix(&digits, 16).expect("checked hex digits"))
+    }
+    
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.chars.get(self.pos) == Some(&'-') {
+            self.pos += 1;
+        }
+        if !self.digits() {
+            return Err(self.error("a digit"));
+        }
+        if self.chars.get(self.pos) == Some(&'.') {
+            is_float = true;
+            self.pos += 1;
+            if !self.digits() {
+                return Err(self.error("a digit"));
+            }
+        }
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            is_float = true;
+            self.pos += 1;
+            if matches!(self.chars.get(self.pos), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if !self.digits() {
+                return Err(self.error("a digit"));
+            }
+        }
+        
+        let text: String = self.chars[start..self.pos].iter().collect();
+        if !is_float {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Value::Number(n));
+            }
+        }
+        text.parse::<f64>().map(Value::Float).map_err(|_| format!("Invalid JSON number '{}'", text))
+    }
+    
+    // Consumes a run of digits, returning whether there was at least one
+    fn digits(&mut self) -> bool {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+}
@@ -5,8 +5,10 @@ pub enum Token {
     End,
     Make,
     Inspace,
+    Import,
+    Defer,
+    With,
     Call,
-    Text,
     Pub,
     Subpub,
     Function,
@@ -14,22 +16,35 @@ pub enum Token {
     If,
     Else,
     While,
+    Do,
     For,
+    In,
     Break,
     Continue,
+    Try,
+    Catch,
+    Throw,
     Let,
+    Const,
+    Nil,
+    True,
+    False,
     
     // Identifiers and literals
     Identifier(String),
     String(String),
+    InterpolatedString(Vec<StringPart>), // a string containing `${...}`
     Number(i64),
     Float(f64),
     
     // Operators
     Plus,
+    PlusPlus,
     Minus,
+    MinusMinus,
     Star,
     Slash,
+    SlashSlash,
     Percent,
     Equal,
     EqualEqual,
@@ -55,16 +70,39 @@ pub enum Token {
     Dot,
     Arrow,
     DashArrow,
+    Question,
     
     // Special
+    Comment(String), // only produced when the lexer keeps comments; the source text, markers included
+    Unknown(char),
+    Error(String),
     Eof,
 }
 
+// A piece of an interpolated string: literal text, or the source of a `${...}` expression
+// with the position where it starts
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Code(String, Span),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    line: usize,
+    column: usize,
+    token_start: Span,
+    finished: bool, // Eof has been returned by the iterator
+    keep_comments: bool, // return comments as Token::Comment instead of skipping them
 }
 
 impl Lexer {
@@ -72,14 +110,74 @@ impl Lexer {
         let chars: Vec<char> = input.chars().collect();
         let current_char = if chars.is_empty() { None } else { Some(chars[0]) };
         
-        Lexer {
+        let mut lexer = Lexer {
             input: chars,
             position: 0,
             current_char,
+            line: 1,
+            column: 1,
+            token_start: Span { line: 1, column: 1 },
+            finished: false,
+            keep_comments: false,
+        };
+        lexer.skip_shebang();
+        lexer
+    }
+    
+    // A lexer for source that is part of a larger file, reporting positions within that file
+    pub fn starting_at(input: &str, start: Span) -> Self {
+        let mut lexer = Lexer::new(input);
+        lexer.line = start.line;
+        lexer.column = start.column;
+        lexer.token_start = start;
+        lexer
+    }
+    
+    /// Makes the lexer return each `''` and `'* ... *'` comment as a `Token::Comment` holding
+    /// its source text, for tools such as formatters. The parser skips these tokens.
+    ///
+    /// ```
+    /// use hiolang::{Lexer, Token};
+    ///
+    /// let source = "'' Squares a number\nfunction square(n) {\n  return n * n; '* no overflow check *'\n}";
+    /// let comments: Vec<String> = Lexer::new(source).keep_comments()
+    ///     .filter_map(|token| match token {
+    ///         Token::Comment(text) => Some(text),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(comments, ["'' Squares a number", "'* no overflow check *'"]);
+    ///
+    /// let tokens = Lexer::new("x = 1; '* note *' '' end").keep_comments().tokenize();
+    /// assert_eq!(tokens[4], Token::Comment("'* note *'".to_string()));
+    /// assert_eq!(tokens[5], Token::Comment("'' end".to_string()));
+    /// assert!(!hiolang::tokenize("'' end").iter().any(|t| matches!(t, Token::Comment(_))));
+    ///
+    /// let mut parser = hiolang::Parser::from_lexer(Lexer::new("'' one\nlet x = 1; '' two").keep_comments());
+    /// let (program, errors) = parser.parse();
+    /// assert_eq!((program.statements.len(), errors.len()), (1, 0));
+    /// ```
+    pub fn keep_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+    
+    fn skip_shebang(&mut self) {
+        // `#!/usr/bin/env hiolang run` is only meaningful as the very first line
+        if self.position == 0 && self.current_char == Some('#') && self.peek(1) == Some('!') {
+            while self.current_char.is_some() && self.current_char != Some('\n') {
+                self.advance();
+            }
         }
     }
     
     fn advance(&mut self) {
+        if self.current_char == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         self.position += 1;
         if self.position >= self.input.len() {
             self.current_char = None;
@@ -115,6 +213,43 @@ impl Lexer {
         }
     }
     
+    // `'*` opens a block comment only when whitespace or the end of input follows it, so
+    // single-quoted strings such as `'*'` and `'*ptr'` still lex as strings
+    fn at_block_comment(&self) -> bool {
+        self.current_char == Some('\'') && self.peek(1) == Some('*') && self.peek(2).is_none_or(char::is_whitespace)
+    }
+    
+    // The mirror rule for the closer: `*'` ends a block comment only after whitespace, so a
+    // commented-out `'*'` or `'x*'` string doesn't end it early
+    fn at_block_comment_end(&self) -> bool {
+        self.current_char == Some('*') && self.peek(1) == Some('\'')
+            && self.position.checked_sub(1).is_some_and(|prev| self.input[prev].is_whitespace())
+    }
+    
+    // Skips a `'* ... *'` block comment, which may nest. Returns false if EOF is hit first.
+    fn skip_block_comment(&mut self) -> bool {
+        let mut depth = 0;
+        loop {
+            match (self.current_char, self.peek(1)) {
+                (Some('\''), Some('*')) if self.at_block_comment() => {
+                    depth += 1;
+                    self.advance();
+                    self.advance();
+                }
+                (Some('*'), Some('\'')) if self.at_block_comment_end() => {
+                    depth -= 1;
+                    self.advance();
+                    self.advance();
+                    if depth == 0 {
+                        return true;
+                    }
+                }
+                (Some(_), _) => self.advance(),
+                (None, _) => return false,
+            }
+        }
+    }
+    
     fn read_identifier(&mut self) -> String {
         let mut result = String::new();
         while let Some(ch) = self.current_char {
@@ -128,7 +263,9 @@ impl Lexer {
         result
     }
     
-    fn read_string(&mut self, quote: char) -> String {
+    // A quoted string. `${expr}` segments make it an InterpolatedString; `\$` is a plain dollar sign.
+    fn read_string(&mut self, quote: char) -> Token {
+        let mut parts = Vec::new();
         let mut result = String::new();
         self.advance(); // skip opening quote
         
@@ -136,6 +273,18 @@ impl Lexer {
             if ch == quote {
                 self.advance(); // skip closing quote
                 break;
+            } else if ch == '$' && self.peek(1) == Some('{') {
+                self.advance();
+                self.advance();
+                let start = Span { line: self.line, column: self.column };
+                let mut code = String::new();
+                if !self.read_embedded_code(&mut code) {
+                    return Token::Error("Unterminated interpolation in string".to_string());
+                }
+                if !result.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut result)));
+                }
+                parts.push(StringPart::Code(code, start));
             } else if ch == '\\' {
                 self.advance();
                 match self.current_char {
@@ -154,16 +303,110 @@ impl Lexer {
                 self.advance();
             }
         }
-        result
+        
+        if parts.is_empty() {
+            return Token::String(result);
+        }
+        if !result.is_empty() {
+            parts.push(StringPart::Text(result));
+        }
+        Token::InterpolatedString(parts)
+    }
+    
+    // Copies the source of a `${...}` body into `code` and skips its closing brace. Braces and
+    // strings inside it are matched, so `${ {"a": "}"}.a }` ends at the right place.
+    // Returns false if the input ends first.
+    fn read_embedded_code(&mut self, code: &mut String) -> bool {
+        let mut depth = 0;
+        while let Some(ch) = self.current_char {
+            match ch {
+                '}' if depth == 0 => {
+                    self.advance();
+                    return true;
+                }
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' | '\'' => {
+                    if !self.copy_quoted(ch, code) {
+                        return false;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            code.push(ch);
+            self.advance();
+        }
+        false
+    }
+    
+    // Copies a string inside embedded code as written, including any interpolations of its own
+    fn copy_quoted(&mut self, quote: char, code: &mut String) -> bool {
+        code.push(quote);
+        self.advance();
+        while let Some(ch) = self.current_char {
+            code.push(ch);
+            self.advance();
+            if ch == quote {
+                return true;
+            } else if ch == '\\' {
+                if let Some(escaped) = self.current_char {
+                    code.push(escaped);
+                    self.advance();
+                }
+            } else if ch == '$' && self.current_char == Some('{') {
+                code.push('{');
+                self.advance();
+                if !self.read_embedded_code(code) {
+                    return false;
+                }
+                code.push('}');
+            }
+        }
+        false
+    }
+    
+    // Whether an `r"..."` or `r#"..."#` raw string starts here
+    fn at_raw_string(&self) -> bool {
+        let mut offset = 1;
+        while self.peek(offset) == Some('#') {
+            offset += 1;
+        }
+        self.current_char == Some('r') && self.peek(offset) == Some('"')
+    }
+    
+    // Raw strings keep backslashes as written. The closing quote must be followed by
+    // as many `#` as the opening one, so `r#"say "hi""#` can hold plain quotes.
+    fn read_raw_string(&mut self) -> Token {
+        self.advance(); // skip `r`
+        let mut hashes = 0;
+        while self.current_char == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        self.advance(); // skip opening quote
+        
+        let mut result = String::new();
+        while let Some(ch) = self.current_char {
+            if ch == '"' && (1..=hashes).all(|i| self.peek(i) == Some('#')) {
+                for _ in 0..=hashes {
+                    self.advance();
+                }
+                return Token::String(result);
+            }
+            result.push(ch);
+            self.advance();
+        }
+        Token::Error("Unterminated raw string".to_string())
     }
     
     fn read_number(&mut self) -> Token {
         let mut result = String::new();
         while let Some(ch) = self.current_char {
-            if ch.is_numeric() {
+            if ch.is_ascii_digit() {
                 result.push(ch);
                 self.advance();
-            } else if ch == '.' && self.peek(1).map_or(false, |c| c.is_numeric()) {
+            } else if ch == '.' && self.peek(1).map_or(false, |c| c.is_ascii_digit()) {
                 result.push(ch);
                 self.advance();
             } else {
@@ -171,35 +414,80 @@ impl Lexer {
             }
         }
         
-        if result.contains('.') {
+        // Optional exponent (`1e3`, `2.5E-4`); an `e` not followed by digits is left for the next token
+        if matches!(self.current_char, Some('e' | 'E')) {
+            let sign_len = if matches!(self.peek(1), Some('+' | '-')) { 1 } else { 0 };
+            if self.peek(1 + sign_len).is_some_and(|c| c.is_ascii_digit()) {
+                for _ in 0..=sign_len {
+                    result.extend(self.current_char);
+                    self.advance();
+                }
+                while let Some(ch) = self.current_char.filter(|c| c.is_ascii_digit()) {
+                    result.push(ch);
+                    self.advance();
+                }
+            }
+        }
+        
+        if result.contains(['.', 'e', 'E']) {
             Token::Float(result.parse().unwrap_or(0.0))
         } else {
-            Token::Number(result.parse().unwrap_or(0))
+            match result.parse() {
+                Ok(n) => Token::Number(n),
+                // One past the largest number is read as the smallest, which `-` in front of it
+                // folds to; the parser rejects it anywhere else
+                Err(_) if result.trim_start_matches('0') == "9223372036854775808" => Token::Number(i64::MIN),
+                Err(_) => Token::Error("Integer literal out of range".to_string()),
+            }
         }
     }
     
     pub fn next_token(&mut self) -> Token {
         loop {
             self.skip_whitespace();
+            self.token_start = Span { line: self.line, column: self.column };
             
             match self.current_char {
                 None => return Token::Eof,
                 Some('\'') if self.peek(1) == Some('\'') => {
+                    let start = self.position;
                     self.skip_comment();
+                    if self.keep_comments {
+                        // A CRLF line ending isn't part of the comment
+                        let text: String = self.input[start..self.position].iter().collect();
+                        return Token::Comment(text.trim_end_matches('\r').to_string());
+                    }
+                }
+                Some('\'') if self.at_block_comment() => {
+                    let start = self.position;
+                    if !self.skip_block_comment() {
+                        return Token::Error("Unterminated block comment".to_string());
+                    }
+                    if self.keep_comments {
+                        return Token::Comment(self.input[start..self.position].iter().collect());
+                    }
                 }
                 Some('+') => {
                     self.advance();
+                    if self.current_char == Some('+') {
+                        self.advance();
+                        return Token::PlusPlus;
+                    }
                     return Token::Plus;
                 }
                 Some('-') => {
                     self.advance();
+                    if self.current_char == Some('-') {
+                        self.advance();
+                        return Token::MinusMinus;
+                    }
                     if self.current_char == Some('>') {
                         self.advance();
                         return Token::Arrow;
                     }
                     return Token::Minus;
                 }
-                Some('—') => { // em-dash
+                Some('\u{2014}') => { // em-dash
                     self.advance();
                     return Token::DashArrow;
                 }
@@ -209,6 +497,10 @@ impl Lexer {
                 }
                 Some('/') => {
                     self.advance();
+                    if self.current_char == Some('/') {
+                        self.advance();
+                        return Token::SlashSlash;
+                    }
                     return Token::Slash;
                 }
                 Some('%') => {
@@ -253,10 +545,8 @@ impl Lexer {
                         self.advance();
                         return Token::And;
                     }
-                    self.position -= 1;
-                    self.current_char = Some('&');
-                    self.advance();
-                    return Token::And;
+                    // There are no bitwise operators
+                    return Token::Error("Unexpected '&', use '&&' for logical and".to_string());
                 }
                 Some('|') => {
                     self.advance();
@@ -264,10 +554,8 @@ impl Lexer {
                         self.advance();
                         return Token::Or;
                     }
-                    self.position -= 1;
-                    self.current_char = Some('|');
-                    self.advance();
-                    return Token::Or;
+                    // There are no bitwise operators
+                    return Token::Error("Unexpected '|', use '||' for logical or".to_string());
                 }
                 Some('(') => {
                     self.advance();
@@ -301,6 +589,10 @@ impl Lexer {
                     self.advance();
                     return Token::Colon;
                 }
+                Some('?') => {
+                    self.advance();
+                    return Token::Question;
+                }
                 Some(',') => {
                     self.advance();
                     return Token::Comma;
@@ -311,12 +603,14 @@ impl Lexer {
                 }
                 Some('"') | Some('\'') => {
                     let quote = self.current_char.unwrap();
-                    let result = self.read_string(quote);
-                    return Token::String(result);
+                    return self.read_string(quote);
                 }
-                Some(ch) if ch.is_numeric() => {
+                Some(ch) if ch.is_ascii_digit() => {
                     return self.read_number();
                 }
+                Some('r') if self.at_raw_string() => {
+                    return self.read_raw_string();
+                }
                 Some(ch) if ch.is_alphabetic() || ch == '_' => {
                     let identifier = self.read_identifier();
                     return match identifier.as_str() {
@@ -324,8 +618,10 @@ impl Lexer {
                         "end" => Token::End,
                         "make" => Token::Make,
                         "inspace" => Token::Inspace,
+                        "import" => Token::Import,
+                        "defer" => Token::Defer,
+                        "with" => Token::With,
                         "call" => Token::Call,
-                        "text" => Token::Text,
                         "pub" => Token::Pub,
                         "subpub" => Token::Subpub,
                         "function" => Token::Function,
@@ -333,30 +629,58 @@ impl Lexer {
                         "if" => Token::If,
                         "else" => Token::Else,
                         "while" => Token::While,
+                        "do" => Token::Do,
                         "for" => Token::For,
+                        "in" => Token::In,
                         "break" => Token::Break,
                         "continue" => Token::Continue,
+                        "try" => Token::Try,
+                        "catch" => Token::Catch,
+                        "throw" => Token::Throw,
                         "let" => Token::Let,
+                        "const" => Token::Const,
+                        "nil" => Token::Nil,
+                        "true" => Token::True,
+                        "false" => Token::False,
                         _ => Token::Identifier(identifier),
                     };
                 }
                 Some(ch) => {
                     self.advance();
+                    return Token::Unknown(ch);
                 }
             }
         }
     }
     
     pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
-        loop {
-            let token = self.next_token();
-            if token == Token::Eof {
-                tokens.push(token);
-                break;
-            }
-            tokens.push(token);
+        self.collect()
+    }
+    
+    // Like `tokenize`, but pairs each token with the position where it starts
+    pub fn tokenize_with_spans(&mut self) -> Vec<(Token, Span)> {
+        self.spanned().collect()
+    }
+    
+    // Reads tokens one at a time, each with the position where it starts, ending after Eof
+    pub fn spanned(&mut self) -> impl Iterator<Item = (Token, Span)> + '_ {
+        std::iter::from_fn(|| {
+            let token = self.next()?;
+            Some((token, self.token_start))
+        })
+    }
+}
+
+// Yields tokens as they are read, ending after the Eof token
+impl Iterator for Lexer {
+    type Item = Token;
+    
+    fn next(&mut self) -> Option<Token> {
+        if self.finished {
+            return None;
         }
-        tokens
+        let token = self.next_token();
+        self.finished = token == Token::Eof;
+        Some(token)
     }
 }
@@ -0,0 +1,80 @@
+// Hiolang as a library: the lexer, parser, interpreter, bytecode compiler and C transpiler
+// behind the `hiolang` binary, for embedding the language in other Rust programs.
+pub mod lexer;
+pub mod parser;
+pub mod ast;
+pub mod interpreter;
+pub mod compiler;
+pub mod transpiler;
+pub mod library;
+pub mod json;
+
+pub use ast::{Expr, Program, Stmt, Value};
+pub use compiler::{BytecodeOp, CompiledModule, Compiler};
+pub use interpreter::Interpreter;
+pub use lexer::{Lexer, Span, Token};
+pub use parser::{ParseError, Parser};
+
+/// Splits source into tokens, ending with `Token::Eof`. Lexical problems show up as
+/// `Token::Error` tokens rather than failing. A `Lexer` is also an iterator over the same
+/// tokens, for reading them one at a time.
+///
+/// ```
+/// use hiolang::{tokenize, Lexer, Token};
+///
+/// let tokens = tokenize("let x = 1;");
+/// assert_eq!(tokens.first(), Some(&Token::Let));
+/// assert_eq!(tokens.last(), Some(&Token::Eof));
+///
+/// let mut lexer = Lexer::new("let x = 1;");
+/// assert_eq!(lexer.next(), Some(Token::Let));
+/// let rest: Vec<Token> = lexer.collect();
+/// assert_eq!(rest, tokens[1..]);
+/// ```
+pub fn tokenize(source: &str) -> Vec<Token> {
+    Lexer::new(source).tokenize()
+}
+
+/// Parses a whole program, failing with its first error. `Parser::parse` keeps going past
+/// errors instead, returning every statement that parsed along with all the errors found;
+/// `Parser::from_lexer` parses the same way while reading tokens only as it needs them.
+///
+/// ```
+/// use hiolang::{Lexer, Parser, Span};
+///
+/// let program = hiolang::parse("let x = 1; call.print(x);").unwrap();
+/// assert_eq!(program.statements.len(), 2);
+///
+/// let (streamed, errors) = Parser::from_lexer(Lexer::new("let x = 1; call.print(x);")).parse();
+/// assert_eq!(streamed.statements.len(), 2);
+/// assert!(errors.is_empty());
+///
+/// assert!(hiolang::parse("let = 1;").is_err());
+///
+/// // Two independent mistakes are both reported, and the statements around them still parse
+/// let source = "let a = 1;\nlet = 2;\ncall.print(a);\nif (a) { let b = ; call.print(b); }\n";
+/// let (program, errors) = Parser::new(source).parse();
+/// assert_eq!(program.statements.len(), 3);
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors[0].span, Span { line: 2, column: 5 });
+/// assert_eq!(errors[1].span, Span { line: 4, column: 18 });
+/// assert_eq!(hiolang::parse(source).unwrap_err(), errors[0].message);
+/// ```
+pub fn parse(source: &str) -> Result<Program, String> {
+    let (program, errors) = Parser::new(source).parse();
+    match errors.into_iter().next() {
+        Some(e) => Err(e.message),
+        None => Ok(program),
+    }
+}
+
+/// Parses and runs a program in a fresh interpreter, returning the value of its last statement.
+///
+/// ```
+/// use hiolang::Value;
+///
+/// assert_eq!(hiolang::eval("function sq(n) { return n * n; } sq(7);"), Ok(Value::Number(49)));
+/// ```
+pub fn eval(source: &str) -> Result<Value, String> {
+    Interpreter::new().interpret(&parse(source)?)
+}
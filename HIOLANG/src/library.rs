@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
 
+use crate::ast::{ObjectMap, Value};
+use crate::json::{from_json, quote};
+
 #[derive(Debug, Clone)]
 pub struct LibraryFunction {
     pub name: String,
@@ -42,13 +45,13 @@ impl HioCLibrary {
     pub fn export_to_json(&self) -> String {
         let mut json = format!(
             r#"{{
-  "name": "{}",
-  "version": "{}",
-  "description": "{}",
-  "language": "{}",
+  "name": {},
+  "version": {},
+  "description": {},
+  "language": {},
   "functions": {{
 "#,
-            self.name, self.version, self.description, self.language
+            quote(&self.name), quote(&self.version), quote(&self.description), quote(&self.language)
         );
         
         let mut first = true;
@@ -57,18 +60,20 @@ impl HioCLibrary {
                 json.push_str(",\n");
             }
             json.push_str(&format!(
-                r#"    "{}": {{
+                r#"    {}: {{
       "params": [{}],
-      "return_type": "{}",
-      "implementation_language": "{}"
+      "return_type": {},
+      "implementation_language": {},
+      "source_code": {}
     }}"#,
-                func.name,
+                quote(&func.name),
                 func.params.iter()
-                    .map(|p| format!(r#""{}""#, p))
+                    .map(|p| quote(p))
                     .collect::<Vec<_>>()
                     .join(", "),
-                func.return_type,
-                func.implementation_language
+                quote(&func.return_type),
+                quote(&func.implementation_language),
+                quote(&func.source_code)
             ));
             first = false;
         }
@@ -76,12 +81,89 @@ impl HioCLibrary {
         json.push_str("\n  }\n}");
         json
     }
+    
+    /// Reads the document written by `export_to_json`, so libraries round-trip through files.
+    ///
+    /// ```
+    /// use hiolang::library::{HioCLibrary, LibraryFunction};
+    ///
+    /// let mut lib = HioCLibrary::new("text".into(), "1.0.0".into(), "Says \"hi\"\nand \\ bye".into(), "C".into());
+    /// lib.add_function(LibraryFunction {
+    ///     name: "greet".into(),
+    ///     params: vec!["name".into()],
+    ///     return_type: "string".into(),
+    ///     implementation_language: "C".into(),
+    ///     source_code: "printf(\"hi %s\\n\", name);".into(),
+    /// });
+    ///
+    /// let loaded = HioCLibrary::from_json(&lib.export_to_json()).unwrap();
+    /// assert_eq!(loaded.description, lib.description);
+    /// assert_eq!(loaded.get_function("greet").unwrap().source_code, "printf(\"hi %s\\n\", name);");
+    /// ```
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let root = match from_json(text)? {
+            Value::Object(map) => map,
+            other => return Err(format!("Library definition must be an object, got {}", other.type_name())),
+        };
+        let mut lib = HioCLibrary::new(
+            string_field(&root, "name", "library")?,
+            string_field(&root, "version", "library")?,
+            string_field(&root, "description", "library")?,
+            string_field(&root, "language", "library")?,
+        );
+        match root.get("functions") {
+            Some(Value::Object(functions)) => {
+                for (name, entry) in functions.iter() {
+                    let Value::Object(entry) = entry else {
+                        return Err(format!("Library function '{}' must be an object", name));
+                    };
+                    let params = match entry.get("params") {
+                        Some(Value::Array(items)) => items.iter()
+                            .map(|p| match p {
+                                Value::String(s) => Ok(s.clone()),
+                                other => Err(format!("Parameter of '{}' must be a string, got {}", name, other.type_name())),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?,
+                        _ => return Err(format!("Library function '{}' is missing 'params'", name)),
+                    };
+                    lib.add_function(LibraryFunction {
+                        name: name.clone(),
+                        params,
+                        return_type: string_field(entry, "return_type", name)?,
+                        implementation_language: string_field(entry, "implementation_language", name)?,
+                        // Exports from before source_code was written leave it out
+                        source_code: match entry.get("source_code") {
+                            Some(Value::String(s)) => s.clone(),
+                            _ => String::new(),
+                        },
+                    });
+                }
+            }
+            None => {}
+            Some(_) => return Err("Library 'functions' must be an object".to_string()),
+        }
+        Ok(lib)
+    }
+}
+
+fn string_field(map: &ObjectMap, key: &str, owner: &str) -> Result<String, String> {
+    match map.get(key) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(other) => Err(format!("'{}' of {} must be a string, got {}", key, owner, other.type_name())),
+        None => Err(format!("'{}' is missing from {}", key, owner)),
+    }
 }
 
 pub struct LibraryManager {
     libraries: HashMap<String, HioCLibrary>,
 }
 
+impl Default for LibraryManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LibraryManager {
     pub fn new() -> Self {
         LibraryManager {
@@ -101,6 +183,27 @@ impl LibraryManager {
         self.libraries.keys().cloned().collect()
     }
     
+    pub fn function_count(&self, lib: &str) -> Option<usize> {
+        self.libraries.get(lib).map(|l| l.functions.len())
+    }
+    
+    // Functions whose name contains `name`, as (library name, function) pairs sorted by library then function
+    pub fn find_function(&self, name: &str) -> Vec<(String, &LibraryFunction)> {
+        let mut found: Vec<(String, &LibraryFunction)> = self.libraries.values()
+            .flat_map(|lib| {
+                lib.functions.values()
+                    .filter(|func| func.name.contains(name))
+                    .map(move |func| (lib.name.clone(), func))
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.name.cmp(&b.1.name)));
+        found
+    }
+    
+    pub fn remove_library(&mut self, name: &str) -> bool {
+        self.libraries.remove(name).is_some()
+    }
+    
     pub fn load_from_file(&mut self, path: &Path) -> Result<(), String> {
         if let Ok(content) = fs::read_to_string(path) {
             self.parse_library_definition(&content)?;
@@ -121,6 +224,11 @@ impl LibraryManager {
     }
     
     fn parse_library_definition(&mut self, content: &str) -> Result<(), String> {
+        if content.trim_start().starts_with('{') {
+            self.register_library(HioCLibrary::from_json(content)?);
+            return Ok(());
+        }
+        
         // Simple parser for library definitions
         // In a real implementation, this would parse JSON or a custom format
         
@@ -0,0 +1,261 @@
+// Line input for the REPL: arrow-key editing and history recall on a terminal, plain
+// line reading otherwise. History is kept in a file so it survives between sessions.
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+
+// Entries kept in memory and loaded from the history file
+const MAX_HISTORY: usize = 1000;
+
+pub struct LineEditor {
+    history: Vec<String>,
+    path: Option<PathBuf>, // None once saving has failed, so the warning is shown only once
+}
+
+impl LineEditor {
+    // Loads earlier sessions' history from `path` if it exists
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let mut history: Vec<String> = path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|text| text.lines().filter(|l| !l.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        let excess = history.len().saturating_sub(MAX_HISTORY);
+        history.drain(..excess);
+        LineEditor { history, path }
+    }
+    
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+    
+    // Records a submitted line, skipping blanks and immediate repeats, and appends it to the file
+    pub fn add_history(&mut self, line: &str) {
+        if line.is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        
+        if let Some(path) = &self.path {
+            let saved = OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| writeln!(file, "{}", line));
+            if let Err(e) = saved {
+                eprintln!("Warning: history will not be saved to {}: {}", path.display(), e);
+                self.path = None;
+            }
+        }
+    }
+    
+    // Reads one line without its newline, or None at end of input
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if io::stdin().is_terminal() {
+            if let Some(_raw) = RawMode::enable() {
+                return self.edit_line(prompt);
+            }
+        }
+        
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+    
+    // The interactive editor. Left/Right/Home/End move the cursor, Up/Down walk the history,
+    // Ctrl-C clears the line and Ctrl-D on an empty line ends input.
+    fn edit_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let mut stdin = io::stdin().lock();
+        let mut stdout = io::stdout();
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // Position in the history while browsing it; history.len() is the line being typed
+        let mut browsing = self.history.len();
+        let mut draft: Vec<char> = Vec::new();
+        
+        redraw(&mut stdout, prompt, &line, cursor)?;
+        loop {
+            let Some(byte) = read_byte(&mut stdin)? else {
+                writeln!(stdout)?;
+                return Ok(None);
+            };
+            match byte {
+                b'\r' | b'\n' => {
+                    writeln!(stdout)?;
+                    return Ok(Some(line.into_iter().collect()));
+                }
+                0x04 if line.is_empty() => {
+                    writeln!(stdout)?;
+                    return Ok(None);
+                }
+                0x03 => {
+                    line.clear();
+                    cursor = 0;
+                    browsing = self.history.len();
+                    writeln!(stdout, "^C")?;
+                }
+                0x7f | 0x08 if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                0x1b => match read_escape(&mut stdin)? {
+                    Some(Key::Up) if browsing > 0 => {
+                        if browsing == self.history.len() {
+                            draft = line.clone();
+                        }
+                        browsing -= 1;
+                        line = self.history[browsing].chars().collect();
+                        cursor = line.len();
+                    }
+                    Some(Key::Down) if browsing < self.history.len() => {
+                        browsing += 1;
+                        line = match self.history.get(browsing) {
+                            Some(entry) => entry.chars().collect(),
+                            None => draft.clone(),
+                        };
+                        cursor = line.len();
+                    }
+                    Some(Key::Left) => cursor = cursor.saturating_sub(1),
+                    Some(Key::Right) => cursor = (cursor + 1).min(line.len()),
+                    Some(Key::Home) => cursor = 0,
+                    Some(Key::End) => cursor = line.len(),
+                    Some(Key::Delete) if cursor < line.len() => {
+                        line.remove(cursor);
+                    }
+                    _ => {}
+                },
+                b if b >= 0x20 && b != 0x7f => {
+                    if let Some(c) = read_utf8(&mut stdin, b)? {
+                        line.insert(cursor, c);
+                        cursor += 1;
+                    }
+                }
+                _ => {}
+            }
+            redraw(&mut stdout, prompt, &line, cursor)?;
+        }
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+// The key named by the rest of an escape sequence (`ESC [ A` and friends)
+fn read_escape(input: &mut impl Read) -> io::Result<Option<Key>> {
+    if !matches!(read_byte(input)?, Some(b'[' | b'O')) {
+        return Ok(None);
+    }
+    Ok(match read_byte(input)? {
+        Some(b'A') => Some(Key::Up),
+        Some(b'B') => Some(Key::Down),
+        Some(b'C') => Some(Key::Right),
+        Some(b'D') => Some(Key::Left),
+        Some(b'H') => Some(Key::Home),
+        Some(b'F') => Some(Key::End),
+        // `ESC [ n ~` forms; consume up to the `~`
+        Some(digit @ b'0'..=b'9') => {
+            let mut code = vec![digit];
+            while let Some(b) = read_byte(input)? {
+                if b == b'~' {
+                    break;
+                }
+                code.push(b);
+            }
+            match code.as_slice() {
+                b"1" | b"7" => Some(Key::Home),
+                b"4" | b"8" => Some(Key::End),
+                b"3" => Some(Key::Delete),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+// Completes a character whose first byte has been read; None for invalid UTF-8
+fn read_utf8(input: &mut impl Read, first: u8) -> io::Result<Option<char>> {
+    let len = match first {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(None),
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte(input)? {
+            Some(b) => bytes.push(b),
+            None => return Ok(None),
+        }
+    }
+    Ok(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()))
+}
+
+// Rewrites the prompt and line, then moves the terminal cursor back to `cursor`
+fn redraw(out: &mut impl Write, prompt: &str, line: &[char], cursor: usize) -> io::Result<()> {
+    let text: String = line.iter().collect();
+    write!(out, "\r\x1b[K{}{}", prompt, text)?;
+    if cursor < line.len() {
+        write!(out, "\x1b[{}D", line.len() - cursor)?;
+    }
+    out.flush()
+}
+
+// Unbuffered, unechoed terminal input for the lifetime of the value. The terminal is
+// switched with `stty`, which avoids platform-specific termios bindings.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    #[cfg(unix)]
+    fn enable() -> Option<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Some(RawMode { saved: saved.trim().to_string() })
+    }
+    
+    #[cfg(not(unix))]
+    fn enable() -> Option<RawMode> {
+        None
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[self.saved.as_str()]);
+    }
+}
+
+// Runs `stty` on the terminal behind stdin, returning its output if it succeeded
+fn stty(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("stty")
+        .args(args)
+        .stdin(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
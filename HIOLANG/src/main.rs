@@ -1,20 +1,38 @@
-mod lexer;
-mod parser;
-mod ast;
-mod interpreter;
-mod compiler;
-mod library;
-
 use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
+use std::time::Instant;
+
+mod line_editor;
+
+use hiolang::{ast, compiler, library};
+use hiolang::parser::Parser;
+use hiolang::lexer::{Lexer, Token};
+use hiolang::interpreter::Interpreter;
+use hiolang::compiler::Compiler;
+use hiolang::transpiler::Transpiler;
+use line_editor::LineEditor;
+use hiolang::library::{LibraryManager, create_stdlib_c, create_stdlib_cpp, create_stdlib_rust, create_stdlib_go};
+
+// Each nested Hiolang call uses several Rust frames, so the default main thread stack runs
+// out well before the interpreter's recursion limit in debug builds
+const MAIN_STACK_SIZE: usize = 256 * 1024 * 1024;
 
-use parser::Parser;
-use interpreter::Interpreter;
-use compiler::Compiler;
-use library::{LibraryManager, create_stdlib_c, create_stdlib_cpp, create_stdlib_rust, create_stdlib_go};
+// Native stack the interpreter may spend on nested calls; the rest of the thread is headroom
+const CALL_STACK_SIZE: usize = MAIN_STACK_SIZE - 16 * 1024 * 1024;
 
 fn main() {
+    let cli = std::thread::Builder::new()
+        .stack_size(MAIN_STACK_SIZE)
+        .spawn(run_cli)
+        .expect("failed to start the main thread");
+    if cli.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn run_cli() {
     let args: Vec<String> = env::args().collect();
     
     if args.len() < 2 {
@@ -27,17 +45,53 @@ fn main() {
     match command.as_str() {
         "run" => {
             if args.len() < 3 {
-                eprintln!("Usage: hiolang run <file>");
+                eprintln!("Usage: hiolang run <file|-|--stdin> [--strict] [--time] [--stats] [--profile] [--no-prelude] [args...]");
                 return;
             }
-            run_file(&args[2]);
+            // Everything after the file except our own flags goes to the script; `--` passes the rest as-is
+            let mut options = RunOptions::default();
+            let mut script_args = Vec::new();
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--" => script_args.extend(rest.by_ref().cloned()),
+                    "--strict" => options.strict = true,
+                    "--time" => options.time = true,
+                    "--stats" => options.stats = true,
+                    "--profile" => options.profile = true,
+                    "--no-prelude" => options.no_prelude = true,
+                    _ => script_args.push(arg.clone()),
+                }
+            }
+            // `--stdin` is spelled `-` from here on
+            let filename = if args[2] == "--stdin" { "-" } else { args[2].as_str() };
+            run_file(filename, &options, script_args);
         }
         "compile" => {
             if args.len() < 3 {
-                eprintln!("Usage: hiolang compile <file> [output]");
+                eprintln!("Usage: hiolang compile <file> [output] [--no-opt]");
                 return;
             }
-            compile_file(&args[2], args.get(3).map(|s| s.as_str()));
+            let optimize = !args[3..].iter().any(|a| a == "--no-opt");
+            let output = args[3..].iter().find(|a| !a.starts_with("--")).map(|s| s.as_str());
+            compile_file(&args[2], output, optimize);
+        }
+        "transpile" => {
+            if args.len() < 3 {
+                eprintln!("Usage: hiolang transpile <file> [output] [--lang c]");
+                return;
+            }
+            let mut lang = "c";
+            let mut output = None;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                if arg == "--lang" {
+                    lang = rest.next().map_or("", |s| s.as_str());
+                } else if output.is_none() {
+                    output = Some(arg.as_str());
+                }
+            }
+            transpile_file(&args[2], output, lang);
         }
         "lib" => {
             if args.len() < 3 {
@@ -54,10 +108,86 @@ fn main() {
                             create_library(&args[3], &args[4]);
                         }
                     }
+                    "find" => {
+                        if args.len() >= 4 {
+                            find_library_function(&args[3]);
+                        } else {
+                            eprintln!("Usage: hiolang lib find <FUNCNAME>");
+                        }
+                    }
                     _ => eprintln!("Unknown library command"),
                 }
             }
         }
+        "ast" => {
+            if args.len() < 3 {
+                eprintln!("Usage: hiolang ast <file>");
+                return;
+            }
+            dump_ast(&args[2]);
+        }
+        "inspect" => {
+            if args.len() < 3 {
+                eprintln!("Usage: hiolang inspect <file> [--tokens] [--ast] [--bytecode]");
+                return;
+            }
+            let mut sections = InspectSections::default();
+            for arg in &args[3..] {
+                match arg.as_str() {
+                    "--tokens" => sections.tokens = true,
+                    "--ast" => sections.ast = true,
+                    "--bytecode" => sections.bytecode = true,
+                    other => {
+                        eprintln!("Unknown inspect option: {}", other);
+                        return;
+                    }
+                }
+            }
+            // With no flags, show every stage
+            if !(sections.tokens || sections.ast || sections.bytecode) {
+                sections = InspectSections { tokens: true, ast: true, bytecode: true };
+            }
+            inspect_file(&args[2], &sections);
+        }
+        "bench" => {
+            if args.len() < 3 {
+                eprintln!("Usage: hiolang bench <file> [--iterations N] [--warmup N] [--reuse]");
+                return;
+            }
+            let mut options = BenchOptions { iterations: 10, warmup: 1, reuse: false };
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                let count = match arg.as_str() {
+                    "--reuse" => {
+                        options.reuse = true;
+                        continue;
+                    }
+                    "--iterations" | "--warmup" => rest.next().and_then(|n| n.parse::<usize>().ok()),
+                    other => {
+                        eprintln!("Unknown bench option: {}", other);
+                        return;
+                    }
+                };
+                match (arg.as_str(), count) {
+                    ("--iterations", Some(n)) if n > 0 => options.iterations = n,
+                    ("--warmup", Some(n)) => options.warmup = n,
+                    _ => {
+                        eprintln!("{} needs a count{}", arg, if arg == "--iterations" { " of at least 1" } else { "" });
+                        return;
+                    }
+                }
+            }
+            bench_file(&args[2], &options);
+        }
+        "test" => {
+            if args.len() < 3 {
+                eprintln!("Usage: hiolang test <dir>");
+                return;
+            }
+            if !run_tests(&args[2]) {
+                std::process::exit(1);
+            }
+        }
         "repl" => {
             start_repl();
         }
@@ -81,29 +211,85 @@ fn print_help() {
     println!("    hiolang <COMMAND> [ARGS]");
     println!();
     println!("COMMANDS:");
-    println!("    run <FILE>              Run a Hiolang file");
-    println!("    compile <FILE> [OUT]    Compile to bytecode");
+    println!("    run <FILE> [--strict] [ARGS...]  Run a Hiolang file, or stdin if FILE is - (--strict disables string coercion on +)");
+    println!("    run <FILE> --time | --stats      Also report wall-clock time or statements executed on stderr");
+    println!("    run <FILE> --profile             Also report how many times each function was called");
+    println!("    run <FILE> --no-prelude          Run without the prelude's functions (square, clamp, ...)");
+    println!("    compile <FILE> [OUT] [--no-opt]  Compile to bytecode (--no-opt disables optimizations)");
+    println!("    transpile <FILE> [OUT] [--lang c]  Translate to C source (printed if OUT is omitted)");
+    println!("    ast <FILE>              Print the parsed syntax tree");
+    println!("    inspect <FILE> [--tokens] [--ast] [--bytecode]  Print the tokens, syntax tree and/or bytecode (all if no flags)");
+    println!("    test <DIR>              Run every .hio file in DIR and report pass/fail");
+    println!("    bench <FILE> [--iterations N] [--warmup N] [--reuse]  Time repeated runs (output discarded)");
     println!("    lib                     List available libraries");
     println!("    lib info <NAME>         Show library information");
     println!("    lib create <NAME> <LANG> Create a new library");
+    println!("    lib find <FUNCNAME>     Find library functions whose name contains FUNCNAME");
     println!("    repl                    Start interactive REPL");
     println!("    version                 Show version");
     println!("    help                    Show this help message");
 }
 
-fn run_file(filename: &str) {
-    let path = Path::new(filename);
-    
-    match fs::read_to_string(path) {
+// Reads a program from a file, or all of `stdin` when the filename is `-`
+fn read_program(filename: &str, stdin: impl Read) -> io::Result<String> {
+    if filename == "-" {
+        io::read_to_string(stdin)
+    } else {
+        fs::read_to_string(Path::new(filename))
+    }
+}
+
+// Flags of `hiolang run`
+#[derive(Default)]
+struct RunOptions {
+    strict: bool, // disables string coercion on `+`
+    time: bool, // report wall-clock time
+    stats: bool, // report how many statements ran
+    profile: bool, // report how often each function was called
+    no_prelude: bool, // run without the functions the prelude defines
+}
+
+fn run_file(filename: &str, options: &RunOptions, script_args: Vec<String>) {
+    match read_program(filename, io::stdin()) {
         Ok(content) => {
-            match execute_code(&content) {
+            let mut interpreter = if options.no_prelude { Interpreter::without_prelude() } else { Interpreter::new() };
+            interpreter.set_max_stack_size(CALL_STACK_SIZE);
+            if filename != "-" {
+                interpreter.set_source_path(filename);
+            }
+            if options.profile {
+                interpreter.enable_profiling();
+            }
+            let started = Instant::now();
+            let result = execute_code(&mut interpreter, &content, options.strict, script_args);
+            let elapsed = started.elapsed();
+            match result {
+                // exit() and panic() have nothing further to report
+                _ if interpreter.exit_code().is_some() => {}
                 Ok(result) => {
                     println!("Result: {}", result.to_string());
                 }
                 Err(e) => {
-                    eprintln!("Error: {}", e);
+                    print_error(&content, &e);
                 }
             }
+            // Reported on stderr so they don't mix with the script's own output
+            if options.time {
+                eprintln!("Time: {:.3} ms", elapsed.as_secs_f64() * 1000.0);
+            }
+            if options.stats {
+                eprintln!("Statements executed: {}", interpreter.statements_executed());
+            }
+            if options.profile {
+                print_profile(&interpreter.profile_report());
+            }
+            if let Some(code) = interpreter.exit_code() {
+                let _ = io::stdout().flush();
+                std::process::exit(code);
+            }
+        }
+        Err(e) if filename == "-" => {
+            eprintln!("Failed to read program from stdin: {}", e);
         }
         Err(e) => {
             eprintln!("Failed to read file {}: {}", filename, e);
@@ -111,42 +297,211 @@ fn run_file(filename: &str) {
     }
 }
 
-fn execute_code(code: &str) -> Result<ast::Value, String> {
-    let mut parser = Parser::new(code);
-    let program = parser.parse()?;
+// Prints an error, with the source line and a caret under the column when the message has a
+// position. Errors from an imported file point into that file, so they get no excerpt.
+fn print_error(source: &str, message: &str) {
+    match error_location(message) {
+        Some((line, col)) if !message.contains("\n  at import of ") => {
+            let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+            eprint!("{}", error_report(source, line, col, message, color));
+        }
+        _ => eprintln!("Error: {}", message),
+    }
+}
+
+// The `line:col` of a message ending its first line with "at line L:C", as parse errors do
+fn error_location(message: &str) -> Option<(usize, usize)> {
+    let first = message.lines().next()?;
+    let (_, position) = first.rsplit_once(" at line ")?;
+    let (line, col) = position.split_once(':')?;
+    Some((line.parse().ok()?, col.parse().ok()?))
+}
+
+// `message` followed by source line `line` and a `^` under column `col` (both 1-based):
+//
+//   Error: Unexpected token: Semicolon at line 2:14
+//     2 | let x = (1 + ;
+//       |              ^
+fn error_report(source: &str, line: usize, col: usize, message: &str, color: bool) -> String {
+    let (red, bold, reset) = if color { ("\x1b[31;1m", "\x1b[1m", "\x1b[0m") } else { ("", "", "") };
+    let mut out = format!("{}Error{}: {}{}{}\n", red, reset, bold, message, reset);
+    if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+        let gutter = line.to_string();
+        // Tabs are copied so the caret lines up however wide they are shown
+        let pad: String = text.chars().take(col.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        out.push_str(&format!("  {} | {}\n", gutter, text));
+        out.push_str(&format!("  {} | {}{}^{}\n", " ".repeat(gutter.len()), pad, red, reset));
+    }
+    out
+}
+
+fn print_profile(report: &[(String, u64)]) {
+    let width = report.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Function".len());
+    eprintln!("{:<width$}  Calls", "Function");
+    for (name, count) in report {
+        eprintln!("{:<width$}  {}", name, count);
+    }
+}
+
+fn execute_code(interpreter: &mut Interpreter, code: &str, strict: bool, script_args: Vec<String>) -> Result<ast::Value, String> {
+    let program = hiolang::parse(code)?;
     
-    let mut interpreter = Interpreter::new();
+    interpreter.set_string_coercion(!strict);
+    interpreter.set_script_args(script_args);
     interpreter.interpret(&program)
 }
 
-fn compile_file(filename: &str, output: Option<&str>) {
+// Settings of `hiolang bench`
+struct BenchOptions {
+    iterations: usize, // timed runs
+    warmup: usize, // untimed runs before them
+    reuse: bool, // run every iteration in the same interpreter instead of a fresh one
+}
+
+// Runs a file repeatedly with its output discarded and prints timing statistics as
+// `key=value` lines, so scripts can read them
+fn bench_file(filename: &str, options: &BenchOptions) {
+    let content = match fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read file {}: {}", filename, e);
+            return;
+        }
+    };
+    
+    let clock = Instant::now();
+    let mut times = match bench_runs(filename, &content, options, || clock.elapsed().as_secs_f64() * 1000.0) {
+        Ok(times) => times,
+        Err((run, e)) => {
+            eprintln!("Run {} failed:", run);
+            print_error(&content, &e);
+            return;
+        }
+    };
+    
+    let (min, mean, median, max) = time_summary(&mut times);
+    println!("iterations={}", times.len());
+    println!("warmup={}", options.warmup);
+    println!("min_ms={:.3}", min);
+    println!("mean_ms={:.3}", mean);
+    println!("median_ms={:.3}", median);
+    println!("max_ms={:.3}", max);
+}
+
+// The time each timed run of `content` took, read off `now_ms`, a clock in milliseconds.
+// A failed run stops the benchmark with its 1-based number and error; exit() counts as success.
+fn bench_runs(filename: &str, content: &str, options: &BenchOptions, mut now_ms: impl FnMut() -> f64) -> Result<Vec<f64>, (usize, String)> {
+    let fresh_interpreter = || {
+        let mut interpreter = Interpreter::with_output(io::sink());
+        interpreter.set_max_stack_size(CALL_STACK_SIZE);
+        interpreter.set_source_path(filename);
+        interpreter
+    };
+    let mut interpreter = fresh_interpreter();
+    let mut times = Vec::with_capacity(options.iterations);
+    for run in 0..options.warmup + options.iterations {
+        if run > 0 && !options.reuse {
+            interpreter = fresh_interpreter();
+        }
+        let started = now_ms();
+        let result = execute_code(&mut interpreter, content, false, Vec::new());
+        let elapsed = now_ms() - started;
+        if let Err(e) = result {
+            if interpreter.exit_code().is_none() {
+                return Err((run + 1, e));
+            }
+        }
+        if run >= options.warmup {
+            times.push(elapsed);
+        }
+    }
+    Ok(times)
+}
+
+// Minimum, mean, median and maximum of a non-empty list of times
+fn time_summary(times: &mut [f64]) -> (f64, f64, f64, f64) {
+    times.sort_by(f64::total_cmp);
+    let n = times.len();
+    let mean = times.iter().sum::<f64>() / n as f64;
+    let median = if n % 2 == 1 { times[n / 2] } else { (times[n / 2 - 1] + times[n / 2]) / 2.0 };
+    (times[0], mean, median, times[n - 1])
+}
+
+// Runs each `.hio` file in `dir`; a file passes if it executes without error
+fn run_tests(dir: &str) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read directory {}: {}", dir, e);
+            return false;
+        }
+    };
+    
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hio"))
+        .collect();
+    files.sort();
+    
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in &files {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_stack_size(CALL_STACK_SIZE);
+        interpreter.set_source_path(path);
+        let result = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| execute_code(&mut interpreter, &content, false, Vec::new()));
+        match (result, interpreter.exit_code()) {
+            (_, Some(0)) | (Ok(_), None) => {
+                println!("PASS {}", path.display());
+                passed += 1;
+            }
+            (_, Some(code)) => {
+                println!("FAIL {}: exited with status {}", path.display(), code);
+                failed += 1;
+            }
+            (Err(e), None) => {
+                println!("FAIL {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    
+    println!();
+    println!("{} passed, {} failed, {} total", passed, failed, files.len());
+    failed == 0
+}
+
+fn compile_file(filename: &str, output: Option<&str>, optimize: bool) {
     let path = Path::new(filename);
     
     match fs::read_to_string(path) {
         Ok(content) => {
-            let mut parser = Parser::new(&content);
-            match parser.parse() {
-                Ok(program) => {
-                    let mut compiler = Compiler::new();
-                    match compiler.compile(&program) {
-                        Ok(bytecode) => {
-                            let output_file = output.unwrap_or("a.hio");
-                            match serialize_bytecode(&bytecode, output_file) {
-                                Ok(_) => {
-                                    println!("Successfully compiled to {}", output_file);
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to write bytecode: {}", e);
-                                }
-                            }
+            let Some(program) = parse_reporting(&content) else {
+                return;
+            };
+            let mut compiler = Compiler::new();
+            compiler.set_optimize(optimize);
+            match compiler.compile_program(&program) {
+                Ok(module) => {
+                    for warning in compiler.get_warnings() {
+                        eprintln!("Warning: {}", warning);
+                    }
+                    let output_file = output.unwrap_or("a.hio");
+                    match serialize_bytecode(&module, output_file) {
+                        Ok(_) => {
+                            println!("Successfully compiled to {}", output_file);
                         }
                         Err(e) => {
-                            eprintln!("Compilation error: {}", e);
+                            eprintln!("Failed to write bytecode: {}", e);
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("Parse error: {}", e);
+                    eprintln!("Compilation error: {}", e);
                 }
             }
         }
@@ -156,38 +511,164 @@ fn compile_file(filename: &str, output: Option<&str>) {
     }
 }
 
-fn serialize_bytecode(bytecode: &[compiler::BytecodeOp], filename: &str) -> Result<(), String> {
-    let serialized = format!("{:#?}", bytecode);
-    fs::write(filename, serialized)
+// Parses a file for the commands that process it without running it, printing every syntax
+// error in it rather than just the first. None when there were any.
+fn parse_reporting(content: &str) -> Option<ast::Program> {
+    let (program, errors) = Parser::new(content).parse();
+    for e in &errors {
+        eprintln!("Parse error: {}", e);
+    }
+    errors.is_empty().then_some(program)
+}
+
+fn transpile_file(filename: &str, output: Option<&str>, lang: &str) {
+    if lang != "c" {
+        eprintln!("Unsupported target language: {} (only 'c' is available)", lang);
+        return;
+    }
+    
+    let content = match fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read file {}: {}", filename, e);
+            return;
+        }
+    };
+    
+    let Some(program) = parse_reporting(&content) else {
+        return;
+    };
+    
+    match Transpiler::new().transpile(&program) {
+        Ok(source) => match output {
+            Some(path) => match fs::write(path, source) {
+                Ok(_) => println!("Successfully transpiled to {}", path),
+                Err(e) => eprintln!("Failed to write {}: {}", path, e),
+            },
+            None => print!("{}", source),
+        },
+        Err(e) => eprintln!("Transpile error: {}", e),
+    }
+}
+
+fn dump_ast(filename: &str) {
+    match fs::read_to_string(filename) {
+        Ok(content) => {
+            if let Some(program) = parse_reporting(&content) {
+                print!("{}", ast::pretty_print(&program));
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to read file {}: {}", filename, e);
+        }
+    }
+}
+
+// Which stages `hiolang inspect` prints
+#[derive(Default)]
+struct InspectSections {
+    tokens: bool,
+    ast: bool,
+    bytecode: bool,
+}
+
+// Prints each requested stage of processing a file under its own header. Later stages stop
+// at the first error, but the sections before it are still shown.
+fn inspect_file(filename: &str, sections: &InspectSections) {
+    let content = match fs::read_to_string(filename) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read file {}: {}", filename, e);
+            return;
+        }
+    };
+    
+    if sections.tokens {
+        println!("== Tokens ==");
+        print!("{}", token_listing(&content));
+    }
+    if !(sections.ast || sections.bytecode) {
+        return;
+    }
+    
+    let Some(program) = parse_reporting(&content) else {
+        return;
+    };
+    if sections.ast {
+        if sections.tokens {
+            println!();
+        }
+        println!("== AST ==");
+        print!("{}", ast::pretty_print(&program));
+    }
+    
+    if sections.bytecode {
+        if sections.tokens || sections.ast {
+            println!();
+        }
+        println!("== Bytecode ==");
+        let mut compiler = Compiler::new();
+        match compiler.compile_program(&program) {
+            Ok(module) => {
+                // Label the first instruction of each linked function
+                let mut labels: Vec<(usize, &String)> = module.symbols.iter().map(|(name, addr)| (*addr, name)).collect();
+                labels.sort();
+                for (offset, op) in module.main.iter().enumerate() {
+                    for (_, name) in labels.iter().filter(|(addr, _)| *addr == offset) {
+                        println!("{}:", name);
+                    }
+                    println!("{:>6}  {:?}", offset, op);
+                }
+            }
+            Err(e) => eprintln!("Compilation error: {}", e),
+        }
+    }
+}
+
+// One line per token of `source`, with the line:column it starts at
+fn token_listing(source: &str) -> String {
+    Lexer::new(source).spanned()
+        .map(|(token, span)| format!("{:>4}:{:<4} {:?}\n", span.line, span.column, token))
+        .collect()
+}
+
+fn serialize_bytecode(module: &compiler::CompiledModule, filename: &str) -> Result<(), String> {
+    // Sorted so the same program always serializes to the same bytes
+    let symbols: std::collections::BTreeMap<_, _> = module.symbols.iter().collect();
+    let functions: std::collections::BTreeMap<_, _> = module.functions.iter().collect();
+    let serialized = format!("symbols: {:#?}\ncode: {:#?}\nfunctions: {:#?}", symbols, module.main, functions);
+    let mut bytes = compiler::write_header(module);
+    bytes.extend_from_slice(serialized.as_bytes());
+    fs::write(filename, bytes)
         .map_err(|e| format!("IO error: {}", e))?;
     Ok(())
 }
 
-fn list_libraries() {
+// A manager with the built-in standard libraries registered
+fn stdlib_manager() -> LibraryManager {
     let mut manager = LibraryManager::new();
-    
-    // Register built-in libraries
     manager.register_library(create_stdlib_c());
     manager.register_library(create_stdlib_cpp());
     manager.register_library(create_stdlib_rust());
     manager.register_library(create_stdlib_go());
+    manager
+}
+
+fn list_libraries() {
+    let manager = stdlib_manager();
     
     println!("Available Libraries:");
     for lib_name in manager.list_libraries() {
         if let Some(lib) = manager.get_library(&lib_name) {
-            println!("  {} v{} ({})", lib.name, lib.version, lib.language);
+            let count = manager.function_count(&lib_name).unwrap_or(0);
+            println!("  {} v{} ({}, {} functions)", lib.name, lib.version, lib.language, count);
             println!("    {}", lib.description);
         }
     }
 }
 
 fn show_library_info(lib_name: &str) {
-    let mut manager = LibraryManager::new();
-    
-    manager.register_library(create_stdlib_c());
-    manager.register_library(create_stdlib_cpp());
-    manager.register_library(create_stdlib_rust());
-    manager.register_library(create_stdlib_go());
+    let manager = stdlib_manager();
     
     if let Some(lib) = manager.get_library(lib_name) {
         println!("Library: {} v{}", lib.name, lib.version);
@@ -208,6 +689,19 @@ fn show_library_info(lib_name: &str) {
     }
 }
 
+fn find_library_function(name: &str) {
+    let manager = stdlib_manager();
+    let found = manager.find_function(name);
+    
+    if found.is_empty() {
+        println!("No library functions match '{}'", name);
+        return;
+    }
+    for (lib_name, func) in found {
+        println!("  {}: {}({}) -> {}", lib_name, func.name, func.params.join(", "), func.return_type);
+    }
+}
+
 fn create_library(name: &str, language: &str) {
     let lib = library::HioCLibrary::new(
         name.to_string(),
@@ -230,20 +724,40 @@ fn create_library(name: &str, language: &str) {
 }
 
 fn start_repl() {
-    use std::io::{self, Write};
-    
     println!("Hiolang REPL v0.1.0");
     println!("Type 'exit' to quit, 'help' for commands");
     println!();
     
+    // One interpreter for the whole session so definitions persist between lines
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_stack_size(CALL_STACK_SIZE);
+    let mut editor = LineEditor::new(history_path());
+    // Lines of an entry that isn't complete yet
+    let mut pending = String::new();
+    
     loop {
-        print!("hio> ");
-        io::stdout().flush().unwrap();
-        
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
+        let prompt = if pending.is_empty() { "hio> " } else { "...> " };
+        match editor.read_line(prompt) {
+            Ok(None) => break,
+            Ok(Some(input)) => {
                 let trimmed = input.trim();
+                editor.add_history(trimmed);
+                
+                // Keep reading until the entry is complete; an empty line runs it as it is
+                if !pending.is_empty() {
+                    if !trimmed.is_empty() {
+                        pending.push('\n');
+                        pending.push_str(&input);
+                        if is_incomplete(&pending) {
+                            continue;
+                        }
+                    }
+                    let source = std::mem::take(&mut pending);
+                    if !eval_repl_entry(&mut interpreter, &source) {
+                        break;
+                    }
+                    continue;
+                }
                 
                 if trimmed == "exit" {
                     println!("Goodbye!");
@@ -255,6 +769,20 @@ fn start_repl() {
                     println!("  exit  - Exit the REPL");
                     println!("  help  - Show this message");
                     println!("  clear - Clear the screen");
+                    println!("  :vars - List defined variables and functions");
+                    println!("  :history - List previously entered lines (Up/Down recall them)");
+                    continue;
+                }
+                
+                if trimmed == ":history" {
+                    for (i, line) in editor.history().iter().enumerate() {
+                        println!("{:5}  {}", i + 1, line);
+                    }
+                    continue;
+                }
+                
+                if trimmed == ":vars" {
+                    print_repl_vars(&interpreter);
                     continue;
                 }
                 
@@ -267,15 +795,12 @@ fn start_repl() {
                     continue;
                 }
                 
-                match execute_code(trimmed) {
-                    Ok(result) => {
-                        if result != ast::Value::Void {
-                            println!("=> {}", result.to_string());
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error: {}", e);
-                    }
+                if is_incomplete(trimmed) {
+                    pending = input;
+                    continue;
+                }
+                if !eval_repl_entry(&mut interpreter, trimmed) {
+                    break;
                 }
             }
             Err(_) => break,
@@ -283,15 +808,163 @@ fn start_repl() {
     }
 }
 
-impl PartialEq for ast::Value {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (ast::Value::Number(a), ast::Value::Number(b)) => a == b,
-            (ast::Value::Float(a), ast::Value::Float(b)) => (a - b).abs() < f64::EPSILON,
-            (ast::Value::String(a), ast::Value::String(b)) => a == b,
-            (ast::Value::Boolean(a), ast::Value::Boolean(b)) => a == b,
-            (ast::Value::Void, ast::Value::Void) => true,
-            _ => false,
+// Runs one entry and prints its value or error. Returns false when it called exit() or
+// panic(), which end the session instead of the process.
+fn eval_repl_entry(interpreter: &mut Interpreter, source: &str) -> bool {
+    let result = hiolang::parse(source)
+        .and_then(|program| interpreter.interpret(&program));
+    if interpreter.exit_code().is_some() {
+        return false;
+    }
+    match result {
+        Ok(result) => {
+            if result != ast::Value::Void {
+                println!("=> {}", result.debug_string());
+            }
+        }
+        Err(e) => {
+            print_error(source, &e);
+        }
+    }
+    true
+}
+
+// Whether REPL input stops partway: inside an unclosed bracket, string or block comment, or
+// in the middle of a statement. Other syntax errors count as complete so they get reported.
+fn is_incomplete(source: &str) -> bool {
+    let tokens = Lexer::new(source).tokenize_with_spans();
+    let mut depth = 0i32;
+    for (token, _) in &tokens {
+        match token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+            Token::Error(msg) if msg.starts_with("Unterminated") => return true,
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return depth > 0;
+    }
+    
+    // A parse error right at the end of the input means more was expected
+    let end = tokens.last().map(|(_, span)| (span.line, span.column));
+    match hiolang::parse(source) {
+        Err(e) => error_location(&e) == end,
+        Ok(_) => false,
+    }
+}
+
+// ~/.hiolang_history, or None when there is no home directory to put it in
+fn history_path() -> Option<std::path::PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".hiolang_history"))
+}
+
+fn print_repl_vars(interpreter: &Interpreter) {
+    let globals = interpreter.globals_snapshot();
+    
+    println!("Variables:");
+    for (name, value) in &globals {
+        match value {
+            ast::Value::Function(_) | ast::Value::Builtin(_) => {}
+            v => println!("  {} = {} ({})", name, v.debug_string(), v.type_name()),
+        }
+    }
+    
+    println!("Functions:");
+    for (name, value) in &globals {
+        if let ast::Value::Function(func_name) = value {
+            for params in interpreter.function_overloads(func_name) {
+                println!("  {}({})", name, params.join(", "));
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dash_reads_the_program_from_stdin() {
+        let program = read_program("-", "println(1);\nprintln(2);".as_bytes()).unwrap();
+        assert_eq!(program, "println(1);\nprintln(2);");
+        assert_eq!(read_program("-", io::empty()).unwrap(), "");
+    }
+
+    #[test]
+    fn a_filename_reads_the_file_and_leaves_stdin_alone() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/transpile_small.hio");
+        let program = read_program(path, "ignored".as_bytes()).unwrap();
+        assert_eq!(program, fs::read_to_string(path).unwrap());
+        assert!(read_program("no/such/file.hio", "ignored".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn error_reports_put_a_caret_under_the_column() {
+        let source = "let a = 1;\nlet x = (1 + ;\n";
+        let message = "Unexpected token: Semicolon at line 2:14";
+        assert_eq!(error_location(message), Some((2, 14)));
+        assert_eq!(
+            error_report(source, 2, 14, message, false),
+            "Error: Unexpected token: Semicolon at line 2:14\n  2 | let x = (1 + ;\n    |              ^\n"
+        );
+        // Tabs are kept so the caret lines up under them
+        assert_eq!(error_report("\tx = ;", 1, 6, "m", false), "Error: m\n  1 | \tx = ;\n    | \t    ^\n");
+        // A line past the end of the source leaves just the message
+        assert_eq!(error_report(source, 9, 1, "m", false), "Error: m\n");
+        assert!(error_report(source, 2, 14, message, true).contains("\x1b[31;1m^\x1b[0m"));
+    }
+
+    #[test]
+    fn repl_input_is_incomplete_until_brackets_strings_and_statements_close() {
+        for source in ["function f() {", "let xs = [1,\n2", "println(\"open", "'* note", "let x =", "let x = 1 +"] {
+            assert!(is_incomplete(source), "{:?} should be incomplete", source);
+        }
+        for source in ["", "let x = 1;", "function f() { return 1; }", "println(\"a\");", "'* note *' 1;"] {
+            assert!(!is_incomplete(source), "{:?} should be complete", source);
+        }
+        // Errors before the end, including an extra closing bracket, are reported instead of waiting
+        for source in ["let = 1;", "println(1));", "let x = ) + 1"] {
+            assert!(!is_incomplete(source), "{:?} should be reported", source);
+        }
+    }
+
+    #[test]
+    fn bench_times_only_the_runs_after_the_warmup() {
+        let options = BenchOptions { iterations: 3, warmup: 2, reuse: false };
+        // Each reading of the clock is a millisecond later than the one before, and the run
+        // itself is between two readings
+        let mut readings = 0;
+        let times = bench_runs("bench.hio", "let x = 1 + 2;", &options, || {
+            readings += 1;
+            readings as f64
+        });
+        assert_eq!(times, Ok(vec![1.0, 1.0, 1.0]));
+        assert_eq!(readings, 10);
+        assert_eq!(time_summary(&mut [4.0, 1.0, 3.0, 2.0]), (1.0, 2.5, 2.5, 4.0));
+    }
+
+    #[test]
+    fn bench_stops_at_the_first_failed_run_but_not_at_exit() {
+        let fresh = BenchOptions { iterations: 5, warmup: 0, reuse: false };
+        let reused = BenchOptions { reuse: true, ..fresh };
+        // Only a reused interpreter still has `seen` from the run before
+        let source = "let again = 0; try { seen; again = 1; } catch (e) { } if (again == 1) { throw \"again\"; } let seen = 1;";
+        assert_eq!(bench_runs("bench.hio", source, &fresh, || 0.0).map(|times| times.len()), Ok(5));
+        assert_eq!(bench_runs("bench.hio", source, &reused, || 0.0), Err((2, "again".to_string())));
+        assert_eq!(bench_runs("bench.hio", "exit(0);", &fresh, || 0.0).map(|times| times.len()), Ok(5));
+    }
+
+    #[test]
+    fn token_listing_names_each_token_at_its_position() {
+        let listing = token_listing("let x = 42;\nprintln(x && \"hi\");");
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines[..5], ["   1:1    Let", "   1:5    Identifier(\"x\")", "   1:7    Equal", "   1:9    Number(42)", "   1:11   Semicolon"]);
+        for name in ["Identifier(\"println\")", "LeftParen", "And", "String(\"hi\")", "RightParen", "Eof"] {
+            assert!(listing.contains(name), "{} missing from\n{}", name, listing);
         }
+        assert!(lines[5].starts_with("   2:1 "), "{}", lines[5]);
     }
 }
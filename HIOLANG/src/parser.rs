@@ -1,18 +1,82 @@
-use crate::lexer::{Lexer, Token};
+use crate::lexer::{Lexer, Span, StringPart, Token};
 use crate::ast::*;
+use std::fmt;
+
+// Tokens the parser may look past the current one
+const LOOKAHEAD: usize = 2;
+
+/// A syntax or lexical error found while parsing, and the position it was found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     position: usize,
+    labels: Vec<String>, // labels of the loops enclosing the current statement
+    lexer: Option<Lexer>, // the rest of the input when reading lazily; None once Eof is buffered
+    errors: Vec<ParseError>, // bad tokens read so far, and statements skipped after an error
 }
 
 impl Parser {
+    // Lexes the whole input up front, so a lexer error anywhere is reported before parsing starts
     pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
-        let tokens = lexer.tokenize();
-        Parser {
-            tokens,
+        let mut parser = Parser::from_lexer(Lexer::new(input));
+        parser.fill(usize::MAX);
+        parser
+    }
+    
+    // Reads tokens from `lexer` only as parsing reaches them, keeping just the current statement's
+    // tokens buffered. Lexer errors are reported when the parser gets to them.
+    pub fn from_lexer(lexer: Lexer) -> Self {
+        let mut parser = Parser {
+            tokens: Vec::new(),
+            spans: Vec::new(),
             position: 0,
+            labels: Vec::new(),
+            lexer: Some(lexer),
+            errors: Vec::new(),
+        };
+        parser.fill(LOOKAHEAD);
+        parser
+    }
+    
+    // Buffers tokens until index `last` is available or the input ends
+    fn fill(&mut self, last: usize) {
+        let Some(lexer) = &mut self.lexer else {
+            return;
+        };
+        while self.tokens.len() <= last {
+            let Some((token, span)) = lexer.spanned().next() else {
+                break;
+            };
+            if matches!(token, Token::Comment(_)) {
+                continue;
+            }
+            let message = match &token {
+                Token::Unknown(ch) => Some(format!("Unexpected character '{}' at line {}:{}", ch, span.line, span.column)),
+                Token::Error(msg) => Some(format!("Lexer error: {} at line {}:{}", msg, span.line, span.column)),
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.errors.push(ParseError { message, span });
+            }
+            let done = token == Token::Eof;
+            self.tokens.push(token);
+            self.spans.push(span);
+            if done {
+                self.lexer = None;
+                return;
+            }
         }
     }
     
@@ -20,6 +84,19 @@ impl Parser {
         self.tokens.get(self.position).unwrap_or(&Token::Eof)
     }
     
+    fn current_span(&self) -> Span {
+        self.spans.get(self.position)
+            .or_else(|| self.spans.last())
+            .copied()
+            .unwrap_or(Span { line: 1, column: 1 })
+    }
+    
+    // The error for 9223372036854775808 where no `-` is in front of it
+    fn literal_out_of_range(&self) -> String {
+        let span = self.current_span();
+        format!("Integer literal out of range at line {}:{}", span.line, span.column)
+    }
+    
     fn peek_token(&self) -> &Token {
         self.tokens.get(self.position + 1).unwrap_or(&Token::Eof)
     }
@@ -28,6 +105,7 @@ impl Parser {
         if self.position < self.tokens.len() {
             self.position += 1;
         }
+        self.fill(self.position + LOOKAHEAD);
     }
     
     fn expect(&mut self, expected: Token) -> Result<(), String> {
@@ -35,50 +113,187 @@ impl Parser {
             self.advance();
             Ok(())
         } else {
-            Err(format!("Expected {:?}, got {:?}", expected, self.current_token()))
+            let span = self.current_span();
+            Err(format!("Expected {:?}, got {:?} at line {}:{}", expected, self.current_token(), span.line, span.column))
         }
     }
     
-    pub fn parse(&mut self) -> Result<Program, String> {
+    // Parses the whole input without stopping at errors. A statement that fails to parse is
+    // reported and skipped, and parsing carries on after it, so the program holds every
+    // statement that did parse. Errors are in source order.
+    pub fn parse(&mut self) -> (Program, Vec<ParseError>) {
         let mut statements = Vec::new();
         
         while self.current_token() != &Token::Eof {
-            statements.push(self.parse_statement()?);
+            let start = self.position;
+            statements.extend(self.parse_statement_recovering());
+            // A stray `}` closes no block at the top level, so step over it
+            if self.position == start {
+                self.advance();
+            }
+            self.discard_parsed();
+        }
+        
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|e| (e.span.line, e.span.column));
+        (Program::new(statements), errors)
+    }
+    
+    // Parses a statement, or records why it failed and skips to the end of it: past the next
+    // `;`, or up to a `}` that closes the enclosing block. Braces opened inside the statement
+    // are skipped over whole.
+    fn parse_statement_recovering(&mut self) -> Option<Stmt> {
+        let start = self.position;
+        let message = match self.parse_statement() {
+            Ok(stmt) => return Some(stmt),
+            Err(message) => message,
+        };
+        
+        // A bad token is the real cause of whatever parse error it led to, and is already reported
+        let end = self.position.min(self.tokens.len().saturating_sub(1));
+        let lexed_badly = self.tokens.get(start..=end).unwrap_or_default().iter()
+            .any(|token| matches!(token, Token::Unknown(_) | Token::Error(_)));
+        if !lexed_badly {
+            self.errors.push(ParseError { message, span: self.current_span() });
         }
         
-        Ok(Program::new(statements))
+        let mut depth = self.tokens.get(start..self.position).unwrap_or_default().iter()
+            .fold(0usize, |depth, token| match token {
+                Token::LeftBrace => depth + 1,
+                Token::RightBrace => depth.saturating_sub(1),
+                _ => depth,
+            });
+        loop {
+            match self.current_token() {
+                Token::Eof => break,
+                Token::Semicolon if depth == 0 => {
+                    self.advance();
+                    break;
+                }
+                Token::RightBrace if depth == 0 => break,
+                Token::RightBrace => {
+                    depth -= 1;
+                    self.advance();
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                Token::LeftBrace => {
+                    depth += 1;
+                    self.advance();
+                }
+                _ => self.advance(),
+            }
+        }
+        None
+    }
+    
+    // Drops the tokens of finished statements when reading lazily
+    fn discard_parsed(&mut self) {
+        if self.lexer.is_some() {
+            self.tokens.drain(..self.position);
+            self.spans.drain(..self.position);
+            self.position = 0;
+        }
     }
     
     fn parse_statement(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         match self.current_token() {
             Token::Space => self.parse_space(),
             Token::Pub => self.parse_pub(),
             Token::Subpub => self.parse_subpub(),
+            Token::Inspace => self.parse_inspace(),
+            Token::Import => {
+                self.advance();
+                let path = match self.current_token() {
+                    Token::String(path) => path.clone(),
+                    _ => return Err("Expected a file path string after 'import'".to_string()),
+                };
+                self.advance();
+                self.expect(Token::Semicolon)?;
+                Ok(Stmt::Import(path))
+            }
+            Token::Defer => {
+                self.advance();
+                if self.current_token() == &Token::Function {
+                    return Err("A function definition cannot be deferred".to_string());
+                }
+                Ok(Stmt::Defer(Box::new(self.parse_statement()?)))
+            }
             Token::Let => self.parse_let(),
+            Token::Const => self.parse_const(),
             Token::If => self.parse_if(),
             Token::While => self.parse_while(),
+            Token::Do => self.parse_do_while(),
             Token::For => self.parse_for(),
             Token::Return => self.parse_return(),
+            Token::Try => self.parse_try(),
+            Token::With => self.parse_with(),
+            Token::Throw => {
+                self.advance();
+                let value = self.parse_expression()?;
+                self.expect(Token::Semicolon)?;
+                Ok(Stmt::Throw(value, line))
+            }
             Token::Break => {
                 self.advance();
+                let label = self.parse_jump_label()?;
                 self.expect(Token::Semicolon)?;
-                Ok(Stmt::Break)
+                Ok(Stmt::Break(label))
             }
             Token::Continue => {
                 self.advance();
+                let label = self.parse_jump_label()?;
                 self.expect(Token::Semicolon)?;
-                Ok(Stmt::Continue)
+                Ok(Stmt::Continue(label))
             }
             Token::LeftBrace => self.parse_block(),
             Token::Function => self.parse_function_def(),
+            Token::Identifier(_) if self.peek_token() == &Token::Equal => self.parse_assign(),
+            Token::Identifier(_) if self.peek_token() == &Token::Colon => self.parse_labeled(),
             _ => {
                 let expr = self.parse_expression()?;
                 self.expect(Token::Semicolon)?;
-                Ok(Stmt::Expression(expr))
+                Ok(Stmt::Expression(expr, line))
             }
         }
     }
     
+    fn parse_labeled(&mut self) -> Result<Stmt, String> {
+        let label = match self.current_token() {
+            Token::Identifier(l) => l.clone(),
+            _ => return Err("Expected label".to_string()),
+        };
+        self.advance();
+        self.expect(Token::Colon)?;
+        
+        if !matches!(self.current_token(), Token::While | Token::Do | Token::For) {
+            let span = self.current_span();
+            return Err(format!("Label '{}' must be followed by a loop at line {}:{}", label, span.line, span.column));
+        }
+        
+        self.labels.push(label.clone());
+        let body = self.parse_statement();
+        self.labels.pop();
+        
+        Ok(Stmt::Labeled { label, body: Box::new(body?) })
+    }
+    
+    // The optional label after `break`/`continue`, which must name an enclosing loop
+    fn parse_jump_label(&mut self) -> Result<Option<String>, String> {
+        let label = match self.current_token() {
+            Token::Identifier(l) => l.clone(),
+            _ => return Ok(None),
+        };
+        if !self.labels.contains(&label) {
+            let span = self.current_span();
+            return Err(format!("Unknown loop label '{}' at line {}:{}", label, span.line, span.column));
+        }
+        self.advance();
+        Ok(Some(label))
+    }
+    
     fn parse_space(&mut self) -> Result<Stmt, String> {
         self.expect(Token::Space)?;
         let name = match self.current_token() {
@@ -90,12 +305,15 @@ impl Parser {
             _ => return Err("Expected identifier after 'space'".to_string()),
         };
         
-        self.expect(Token::Identifier("name".to_string()))?; // Skip "name" keyword if present
+        // Older sources write `space Foo name { ... }`; the `name` word is optional
+        if self.current_token() == &Token::Identifier("name".to_string()) && self.peek_token() == &Token::LeftBrace {
+            self.advance();
+        }
         self.expect(Token::LeftBrace)?;
         
         let mut body = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            body.extend(self.parse_statement_recovering());
         }
         
         self.expect(Token::RightBrace)?;
@@ -108,56 +326,145 @@ impl Parser {
     
     fn parse_pub(&mut self) -> Result<Stmt, String> {
         self.expect(Token::Pub)?;
-        self.expect(Token::Semicolon)?;
-        self.expect(Token::LeftBrace)?;
+        let (mode, body) = self.parse_section("interpretation")?;
+        Ok(Stmt::Pub { mode, body })
+    }
+    
+    fn parse_subpub(&mut self) -> Result<Stmt, String> {
+        self.expect(Token::Subpub)?;
+        let (mode, body) = self.parse_section("compilation")?;
+        Ok(Stmt::Subpub { mode, body })
+    }
+    
+    // The rest of a `pub` or `subpub`: an optional `;`, an optional mode header such as
+    // `{;com 'interpretation'};` or `{compilation};`, the body, and an optional `—`/`->`
+    fn parse_section(&mut self, default_mode: &str) -> Result<(String, Vec<Stmt>), String> {
+        if self.current_token() == &Token::Semicolon {
+            self.advance();
+        }
         
-        let kind = match self.current_token() {
-            Token::Semicolon => {
+        let mut mode = default_mode.to_string();
+        if self.at_mode_header() {
+            self.advance();
+            if self.current_token() == &Token::Semicolon {
                 self.advance();
-                "interpretation".to_string()
             }
-            _ => "interpretation".to_string(),
-        };
-        
-        self.expect(Token::Identifier("com".to_string()))?;
-        let name = if let Token::String(s) = self.current_token().clone() {
-            self.advance();
-            s
-        } else {
-            return Err("Expected string token".into());
-        };
+            // `{compilation}` names the mode directly; `{com 'interpretation'}` gives it as a string
+            if let Token::Identifier(word) = self.current_token() {
+                mode = word.clone();
+                self.advance();
+            }
+            if let Token::String(s) = self.current_token() {
+                mode = s.clone();
+                self.advance();
+            }
+            self.expect(Token::RightBrace)?;
+            if self.current_token() == &Token::Semicolon {
+                self.advance();
+            }
+        }
         
-        let mut body = Vec::new();
         self.expect(Token::LeftBrace)?;
+        let mut body = Vec::new();
+        let mut terminated = false;
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            // The optional `—` terminator may come just before the closing brace, as in `—}`
+            if matches!(self.current_token(), Token::DashArrow | Token::Arrow) && self.peek_token() == &Token::RightBrace {
+                self.advance();
+                terminated = true;
+                break;
+            }
+            body.extend(self.parse_statement_recovering());
         }
         self.expect(Token::RightBrace)?;
-        self.expect(Token::DashArrow)?;
+        // The em-dash terminator is hard to type, so ASCII `->` is accepted too
+        if !terminated && matches!(self.current_token(), Token::DashArrow | Token::Arrow) {
+            self.advance();
+        }
         
-        Ok(Stmt::Pub { name, kind, body })
+        Ok((mode, body))
     }
     
-    fn parse_subpub(&mut self) -> Result<Stmt, String> {
-        self.expect(Token::Subpub)?;
-        self.expect(Token::Semicolon)?;
-        self.expect(Token::LeftBrace)?;
-        
-        let compilation_type = "compilation".to_string();
-        let name = "subpub_block".to_string();
+    // Just inside a `{` literal, whether its first entry is a set element rather than a `key:`.
+    // `{}` is an empty object, and `{[k]: v}` needs a scan to its `]` to tell it from `{[1, 2]}`.
+    fn at_set_literal(&mut self) -> bool {
+        match self.current_token() {
+            Token::RightBrace | Token::End => false,
+            Token::String(_) | Token::Identifier(_) | Token::Number(_) | Token::True | Token::False => {
+                self.peek_token() != &Token::Colon
+            }
+            Token::LeftBracket => {
+                let mut depth = 0;
+                let mut i = self.position;
+                loop {
+                    self.fill(i + 1);
+                    match self.tokens.get(i) {
+                        Some(Token::LeftBracket) => depth += 1,
+                        Some(Token::RightBracket) => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return self.tokens.get(i + 1) != Some(&Token::Colon);
+                            }
+                        }
+                        Some(Token::Eof) | None => return true,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+            _ => true,
+        }
+    }
+    
+    // A `{` that opens a mode header rather than a body: `{;`, `{word}` or `{word 'text'`
+    fn at_mode_header(&self) -> bool {
+        if self.current_token() != &Token::LeftBrace {
+            return false;
+        }
+        let after = self.tokens.get(self.position + LOOKAHEAD).unwrap_or(&Token::Eof);
+        match self.peek_token() {
+            Token::Semicolon => true,
+            Token::Identifier(_) => matches!(after, Token::String(_) | Token::RightBrace),
+            _ => false,
+        }
+    }
+    
+    fn parse_inspace(&mut self) -> Result<Stmt, String> {
+        self.expect(Token::Inspace)?;
+        // `inspace.Name;{ ... }` is accepted as well as `inspace Name { ... }`
+        if self.current_token() == &Token::Dot {
+            self.advance();
+        }
+        let space = match self.current_token() {
+            Token::Identifier(n) => {
+                let name = n.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected space name after 'inspace'".to_string()),
+        };
+        if self.current_token() == &Token::Semicolon {
+            self.advance();
+        }
         
+        self.expect(Token::LeftBrace)?;
         let mut body = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            body.extend(self.parse_statement_recovering());
         }
         self.expect(Token::RightBrace)?;
         
-        Ok(Stmt::Subpub { name, compilation_type, body })
+        Ok(Stmt::Inspace { space, body })
     }
     
     fn parse_let(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::Let)?;
         
+        if self.current_token() == &Token::LeftBracket {
+            return self.parse_let_array(line);
+        }
+        
         let name = match self.current_token() {
             Token::Identifier(n) => {
                 let name = n.clone();
@@ -171,10 +478,93 @@ impl Parser {
         let value = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
         
-        Ok(Stmt::Let { name, value })
+        Ok(Stmt::Let { name, value, line })
+    }
+    
+    fn parse_const(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
+        self.expect(Token::Const)?;
+        
+        let name = match self.current_token() {
+            Token::Identifier(n) => {
+                let name = n.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected identifier after 'const'".to_string()),
+        };
+        
+        self.expect(Token::Equal)?;
+        let value = self.parse_expression()?;
+        self.expect(Token::Semicolon)?;
+        
+        Ok(Stmt::Const { name, value, line })
+    }
+    
+    // `let [a, b, ...rest] = expr;` - the rest binding is optional and must come last
+    fn parse_let_array(&mut self, line: usize) -> Result<Stmt, String> {
+        self.expect(Token::LeftBracket)?;
+        
+        let mut names = Vec::new();
+        let mut rest = None;
+        while self.current_token() != &Token::RightBracket {
+            let is_rest = self.current_token() == &Token::Dot;
+            if is_rest {
+                self.expect(Token::Dot)?;
+                self.expect(Token::Dot)?;
+                self.expect(Token::Dot)?;
+            }
+            
+            let name = match self.current_token() {
+                Token::Identifier(n) => n.clone(),
+                _ => {
+                    let span = self.current_span();
+                    return Err(format!("Expected identifier in destructuring pattern at line {}:{}", span.line, span.column));
+                }
+            };
+            self.advance();
+            
+            if is_rest {
+                rest = Some(name);
+                break;
+            }
+            names.push(name);
+            
+            if self.current_token() == &Token::Comma {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(Token::RightBracket)?;
+        
+        self.expect(Token::Equal)?;
+        let value = self.parse_expression()?;
+        self.expect(Token::Semicolon)?;
+        
+        Ok(Stmt::LetArray { names, rest, value, line })
+    }
+    
+    fn parse_assign(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
+        let target = match self.current_token() {
+            Token::Identifier(n) => {
+                let name = n.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected identifier in assignment".to_string()),
+        };
+        
+        self.expect(Token::Equal)?;
+        let value = self.parse_expression()?;
+        self.expect(Token::Semicolon)?;
+        
+        Ok(Stmt::Assign { target, value, line })
     }
     
     fn parse_if(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::If)?;
         self.expect(Token::LeftParen)?;
         let condition = self.parse_expression()?;
@@ -183,7 +573,7 @@ impl Parser {
         
         let mut then_branch = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            then_branch.push(self.parse_statement()?);
+            then_branch.extend(self.parse_statement_recovering());
         }
         self.expect(Token::RightBrace)?;
         
@@ -192,7 +582,7 @@ impl Parser {
             self.expect(Token::LeftBrace)?;
             let mut branch = Vec::new();
             while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-                branch.push(self.parse_statement()?);
+                branch.extend(self.parse_statement_recovering());
             }
             self.expect(Token::RightBrace)?;
             Some(branch)
@@ -200,10 +590,11 @@ impl Parser {
             None
         };
         
-        Ok(Stmt::If { condition, then_branch, else_branch })
+        Ok(Stmt::If { condition, then_branch, else_branch, line })
     }
     
     fn parse_while(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::While)?;
         self.expect(Token::LeftParen)?;
         let condition = self.parse_expression()?;
@@ -212,17 +603,47 @@ impl Parser {
         
         let mut body = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            body.extend(self.parse_statement_recovering());
         }
         self.expect(Token::RightBrace)?;
         
-        Ok(Stmt::While { condition, body })
+        let else_branch = self.parse_loop_else()?;
+        
+        Ok(Stmt::While { condition, body, else_branch, line })
+    }
+    
+    fn parse_do_while(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
+        self.expect(Token::Do)?;
+        self.expect(Token::LeftBrace)?;
+        
+        let mut body = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            body.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        self.expect(Token::While)?;
+        self.expect(Token::LeftParen)?;
+        let condition = self.parse_expression()?;
+        self.expect(Token::RightParen)?;
+        self.expect(Token::Semicolon)?;
+        
+        Ok(Stmt::DoWhile { body, condition, line })
     }
     
     fn parse_for(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::For)?;
         self.expect(Token::LeftParen)?;
         
+        if let (Token::Identifier(var), Token::In) = (self.current_token(), self.peek_token()) {
+            let var = var.clone();
+            self.advance();
+            self.advance();
+            return self.parse_for_in(var, line);
+        }
+        
         let init = if self.current_token() != &Token::Semicolon {
             Some(Box::new(self.parse_statement()?))
         } else {
@@ -247,14 +668,47 @@ impl Parser {
         
         let mut body = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            body.extend(self.parse_statement_recovering());
         }
         self.expect(Token::RightBrace)?;
         
-        Ok(Stmt::For { init, condition, increment, body })
+        let else_branch = self.parse_loop_else()?;
+        
+        Ok(Stmt::For { init, condition, increment, body, else_branch, line })
+    }
+    
+    fn parse_for_in(&mut self, var: String, line: usize) -> Result<Stmt, String> {
+        let iterable = self.parse_expression()?;
+        self.expect(Token::RightParen)?;
+        self.expect(Token::LeftBrace)?;
+        
+        let mut body = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            body.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        Ok(Stmt::ForIn { var, iterable, body, line })
+    }
+    
+    fn parse_loop_else(&mut self) -> Result<Option<Vec<Stmt>>, String> {
+        if self.current_token() != &Token::Else {
+            return Ok(None);
+        }
+        
+        self.advance();
+        self.expect(Token::LeftBrace)?;
+        let mut branch = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            branch.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        Ok(Some(branch))
     }
     
     fn parse_return(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::Return)?;
         
         let value = if self.current_token() != &Token::Semicolon {
@@ -264,7 +718,65 @@ impl Parser {
         };
         self.expect(Token::Semicolon)?;
         
-        Ok(Stmt::Return(value))
+        Ok(Stmt::Return(value, line))
+    }
+    
+    fn parse_try(&mut self) -> Result<Stmt, String> {
+        self.expect(Token::Try)?;
+        self.expect(Token::LeftBrace)?;
+        
+        let mut body = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            body.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        self.expect(Token::Catch)?;
+        self.expect(Token::LeftParen)?;
+        let var = match self.current_token() {
+            Token::Identifier(n) => {
+                let name = n.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected identifier after 'catch ('".to_string()),
+        };
+        self.expect(Token::RightParen)?;
+        self.expect(Token::LeftBrace)?;
+        
+        let mut handler = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            handler.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        Ok(Stmt::Try { body, var, handler })
+    }
+    
+    fn parse_with(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
+        self.expect(Token::With)?;
+        self.expect(Token::LeftParen)?;
+        let var = match self.current_token() {
+            Token::Identifier(n) => {
+                let name = n.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected identifier after 'with ('".to_string()),
+        };
+        self.expect(Token::Equal)?;
+        let value = self.parse_expression()?;
+        self.expect(Token::RightParen)?;
+        self.expect(Token::LeftBrace)?;
+        
+        let mut body = Vec::new();
+        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+            body.extend(self.parse_statement_recovering());
+        }
+        self.expect(Token::RightBrace)?;
+        
+        Ok(Stmt::With { var, value, body, line })
     }
     
     fn parse_block(&mut self) -> Result<Stmt, String> {
@@ -272,7 +784,7 @@ impl Parser {
         let mut statements = Vec::new();
         
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            statements.push(self.parse_statement()?);
+            statements.extend(self.parse_statement_recovering());
         }
         
         self.expect(Token::RightBrace)?;
@@ -281,6 +793,7 @@ impl Parser {
     }
     
     fn parse_function_def(&mut self) -> Result<Stmt, String> {
+        let line = self.current_span().line;
         self.expect(Token::Function)?;
         
         let name = match self.current_token() {
@@ -311,18 +824,41 @@ impl Parser {
         self.expect(Token::RightParen)?;
         self.expect(Token::LeftBrace)?;
         
+        // Loop labels don't reach into function bodies
+        let outer_labels = std::mem::take(&mut self.labels);
         let mut body = Vec::new();
         while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement()?);
+            body.extend(self.parse_statement_recovering());
         }
+        self.labels = outer_labels;
         
         self.expect(Token::RightBrace)?;
         
-        Ok(Stmt::FunctionDef { name, params, body })
+        Ok(Stmt::FunctionDef { name, params, body, line })
     }
     
     fn parse_expression(&mut self) -> Result<Expr, String> {
-        self.parse_logical_or()
+        self.parse_ternary()
+    }
+    
+    fn parse_ternary(&mut self) -> Result<Expr, String> {
+        let cond = self.parse_logical_or()?;
+        
+        if self.current_token() != &Token::Question {
+            return Ok(cond);
+        }
+        
+        self.advance();
+        let then = self.parse_expression()?;
+        self.expect(Token::Colon)?;
+        // Right-associative, so `a ? b : c ? d : e` nests in the else branch
+        let otherwise = self.parse_ternary()?;
+        
+        Ok(Expr::Ternary {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        })
     }
     
     fn parse_logical_or(&mut self) -> Result<Expr, String> {
@@ -378,7 +914,8 @@ impl Parser {
     }
     
     fn parse_comparison(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_additive()?;
+        let first = self.parse_additive()?;
+        let mut rest = Vec::new();
         
         while let Some(op) = match self.current_token() {
             Token::Less => Some(BinaryOp::Less),
@@ -388,15 +925,22 @@ impl Parser {
             _ => None,
         } {
             self.advance();
-            let right = self.parse_additive()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+            rest.push((op, self.parse_additive()?));
         }
         
-        Ok(left)
+        // A single comparison stays a plain binary node; longer chains read like math
+        if rest.len() <= 1 {
+            return Ok(match rest.pop() {
+                Some((op, right)) => Expr::Binary {
+                    left: Box::new(first),
+                    op,
+                    right: Box::new(right),
+                },
+                None => first,
+            });
+        }
+        
+        Ok(Expr::Compare { first: Box::new(first), rest })
     }
     
     fn parse_additive(&mut self) -> Result<Expr, String> {
@@ -425,6 +969,7 @@ impl Parser {
         while let Some(op) = match self.current_token() {
             Token::Star => Some(BinaryOp::Multiply),
             Token::Slash => Some(BinaryOp::Divide),
+            Token::SlashSlash => Some(BinaryOp::IntDivide),
             Token::Percent => Some(BinaryOp::Modulo),
             _ => None,
         } {
@@ -451,12 +996,26 @@ impl Parser {
                 })
             }
             Token::Minus => {
+                self.advance();
+                if self.current_token() == &Token::Number(i64::MIN) {
+                    self.advance();
+                    return Ok(Expr::Number(i64::MIN));
+                }
+                // A minus in front of a literal is part of the literal
+                match self.parse_unary()? {
+                    Expr::Number(n) if n.checked_neg().is_some() => Ok(Expr::Number(-n)),
+                    Expr::Float(f) => Ok(Expr::Float(-f)),
+                    expr => Ok(Expr::Unary {
+                        op: UnaryOp::Negate,
+                        expr: Box::new(expr),
+                    }),
+                }
+            }
+            Token::PlusPlus | Token::MinusMinus => {
+                let increment = self.current_token() == &Token::PlusPlus;
                 self.advance();
                 let expr = self.parse_unary()?;
-                Ok(Expr::Unary {
-                    op: UnaryOp::Negate,
-                    expr: Box::new(expr),
-                })
+                update_target(expr, increment, true)
             }
             _ => self.parse_postfix(),
         }
@@ -468,6 +1027,7 @@ impl Parser {
         loop {
             match self.current_token() {
                 Token::LeftParen => {
+                    let line = self.current_span().line;
                     self.advance();
                     let mut args = Vec::new();
                     
@@ -482,16 +1042,37 @@ impl Parser {
                     expr = Expr::Call {
                         func: Box::new(expr),
                         args,
+                        line,
                     };
                 }
                 Token::LeftBracket => {
                     self.advance();
-                    let index = self.parse_expression()?;
-                    self.expect(Token::RightBracket)?;
-                    expr = Expr::Index {
-                        object: Box::new(expr),
-                        index: Box::new(index),
+                    let start = if self.current_token() != &Token::Colon {
+                        Some(Box::new(self.parse_expression()?))
+                    } else {
+                        None
                     };
+                    
+                    if self.current_token() == &Token::Colon {
+                        self.advance();
+                        let end = if self.current_token() != &Token::RightBracket {
+                            Some(Box::new(self.parse_expression()?))
+                        } else {
+                            None
+                        };
+                        self.expect(Token::RightBracket)?;
+                        expr = Expr::Slice {
+                            object: Box::new(expr),
+                            start,
+                            end,
+                        };
+                    } else {
+                        self.expect(Token::RightBracket)?;
+                        expr = Expr::Index {
+                            object: Box::new(expr),
+                            index: start.ok_or("Expected index expression")?,
+                        };
+                    }
                 }
                 Token::Dot => {
                     self.advance();
@@ -507,6 +1088,11 @@ impl Parser {
                         _ => return Err("Expected member name after '.'".to_string()),
                     }
                 }
+                Token::PlusPlus | Token::MinusMinus => {
+                    let increment = self.current_token() == &Token::PlusPlus;
+                    self.advance();
+                    expr = update_target(expr, increment, false)?;
+                }
                 _ => break,
             }
         }
@@ -516,6 +1102,7 @@ impl Parser {
     
     fn parse_primary(&mut self) -> Result<Expr, String> {
         match self.current_token().clone() {
+            Token::Number(i64::MIN) => Err(self.literal_out_of_range()),
             Token::Number(n) => {
                 let val = n;
                 self.advance();
@@ -531,6 +1118,25 @@ impl Parser {
                 self.advance();
                 Ok(Expr::String(val))
             }
+            Token::InterpolatedString(parts) => {
+                self.advance();
+                let parts = parts.into_iter()
+                    .map(|part| match part {
+                        StringPart::Text(text) => Ok(Expr::String(text)),
+                        StringPart::Code(code, start) => parse_interpolation(&code, start),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(Expr::Interpolated(parts))
+            }
+            Token::Nil => {
+                self.advance();
+                Ok(Expr::Null)
+            }
+            Token::True | Token::False => {
+                let value = self.current_token() == &Token::True;
+                self.advance();
+                Ok(Expr::Boolean(value))
+            }
             Token::Identifier(id) => {
                 self.advance();
                 Ok(Expr::Identifier(id))
@@ -555,13 +1161,72 @@ impl Parser {
                 self.expect(Token::RightBracket)?;
                 Ok(Expr::Array(elements))
             }
+            Token::LeftBrace => {
+                self.advance();
+                if self.at_set_literal() {
+                    let mut elements = Vec::new();
+                    while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+                        elements.push(self.parse_expression()?);
+                        if self.current_token() == &Token::Comma {
+                            self.advance();
+                        }
+                    }
+                    self.expect(Token::RightBrace)?;
+                    return Ok(Expr::Set(elements));
+                }
+                let mut pairs = Vec::new();
+                
+                while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
+                    let key = match self.current_token() {
+                        Token::String(k) | Token::Identifier(k) => ObjectKey::Name(k.clone()),
+                        Token::Number(i64::MIN) => return Err(self.literal_out_of_range()),
+                        Token::Number(n) => ObjectKey::Name(n.to_string()),
+                        // `end` is a keyword for `end make;`, but print options use it as a key
+                        Token::End => ObjectKey::Name("end".to_string()),
+                        Token::True => ObjectKey::Name("true".to_string()),
+                        Token::False => ObjectKey::Name("false".to_string()),
+                        Token::LeftBracket => {
+                            self.advance();
+                            let key = self.parse_expression()?;
+                            if self.current_token() != &Token::RightBracket {
+                                return Err(format!("Expected ']' after computed key, got {:?}", self.current_token()));
+                            }
+                            ObjectKey::Computed(key)
+                        }
+                        _ => return Err(format!("Expected object key, got {:?}", self.current_token())),
+                    };
+                    self.advance();
+                    self.expect(Token::Colon)?;
+                    pairs.push((key, self.parse_expression()?));
+                    if self.current_token() == &Token::Comma {
+                        self.advance();
+                    }
+                }
+                
+                self.expect(Token::RightBrace)?;
+                Ok(Expr::Object(pairs))
+            }
             Token::Call => {
+                let line = self.current_span().line;
                 self.advance();
                 self.expect(Token::Dot)?;
                 match self.current_token() {
                     Token::Identifier(func_name) => {
-                        let name = func_name.clone();
+                        let mut func = Expr::Identifier(func_name.clone());
                         self.advance();
+                        
+                        // `call.writeutil.text(...)` calls a member of a namespace
+                        while self.current_token() == &Token::Dot {
+                            self.advance();
+                            match self.current_token() {
+                                Token::Identifier(member) => {
+                                    func = Expr::Member { object: Box::new(func), member: member.clone() };
+                                    self.advance();
+                                }
+                                _ => return Err("Expected member name after '.'".to_string()),
+                            }
+                        }
+                        
                         self.expect(Token::LeftParen)?;
                         let mut args = Vec::new();
                         while self.current_token() != &Token::RightParen {
@@ -572,14 +1237,44 @@ impl Parser {
                         }
                         self.expect(Token::RightParen)?;
                         Ok(Expr::Call {
-                            func: Box::new(Expr::Identifier(name)),
+                            func: Box::new(func),
                             args,
+                            line,
                         })
                     }
                     _ => Err("Expected function name after 'call.'".to_string()),
                 }
             }
-            _ => Err(format!("Unexpected token: {:?}", self.current_token())),
+            _ => {
+                let span = self.current_span();
+                Err(format!("Unexpected token: {:?} at line {}:{}", self.current_token(), span.line, span.column))
+            }
         }
     }
 }
+
+// `++`/`--` read and write a variable, so the operand must be a plain identifier
+fn update_target(expr: Expr, increment: bool, prefix: bool) -> Result<Expr, String> {
+    match expr {
+        Expr::Identifier(target) => Ok(Expr::Update { target, increment, prefix }),
+        _ => Err(format!("Operator {} requires a variable", if increment { "++" } else { "--" })),
+    }
+}
+
+// Parses the body of a `${...}` segment, which must be exactly one expression
+fn parse_interpolation(code: &str, start: Span) -> Result<Expr, String> {
+    let mut parser = Parser::from_lexer(Lexer::starting_at(code, start));
+    if parser.current_token() == &Token::Eof {
+        return Err(format!("Empty interpolation at line {}:{}", start.line, start.column));
+    }
+    let expr = parser.parse_expression();
+    if let Some(e) = parser.errors.first() {
+        return Err(e.message.clone());
+    }
+    let expr = expr?;
+    if parser.current_token() != &Token::Eof {
+        let span = parser.current_span();
+        return Err(format!("Unexpected token in interpolation: {:?} at line {}:{}", parser.current_token(), span.line, span.column));
+    }
+    Ok(expr)
+}
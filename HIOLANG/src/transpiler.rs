@@ -0,0 +1,561 @@
+use crate::ast::*;
+use std::collections::HashMap;
+
+// Static types for the subset of Hiolang that maps directly onto C
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CType {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Void,
+}
+
+impl CType {
+    fn c_name(self) -> &'static str {
+        match self {
+            CType::Int => "long long",
+            CType::Float => "double",
+            CType::Bool => "int",
+            CType::Str => "const char *",
+            CType::Void => "void",
+        }
+    }
+    
+    fn hio_name(self) -> &'static str {
+        match self {
+            CType::Int => "number",
+            CType::Float => "float",
+            CType::Bool => "boolean",
+            CType::Str => "string",
+            CType::Void => "void",
+        }
+    }
+    
+    fn is_numeric(self) -> bool {
+        matches!(self, CType::Int | CType::Float)
+    }
+}
+
+// A transpiled expression: C source text plus the type it evaluates to
+struct CExpr {
+    code: String,
+    ty: CType,
+}
+
+struct FunctionSig {
+    arity: usize,
+    returns_value: bool,
+}
+
+// Translates a program into a standalone C source file. Only numbers, floats,
+// strings and booleans are supported; functions take and return integers and
+// must be defined at the top level.
+pub struct Transpiler {
+    scopes: Vec<HashMap<String, CType>>,
+    functions: HashMap<String, FunctionSig>,
+    in_function: bool,
+    indent: usize,
+    out: String,
+}
+
+impl Default for Transpiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transpiler {
+    pub fn new() -> Self {
+        Transpiler {
+            scopes: Vec::new(),
+            functions: HashMap::new(),
+            in_function: false,
+            indent: 0,
+            out: String::new(),
+        }
+    }
+    
+    pub fn transpile(&mut self, program: &Program) -> Result<String, String> {
+        let mut defs = Vec::new();
+        collect_functions(&program.statements, &mut defs);
+        for (name, params, body) in &defs {
+            let sig = FunctionSig { arity: params.len(), returns_value: returns_value(body) };
+            if self.functions.insert(name.to_string(), sig).is_some() {
+                return Err(format!("Function '{}' is defined more than once", name));
+            }
+        }
+        
+        self.out.push_str("/* Generated by hiolang transpile */\n");
+        self.out.push_str("#include <stdio.h>\n");
+        self.out.push_str("#include <string.h>\n\n");
+        
+        // Prototypes first so functions can call each other in any order
+        for (name, params, _) in &defs {
+            let signature = self.signature(name, params);
+            self.line(&format!("{};", signature));
+        }
+        if !defs.is_empty() {
+            self.out.push('\n');
+        }
+        for (name, params, body) in &defs {
+            self.emit_function(name, params, body)?;
+            self.out.push('\n');
+        }
+        
+        self.line("int main(void) {");
+        self.in_function = false;
+        self.emit_body(&program.statements)?;
+        self.indent += 1;
+        self.line("return 0;");
+        self.indent -= 1;
+        self.line("}");
+        
+        Ok(std::mem::take(&mut self.out))
+    }
+    
+    fn signature(&self, name: &str, params: &[String]) -> String {
+        let ret = if self.functions[name].returns_value { CType::Int } else { CType::Void };
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.iter()
+                .map(|p| format!("{} {}", CType::Int.c_name(), p))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!("{} {}({})", ret.c_name(), name, params)
+    }
+    
+    fn emit_function(&mut self, name: &str, params: &[String], body: &[Stmt]) -> Result<(), String> {
+        self.line(&format!("{} {{", self.signature(name, params)));
+        
+        // Parameters share the outermost frame with the body's own `let`s, as in the interpreter
+        self.scopes = vec![params.iter().map(|p| (p.clone(), CType::Int)).collect()];
+        self.in_function = true;
+        self.indent += 1;
+        self.emit_stmts(body)?;
+        if self.functions[name].returns_value && !matches!(body.last(), Some(Stmt::Return(..))) {
+            self.line("return 0;");
+        }
+        self.indent -= 1;
+        self.scopes.clear();
+        
+        self.line("}");
+        Ok(())
+    }
+    
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"    ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+    
+    fn lookup(&self, name: &str) -> Option<CType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+    
+    fn emit_body(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        let result = self.emit_stmts(stmts);
+        self.indent -= 1;
+        self.scopes.pop();
+        result
+    }
+    
+    fn emit_stmts(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        for stmt in stmts {
+            self.emit_stmt(stmt)?;
+        }
+        Ok(())
+    }
+    
+    fn emit_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expression(expr, _) => {
+                let e = self.expr(expr)?;
+                self.line(&format!("{};", e.code));
+            }
+            Stmt::Let { name, value, .. } | Stmt::Const { name, value, .. } => self.emit_declare(name, value)?,
+            Stmt::Assign { target, value, .. } => {
+                // Like the interpreter, assigning to an unknown name declares it
+                let Some(ty) = self.lookup(target) else {
+                    return self.emit_declare(target, value);
+                };
+                let e = self.expr(value)?;
+                if !assignable(ty, e.ty) {
+                    return Err(format!("Cannot assign a {} to '{}', which holds a {}", e.ty.hio_name(), target, ty.hio_name()));
+                }
+                self.line(&format!("{} = {};", target, e.code));
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                let cond = self.condition(condition)?;
+                self.line(&format!("if ({}) {{", cond));
+                self.emit_body(then_branch)?;
+                if let Some(else_stmts) = else_branch {
+                    self.line("} else {");
+                    self.emit_body(else_stmts)?;
+                }
+                self.line("}");
+            }
+            Stmt::While { condition, body, else_branch, .. } => {
+                if else_branch.is_some() {
+                    return Err(unsupported("Loop else branches"));
+                }
+                let cond = self.condition(condition)?;
+                self.line(&format!("while ({}) {{", cond));
+                self.emit_body(body)?;
+                self.line("}");
+            }
+            Stmt::DoWhile { body, condition, .. } => {
+                self.line("do {");
+                self.emit_body(body)?;
+                let cond = self.condition(condition)?;
+                self.line(&format!("}} while ({});", cond));
+            }
+            Stmt::For { init, condition, increment, body, else_branch, .. } => {
+                if else_branch.is_some() {
+                    return Err(unsupported("Loop else branches"));
+                }
+                // The initializer goes in an enclosing block so it can be any statement
+                self.line("{");
+                self.scopes.push(HashMap::new());
+                self.indent += 1;
+                if let Some(init_stmt) = init {
+                    self.emit_stmt(init_stmt)?;
+                }
+                let cond = match condition {
+                    Some(c) => self.condition(c)?,
+                    None => String::new(),
+                };
+                let inc = match increment {
+                    Some(i) => self.expr(i)?.code,
+                    None => String::new(),
+                };
+                self.line(&format!("for (; {}; {}) {{", cond, inc));
+                self.emit_body(body)?;
+                self.line("}");
+                self.indent -= 1;
+                self.scopes.pop();
+                self.line("}");
+            }
+            Stmt::ForIn { .. } => return Err(unsupported("For-in loops")),
+            Stmt::LetArray { .. } => return Err(unsupported("Array destructuring")),
+            Stmt::Throw(..) | Stmt::Try { .. } => return Err(unsupported("try/catch and throw")),
+            Stmt::FunctionDef { name, .. } => {
+                // Top-level definitions were already hoisted above main
+                if self.in_function {
+                    return Err(unsupported("Nested functions"));
+                }
+                if !self.functions.contains_key(name) {
+                    return Err(format!("Function '{}' must be defined at the top level to be transpiled to C", name));
+                }
+            }
+            Stmt::Return(expr, _) => {
+                if !self.in_function {
+                    return Err("Return outside of a function cannot be transpiled to C".to_string());
+                }
+                match expr {
+                    Some(e) => {
+                        let e = self.expr(e)?;
+                        if !assignable(CType::Int, e.ty) {
+                            return Err(format!("Functions can only return numbers in C output, got {}", e.ty.hio_name()));
+                        }
+                        self.line(&format!("return {};", e.code));
+                    }
+                    None => self.line("return;"),
+                }
+            }
+            Stmt::Break(None) => self.line("break;"),
+            Stmt::Continue(None) => self.line("continue;"),
+            Stmt::Break(Some(_)) | Stmt::Continue(Some(_)) | Stmt::Labeled { .. } => {
+                return Err(unsupported("Labeled loops"));
+            }
+            // C has no visibility between blocks, so `inspace` is just a nested block
+            Stmt::Space { body, .. } | Stmt::Inspace { body, .. } | Stmt::Block(body) => {
+                self.line("{");
+                self.emit_body(body)?;
+                self.line("}");
+            }
+            Stmt::Pub { body, .. } | Stmt::Subpub { body, .. } => {
+                self.emit_stmts(body)?;
+            }
+            Stmt::Import(_) => return Err(unsupported("Imports")),
+            Stmt::Defer(_) => return Err(unsupported("Deferred statements")),
+            Stmt::With { .. } => return Err(unsupported("'with' statements")),
+        }
+        Ok(())
+    }
+    
+    fn emit_declare(&mut self, name: &str, value: &Expr) -> Result<(), String> {
+        let e = self.expr(value)?;
+        if e.ty == CType::Void {
+            return Err(format!("Cannot store the result of a void call in '{}'", name));
+        }
+        
+        let scope = self.scopes.last_mut().expect("transpiler always has a scope");
+        match scope.get(name) {
+            // C forbids redeclaring in the same block, so a repeated `let` becomes an assignment
+            Some(&ty) if assignable(ty, e.ty) => {
+                self.line(&format!("{} = {};", name, e.code));
+            }
+            Some(&ty) => {
+                return Err(format!("Cannot redeclare '{}' as a {} after it held a {}", name, e.ty.hio_name(), ty.hio_name()));
+            }
+            None => {
+                scope.insert(name.to_string(), e.ty);
+                self.line(&format!("{} {} = {};", e.ty.c_name(), name, e.code));
+            }
+        }
+        Ok(())
+    }
+    
+    fn condition(&mut self, expr: &Expr) -> Result<String, String> {
+        let e = self.expr(expr)?;
+        match e.ty {
+            CType::Int | CType::Bool => Ok(e.code),
+            CType::Str => Ok(format!("({}[0] != '\\0')", e.code)),
+            _ => Err(format!("A {} condition cannot be transpiled to C", e.ty.hio_name())),
+        }
+    }
+    
+    fn expr(&mut self, expr: &Expr) -> Result<CExpr, String> {
+        match expr {
+            // C has no literal for the smallest long long, only a negated one past the largest
+            Expr::Number(i64::MIN) => Ok(CExpr { code: "(-9223372036854775807LL - 1)".to_string(), ty: CType::Int }),
+            Expr::Number(n) => Ok(CExpr { code: format!("{}LL", n), ty: CType::Int }),
+            Expr::Float(f) => Ok(CExpr { code: format!("{:?}", f), ty: CType::Float }),
+            Expr::String(s) => Ok(CExpr { code: c_string(s), ty: CType::Str }),
+            Expr::Boolean(b) => Ok(CExpr { code: (*b as i32).to_string(), ty: CType::Bool }),
+            Expr::Identifier(name) => match self.lookup(name) {
+                Some(ty) => Ok(CExpr { code: name.clone(), ty }),
+                None if self.functions.contains_key(name) => Err(unsupported("Functions used as values")),
+                None => Err(format!("Undefined variable '{}' (functions cannot read globals in C output)", name)),
+            },
+            Expr::Binary { left, op, right } => self.binary(left, op, right),
+            Expr::Unary { op, expr } => {
+                match op {
+                    UnaryOp::Negate => {
+                        let e = self.expr(expr)?;
+                        if !e.ty.is_numeric() {
+                            return Err(format!("Cannot negate a {}", e.ty.hio_name()));
+                        }
+                        Ok(CExpr { code: format!("(-{})", e.code), ty: e.ty })
+                    }
+                    UnaryOp::Not => {
+                        let cond = self.condition(expr)?;
+                        Ok(CExpr { code: format!("(!{})", cond), ty: CType::Bool })
+                    }
+                }
+            }
+            Expr::Ternary { cond, then, otherwise } => {
+                let cond = self.condition(cond)?;
+                let then = self.expr(then)?;
+                let otherwise = self.expr(otherwise)?;
+                let ty = if then.ty == otherwise.ty {
+                    then.ty
+                } else if then.ty.is_numeric() && otherwise.ty.is_numeric() {
+                    CType::Float
+                } else {
+                    return Err(format!("Ternary branches must have the same type in C output, got {} and {}", then.ty.hio_name(), otherwise.ty.hio_name()));
+                };
+                Ok(CExpr { code: format!("({} ? {} : {})", cond, then.code, otherwise.code), ty })
+            }
+            Expr::Call { func, args, .. } => {
+                let name = match func.as_ref() {
+                    Expr::Identifier(name) => name,
+                    _ => return Err(unsupported("Calls through expressions")),
+                };
+                self.call(name, args)
+            }
+            Expr::Update { target, increment, prefix } => {
+                let ty = self.lookup(target)
+                    .ok_or_else(|| format!("Undefined variable '{}' (functions cannot read globals in C output)", target))?;
+                let symbol = if *increment { "++" } else { "--" };
+                if !ty.is_numeric() {
+                    return Err(format!("Operator {} requires a number or float, got {}", symbol, ty.hio_name()));
+                }
+                let code = if *prefix { format!("({}{})", symbol, target) } else { format!("({}{})", target, symbol) };
+                Ok(CExpr { code, ty })
+            }
+            Expr::Null => Err(unsupported("nil")),
+            Expr::Array(_) => Err(unsupported("Arrays")),
+            Expr::Interpolated(_) => Err(unsupported("String interpolation")),
+            Expr::Object(_) => Err(unsupported("Objects")),
+            Expr::Set(_) => Err(unsupported("Sets")),
+            Expr::Index { .. } => Err(unsupported("Indexing")),
+            Expr::Member { .. } => Err(unsupported("Member access")),
+            Expr::Slice { .. } => Err(unsupported("Slices")),
+            Expr::Compare { .. } => Err(unsupported("Chained comparisons")),
+        }
+    }
+    
+    fn binary(&mut self, left: &Expr, op: &BinaryOp, right: &Expr) -> Result<CExpr, String> {
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            let l = self.condition(left)?;
+            let r = self.condition(right)?;
+            return Ok(CExpr { code: format!("({} {} {})", l, op.symbol(), r), ty: CType::Bool });
+        }
+        
+        let l = self.expr(left)?;
+        let r = self.expr(right)?;
+        let mismatch = || format!("Cannot apply {} to {} and {} in C output", op.symbol(), l.ty.hio_name(), r.ty.hio_name());
+        let numeric = l.ty.is_numeric() && r.ty.is_numeric();
+        let both_int = l.ty == CType::Int && r.ty == CType::Int;
+        
+        match op {
+            BinaryOp::Equal | BinaryOp::NotEqual if l.ty == CType::Str && r.ty == CType::Str => {
+                Ok(CExpr { code: format!("(strcmp({}, {}) {} 0)", l.code, r.code, op.symbol()), ty: CType::Bool })
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual
+            | BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                let comparable = numeric || (l.ty == CType::Bool && r.ty == CType::Bool);
+                if !comparable {
+                    return Err(mismatch());
+                }
+                Ok(CExpr { code: format!("({} {} {})", l.code, op.symbol(), r.code), ty: CType::Bool })
+            }
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply => {
+                if !numeric {
+                    return Err(mismatch());
+                }
+                let ty = if both_int { CType::Int } else { CType::Float };
+                Ok(CExpr { code: format!("({} {} {})", l.code, op.symbol(), r.code), ty })
+            }
+            BinaryOp::Divide => {
+                // `/` can produce a float in Hiolang, so always divide as doubles
+                if !numeric {
+                    return Err(mismatch());
+                }
+                Ok(CExpr { code: format!("((double){} / {})", l.code, r.code), ty: CType::Float })
+            }
+            BinaryOp::IntDivide | BinaryOp::Modulo => {
+                if !both_int {
+                    return Err(mismatch());
+                }
+                let symbol = if *op == BinaryOp::IntDivide { "/" } else { "%" };
+                Ok(CExpr { code: format!("({} {} {})", l.code, symbol, r.code), ty: CType::Int })
+            }
+            BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+        }
+    }
+    
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<CExpr, String> {
+        let mut vals = Vec::new();
+        for arg in args {
+            vals.push(self.expr(arg)?);
+        }
+        
+        match name {
+            "print" | "println" | "eprint" => {
+                let mut specs = Vec::new();
+                let mut codes = Vec::new();
+                for v in vals {
+                    match v.ty {
+                        CType::Int => specs.push("%lld"),
+                        CType::Float => specs.push("%g"),
+                        CType::Str => specs.push("%s"),
+                        CType::Bool => {
+                            specs.push("%s");
+                            codes.push(format!("({} ? \"true\" : \"false\")", v.code));
+                            continue;
+                        }
+                        CType::Void => return Err("Cannot print the result of a void call".to_string()),
+                    }
+                    codes.push(v.code);
+                }
+                let target = if name == "eprint" { "fprintf(stderr, " } else { "printf(" };
+                let newline = if name == "print" { "" } else { "\\n" };
+                let mut call = format!("{}\"{}{}\"", target, specs.join(" "), newline);
+                for code in codes {
+                    call.push_str(", ");
+                    call.push_str(&code);
+                }
+                call.push(')');
+                Ok(CExpr { code: call, ty: CType::Void })
+            }
+            "len" => match vals.as_slice() {
+                [v] if v.ty == CType::Str => {
+                    Ok(CExpr { code: format!("((long long)strlen({}))", v.code), ty: CType::Int })
+                }
+                _ => Err("len() only supports a single string in C output".to_string()),
+            },
+            _ => {
+                let sig = self.functions.get(name)
+                    .ok_or_else(|| format!("Function '{}' cannot be transpiled to C", name))?;
+                if sig.arity != vals.len() {
+                    return Err(format!("Function '{}' expects {} arguments, got {}", name, sig.arity, vals.len()));
+                }
+                if let Some(bad) = vals.iter().find(|v| !assignable(CType::Int, v.ty)) {
+                    return Err(format!("Functions only take numbers in C output, got {} for '{}'", bad.ty.hio_name(), name));
+                }
+                let ty = if sig.returns_value { CType::Int } else { CType::Void };
+                let args: Vec<String> = vals.into_iter().map(|v| v.code).collect();
+                Ok(CExpr { code: format!("{}({})", name, args.join(", ")), ty })
+            }
+        }
+    }
+}
+
+// Function definitions at the top level, including those nested in spaces and pub blocks
+fn collect_functions<'a>(stmts: &'a [Stmt], defs: &mut Vec<(&'a str, &'a [String], &'a [Stmt])>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::FunctionDef { name, params, body, .. } => defs.push((name, params, body)),
+            Stmt::Space { body, .. } | Stmt::Pub { body, .. } | Stmt::Subpub { body, .. }
+            | Stmt::Inspace { body, .. } | Stmt::Block(body) => {
+                collect_functions(body, defs);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn returns_value(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Return(expr, _) => expr.is_some(),
+        Stmt::If { then_branch, else_branch, .. } => {
+            returns_value(then_branch) || else_branch.as_deref().is_some_and(returns_value)
+        }
+        Stmt::While { body, else_branch, .. } | Stmt::For { body, else_branch, .. } => {
+            returns_value(body) || else_branch.as_deref().is_some_and(returns_value)
+        }
+        Stmt::Labeled { body, .. } => returns_value(std::slice::from_ref(body)),
+        Stmt::DoWhile { body, .. } | Stmt::ForIn { body, .. } | Stmt::Block(body)
+        | Stmt::Space { body, .. } | Stmt::Pub { body, .. } | Stmt::Subpub { body, .. }
+        | Stmt::Inspace { body, .. } => returns_value(body),
+        _ => false,
+    })
+}
+
+fn assignable(to: CType, from: CType) -> bool {
+    to == from || matches!((to, from), (CType::Float, CType::Int) | (CType::Int, CType::Bool))
+}
+
+fn unsupported(what: &str) -> String {
+    format!("{} cannot be transpiled to C", what)
+}
+
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_ascii() && !c.is_ascii_control() => out.push(c),
+            c => {
+                // Everything else is written byte by byte as octal escapes of its UTF-8 encoding
+                let mut buf = [0u8; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("\\{:03o}", byte));
+                }
+            }
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,356 @@
+// Builtin functions, called the way programs call them
+
+mod common;
+
+use common::{eval_err, eval_str, output};
+
+#[test]
+fn deep_merge_combines_nested_objects() {
+    let source = "let base = {db: {host: \"localhost\", port: 5432}, debug: 0};
+        let over = {db: {port: 6543, user: \"app\"}, name: \"svc\"};
+        deep_merge(base, over);";
+    assert_eq!(eval_str(source), "{db: {host: localhost, port: 6543, user: app}, debug: 0, name: svc}");
+}
+
+#[test]
+fn deep_merge_lets_the_override_win_and_leaves_inputs_alone() {
+    let source = "let base = {a: {b: 1}, c: 2}; let merged = deep_merge(base, {a: 5, c: {d: 3}}); [merged, base];";
+    assert_eq!(eval_str(source), "[{a: 5, c: {d: 3}}, {a: {b: 1}, c: 2}]");
+}
+
+#[test]
+fn deep_merge_replaces_arrays_unless_asked_to_concatenate() {
+    assert_eq!(eval_str("deep_merge({xs: [1, 2]}, {xs: [3]});"), "{xs: [3]}");
+    assert_eq!(eval_str("deep_merge({xs: [1, 2]}, {xs: [3]}, 1);"), "{xs: [1, 2, 3]}");
+}
+
+#[test]
+fn deep_merge_requires_two_objects() {
+    assert_eq!(eval_err("deep_merge({a: 1}, [1]);"), "deep_merge() requires two objects");
+    assert_eq!(eval_err("deep_merge({a: 1});"), "deep_merge() requires 2 arguments");
+}
+
+#[test]
+fn map_doubles_each_element() {
+    assert_eq!(eval_str("function double(n) { return n * 2; } map([1, 2, 3], double);"), "[2, 4, 6]");
+    assert_eq!(eval_str("map([], len);"), "[]");
+}
+
+#[test]
+fn filter_keeps_matching_elements() {
+    assert_eq!(eval_str("filter([1, 2, 3, 4, 5], is_odd);"), "[1, 3, 5]");
+}
+
+#[test]
+fn reduce_sums_an_array() {
+    assert_eq!(eval_str("function add(a, b) { return a + b; } reduce([1, 2, 3, 4], add, 0);"), "10");
+    assert_eq!(eval_str("function add(a, b) { return a + b; } reduce([], add, 7);"), "7");
+}
+
+#[test]
+fn builtins_can_be_passed_and_called_through_parameters() {
+    assert_eq!(eval_str("function apply(fn, x) { return fn(x); } apply(len, \"abc\");"), "3");
+    assert_eq!(eval_str("map([\"a\", \"bc\"], len);"), "[1, 2]");
+    assert_eq!(eval_str("let f = len; f([1, 2]);"), "2");
+}
+
+#[test]
+fn a_variable_named_like_a_builtin_does_not_hide_it() {
+    assert_eq!(eval_str("let len = 5; len(\"ab\") + len;"), "7");
+}
+
+#[test]
+fn type_tells_builtins_functions_and_strings_apart() {
+    assert_eq!(eval_str("function f() { } [type(len), type(f), type(\"builtin:exit\")];"), "[builtin, function, string]");
+    assert_eq!(eval_str("[len, len == len, len == type];"), "[<builtin len>, true, false]");
+}
+
+#[test]
+fn a_string_that_looks_like_a_builtin_cannot_be_called() {
+    assert_eq!(eval_err("let s = \"builtin:exit\"; s(3);"), "Unknown function: s");
+    assert_eq!(eval_err("map([1], \"builtin:len\");"), "map() requires a function as second argument");
+}
+
+#[test]
+fn higher_order_builtins_check_their_arguments() {
+    assert_eq!(eval_err("map([1], 5);"), "map() requires a function as second argument");
+    assert_eq!(eval_err("filter(5, len);"), "filter() requires an array as first argument");
+}
+
+#[test]
+fn math_builtins_mix_numbers_and_floats() {
+    assert_eq!(eval_str("[abs(-3), abs(-2.5), min(2, 1.5), max([3, 7.5, -1]), sqrt(16), pow(2, 10), pow(2, -1), pow(4, 0.5)];"), "[3, 2.5, 1.5, 7.5, 4, 1024, 0.5, 2]");
+    assert_eq!(eval_str("[type(sqrt(16)), type(pow(2, 3)), type(pow(2.0, 3)), type(min(1, 2.0))];"), "[float, number, float, number]");
+}
+
+#[test]
+fn rounding_returns_numbers() {
+    assert_eq!(eval_str("[floor(2.7), ceil(2.1), round(2.5), round(-2.5), floor(-0.5), floor(7)];"), "[2, 3, 3, -3, -1, 7]");
+    assert_eq!(eval_str("type(round(2.4));"), "number");
+}
+
+#[test]
+fn rounding_refuses_values_a_number_cannot_hold() {
+    assert_eq!(eval_err("floor(pow(-1, 0.5));"), "floor() result NaN does not fit in a number");
+    assert_eq!(eval_err("ceil(1e300 * 1e300);"), "ceil() result inf does not fit in a number");
+    assert_eq!(eval_err("round(-1e300 * 1e300);"), "round() result -inf does not fit in a number");
+    assert!(eval_err("floor(1e30);").starts_with("floor() result"));
+    assert!(eval_err("round(9223372036854775807.0);").starts_with("round() result"));
+    assert_eq!(eval_str("floor(-9223372036854775808.0);"), "-9223372036854775808");
+}
+
+#[test]
+fn format_float_prints_a_fixed_number_of_digits() {
+    assert_eq!(eval_str("[format_float(3.14159, 2), format_float(2, 3), format_float(1e3, 0), format_float(0.5, 0)];"), "[3.14, 2.000, 1000, 0]");
+    assert_eq!(eval_str("format_float(1.0 / 3, 20);"), format!("{:.20}", 1.0f64 / 3.0));
+    assert_eq!(eval_err("format_float(1.0, 21);"), "format_float() digit count must be between 0 and 20, got 21");
+    assert_eq!(eval_err("format_float(1.0, -1);"), "format_float() digit count must be between 0 and 20, got -1");
+}
+
+#[test]
+fn print_leaves_the_line_open() {
+    assert_eq!(output("print(\"a\", 1); print(\"b\");"), "a 1b");
+    assert_eq!(output("print(\"a\", \"b\", {sep: \",\", end: \"\\n\"});"), "a,b\n");
+}
+
+#[test]
+fn println_ends_the_line() {
+    assert_eq!(output("println(\"a\", 1); println();"), "a 1\n\n");
+    assert_eq!(output("println(1, 2, {sep: \";\", end: \"!\"});"), "1;2!");
+}
+
+#[test]
+fn a_custom_sep_goes_between_arguments_only() {
+    assert_eq!(output("println(1, [2, 3], \"x\", {sep: \" | \"});"), "1 | [2, 3] | x\n");
+    assert_eq!(output("print(\"a\", \"b\", {sep: \"\"}); print(\"only\", {sep: \"-\"});"), "abonly");
+    assert_eq!(eval_err("print(1, 2, {sep: 5});"), "print() option 'sep' must be a string, got number");
+}
+
+#[test]
+fn printf_fills_placeholders_without_a_newline() {
+    assert_eq!(output("printf(\"{} of {}\", 3, 10); printf(\"!\");"), "3 of 10!");
+}
+
+#[test]
+fn eprint_writes_to_stderr_only() {
+    assert_eq!(output("eprint(\"warning\"); print(\"ok\");"), "ok");
+}
+
+#[test]
+fn sort_orders_numbers_and_strings_and_returns_a_new_array() {
+    assert_eq!(eval_str("let xs = [3, 1.5, -2, 10]; let s = sort(xs); [s, xs];"), "[[-2, 1.5, 3, 10], [3, 1.5, -2, 10]]");
+    assert_eq!(eval_str("sort([\"pear\", \"Apple\", \"apple\"]);"), "[Apple, apple, pear]");
+    assert_eq!(eval_str("sort([]);"), "[]");
+}
+
+#[test]
+fn sort_with_a_comparator_is_stable() {
+    assert_eq!(eval_str("function desc(a, b) { return b - a; } sort([1, 5, 2, 4], desc);"), "[5, 4, 2, 1]");
+    let source = "let people = [[\"b\", 2], [\"a\", 1], [\"c\", 2], [\"d\", 1]];
+        function by_age(x, y) { return x[1] - y[1]; }
+        sort(people, by_age);";
+    assert_eq!(eval_str(source), "[[a, 1], [d, 1], [b, 2], [c, 2]]");
+}
+
+#[test]
+fn sort_rejects_values_it_cannot_order() {
+    assert_eq!(eval_err("sort([1, \"a\"]);"), "sort() cannot compare string with number");
+    assert_eq!(eval_err("sort([[1], [2]]);"), "sort() can only order numbers, strings or chars, got array");
+    assert_eq!(eval_err("sort(5);"), "sort() requires an array as first argument");
+    let source = "function bad(a, b) { return \"x\"; } sort([2, 1], bad);";
+    assert_eq!(eval_err(source), "sort() comparator must return a number, got string");
+}
+
+#[test]
+fn reverse_handles_arrays_and_strings() {
+    assert_eq!(eval_str("let xs = [1, 2, 3]; [reverse(xs), xs];"), "[[3, 2, 1], [1, 2, 3]]");
+    assert_eq!(eval_str("reverse(\"héllo\");"), "olléh");
+    assert_eq!(eval_err("reverse(5);"), "reverse() requires an array or a string");
+}
+
+#[test]
+fn contains_searches_strings_and_arrays() {
+    assert_eq!(eval_str("[contains(\"hello\", \"ell\"), contains(\"hello\", \"xyz\"), contains(\"hello\", \"\")];"), "[true, false, true]");
+    assert_eq!(eval_str("[contains([1, [2, 3]], [2, 3]), contains([1, 2], 2.0), contains([1, 2], \"1\")];"), "[true, true, false]");
+    assert_eq!(eval_str("contains(\"abc\", \"abc\"[1]);"), "true");
+}
+
+#[test]
+fn index_of_counts_characters_and_returns_minus_one_when_missing() {
+    assert_eq!(eval_str("[index_of(\"héllo\", \"llo\"), index_of(\"abcabc\", \"c\"), index_of(\"abc\", \"z\")];"), "[2, 2, -1]");
+    assert_eq!(eval_str("[index_of([5, 6, 6], 6), index_of([], 1), index_of([[1]], [1])];"), "[1, -1, 0]");
+}
+
+#[test]
+fn searching_needs_a_string_or_an_array() {
+    assert_eq!(eval_err("contains(\"abc\", 1);"), "contains() on a string needs a string to search for, got number");
+    assert_eq!(eval_err("index_of(5, 5);"), "index_of() requires a string or an array to search in");
+    assert_eq!(eval_err("contains([1]);"), "contains() requires 2 arguments");
+}
+
+#[test]
+fn to_number_parses_strings_and_truncates_floats() {
+    assert_eq!(eval_str("[to_number(\" 42 \"), to_number(\"-2.5\"), to_number(7)];"), "[42, -2.5, 7]");
+    assert_eq!(eval_str("[to_number(3.9), to_number(-3.9), to_number(1 < 2), to_number(1 > 2)];"), "[3, -3, 1, 0]");
+    assert_eq!(eval_str("type(to_number(\"2.0\"));"), "float");
+}
+
+#[test]
+fn to_number_rejects_what_it_cannot_convert() {
+    assert_eq!(eval_err("to_number(\"12abc\");"), "to_number() could not parse '12abc'");
+    for text in ["inf", "-inf", "infinity", "nan", "NaN", "1e400"] {
+        assert_eq!(eval_err(&format!("to_number(\"{}\");", text)), format!("to_number() could not parse '{}'", text));
+    }
+    assert_eq!(eval_err("to_number([1]);"), "to_number() cannot convert array");
+    assert_eq!(eval_err("to_number(1e19);"), "to_number() cannot convert 10000000000000000000 to an integer");
+    assert_eq!(eval_err("to_number();"), "to_number() requires 1 argument");
+}
+
+#[test]
+fn to_bool_and_to_string_follow_truthiness_and_printing() {
+    assert_eq!(eval_str("[to_bool(0), to_bool(\"\"), to_bool([]), to_bool(nil), to_bool(2), to_bool(\"0\")];"), "[false, false, false, false, true, true]");
+    assert_eq!(eval_str("[to_string(1.5), to_string([1, \"a\"]), to_string(nil)];"), "[1.5, [1, a], null]");
+    assert_eq!(eval_str("type(to_string(12));"), "string");
+    assert_eq!(eval_err("to_bool(1, 2);"), "to_bool() requires 1 argument");
+}
+
+#[test]
+fn to_hex_bin_and_oct_keep_the_sign() {
+    assert_eq!(eval_str("[to_hex(255), to_bin(5), to_oct(8), to_hex(0)];"), "[ff, 101, 10, 0]");
+    assert_eq!(eval_str("[to_hex(-255), to_bin(-9223372036854775807 - 1)];"), "[-ff, -1000000000000000000000000000000000000000000000000000000000000000]");
+    assert_eq!(eval_err("to_hex(1.5);"), "to_hex() requires an integer, got float");
+}
+
+#[test]
+fn parse_radix_reads_bases_from_2_to_36() {
+    assert_eq!(eval_str("[parse_radix(\"ff\", 16), parse_radix(\"-101\", 2), parse_radix(\"zz\", 36), parse_radix(\" 17 \", 8)];"), "[255, -5, 1295, 15]");
+    assert_eq!(eval_str("parse_radix(to_hex(123456), 16);"), "123456");
+}
+
+#[test]
+fn parse_radix_rejects_bad_digits_and_bases() {
+    assert_eq!(eval_err("parse_radix(\"12\", 1);"), "parse_radix() base must be between 2 and 36, got 1");
+    assert_eq!(eval_err("parse_radix(\"12\", 37);"), "parse_radix() base must be between 2 and 36, got 37");
+    assert_eq!(eval_err("parse_radix(\"19\", 8);"), "parse_radix() could not parse '19' in base 8");
+    assert_eq!(eval_err("parse_radix(12, 10);"), "parse_radix() requires a string and an integer base");
+}
+
+#[test]
+fn set_literals_drop_duplicates_and_keep_first_insertion_order() {
+    assert_eq!(eval_str("let s = {3, 1, 3, 2.0, 2}; [s, type(s), len(s), to_array(s)];"), "[{3, 1, 2}, set, 3, [3, 1, 2]]");
+    assert_eq!(eval_str("let s = {[1], [1], [2]}; s;"), "{[1], [2]}");
+    assert_eq!(eval_str("type({});"), "object");
+    assert_eq!(eval_str("let out = \"\"; for (x in {5, 6, 5}) { out = out + x; } out;"), "56");
+}
+
+#[test]
+fn set_operations_and_membership() {
+    assert_eq!(eval_str("[union({1, 2}, {2, 3}), intersect({1, 2, 3}, {3, 1}), difference({1, 2, 3}, {2})];"), "[{1, 2, 3}, {1, 3}, {1, 3}]");
+    assert_eq!(eval_str("let s = {1, 2}; [contains(s, 2), contains(s, 2.0), contains(s, 5)];"), "[true, true, false]");
+    assert_eq!(eval_str("contains({1, [2]}, [2]);"), "true");
+    assert_eq!(eval_str("[{1, 2} == {2, 1}, {1} == {1, 2}];"), "[true, false]");
+    assert_eq!(eval_err("union({1}, [1]);"), "union() requires two sets, got array");
+    assert_eq!(eval_err("index_of({1}, 1);"), "index_of() requires a string or an array to search in");
+    assert_eq!(eval_err("let s = {1}; s[0];"), "Invalid index operation");
+}
+
+#[test]
+fn zip_pairs_elements_up_to_the_shorter_array() {
+    assert_eq!(eval_str("[zip([1, 2, 3], [\"a\", \"b\"]), zip([], [1]), zip([[1]], [2])];"), "[[[1, a], [2, b]], [], [[[1], 2]]]");
+    assert_eq!(eval_err("zip([1], 2);"), "zip() requires two arrays, got number");
+    assert_eq!(eval_err("zip([1]);"), "zip() requires 2 arguments");
+}
+
+#[test]
+fn enumerate_pairs_each_element_with_its_index() {
+    assert_eq!(eval_str("[enumerate([\"x\", \"y\"]), enumerate([])];"), "[[[0, x], [1, y]], []]");
+    assert_eq!(eval_str("let total = 0; for (pair in enumerate([5, 6])) { let [i, v] = pair; total = total + i * v; } total;"), "6");
+    assert_eq!(eval_err("enumerate(\"ab\");"), "enumerate() requires an array, got string");
+}
+
+#[test]
+fn format_fills_placeholders_left_to_right() {
+    assert_eq!(eval_str("format(\"{} + {} = {}\", 1, 2.5, \"3.5\");"), "1 + 2.5 = 3.5");
+    assert_eq!(eval_str("format(\"{}\", [1, \"a\"]);"), "[1, a]");
+    assert_eq!(eval_str("format(\"plain\");"), "plain");
+}
+
+#[test]
+fn format_turns_doubled_braces_into_literal_ones() {
+    assert_eq!(eval_str("format(\"{{}} {{{}}} }}{{\", 7);"), "{} {7} }{");
+}
+
+#[test]
+fn format_rejects_mismatched_arguments_and_stray_braces() {
+    assert_eq!(eval_err("format(\"{} and {}\", 1);"), "format() has more placeholders than arguments (1 given)");
+    assert_eq!(eval_err("format(\"{}\", 1, 2);"), "format() got 2 arguments but only 1 placeholders");
+    assert_eq!(eval_err("format(\"{x}\");"), "format() found '{' without a matching '}'");
+    assert_eq!(eval_err("format(\"a}\");"), "format() found '}' without a matching '{'");
+    assert_eq!(eval_err("format(5);"), "format() requires a template string");
+}
+
+#[test]
+fn objects_keep_keys_in_insertion_order() {
+    let mut map = hiolang::ast::ObjectMap::new();
+    for key in ["zeta", "alpha", "mid"] {
+        map.insert(key.to_string(), hiolang::Value::Number(key.len() as i64));
+    }
+    map.insert("alpha".to_string(), hiolang::Value::Number(0));
+    assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["zeta", "alpha", "mid"]);
+    assert_eq!(hiolang::Value::Object(map).to_string(), "{zeta: 4, alpha: 0, mid: 3}");
+}
+
+#[test]
+fn object_key_order_survives_a_json_round_trip() {
+    let source = "let o = {zeta: 1, alpha: {y: 2, b: 3}, mid: 4}; o = deep_merge(o, {beta: 5, zeta: 6});
+        let back = from_json(to_json(o)); [keys(back), keys(back.alpha), back];";
+    assert_eq!(eval_str(source), "[[zeta, alpha, mid, beta], [y, b], {zeta: 6, alpha: {y: 2, b: 3}, mid: 4, beta: 5}]");
+}
+
+#[test]
+fn numeric_and_string_object_keys_name_the_same_entry() {
+    let source = "let o = {1: \"one\", \"2\": \"two\"}; [keys(o), o[1], o[\"1\"], o[2], o[\"2\"], o[2.0]];";
+    assert_eq!(eval_str(source), "[[1, 2], one, one, two, two, two]");
+    assert_eq!(eval_str("deep_merge({1: \"one\", \"2\": \"two\"}, {\"1\": \"uno\", 2: \"dos\"});"), "{1: uno, 2: dos}");
+}
+
+// Runs the program as a script started with `script_args` and returns its last value
+fn eval_with_args(source: &str, script_args: &[&str]) -> String {
+    let mut interpreter = hiolang::Interpreter::new();
+    interpreter.set_script_args(script_args.iter().map(|a| a.to_string()).collect());
+    interpreter.interpret(&hiolang::parse(source).unwrap()).unwrap().to_string()
+}
+
+#[test]
+fn args_lists_the_script_arguments() {
+    assert_eq!(eval_with_args("args();", &["in.txt", "--fast"]), "[in.txt, --fast]");
+    assert_eq!(eval_with_args("len(args());", &[]), "0");
+}
+
+#[test]
+fn env_reads_a_variable_or_gives_void() {
+    // Cargo sets the package name in the environment of every test run
+    assert_eq!(eval_str("env(\"CARGO_PKG_NAME\");"), env!("CARGO_PKG_NAME"));
+    assert_eq!(eval_str("type(env(\"HIOLANG_TEST_SURELY_UNSET\"));"), "void");
+    assert_eq!(eval_err("env();"), "env() requires a variable name");
+}
+
+#[test]
+fn nested_values_with_escaped_characters_survive_a_json_round_trip() {
+    use hiolang::json::{from_json, to_json};
+    let source = "let o = {name: \"say \\\"hi\\\"\\n\", path: \"C:\\\\tmp\\t1\", items: [from_char_code(1), {deep: [\"/é€😀\", 1.5, 2]}], \"key \\\"q\\\"\": 0}; o;";
+    let value = common::eval(source);
+    let json = to_json(&value).unwrap();
+    assert_eq!(json, r#"{"name":"say \"hi\"\n","path":"C:\\tmp\t1","items":["\u0001",{"deep":["/é€😀",1.5,2]}],"key \"q\"":0}"#);
+    assert_eq!(from_json(&json), Ok(value));
+    // Escapes JSON allows but to_json never writes read back the same way
+    assert_eq!(from_json(r#"["\/", "\u00e9\ud83d\ude00", "\b\f\r"]"#).unwrap().to_string(), "[/, é😀, \u{8}\u{c}\r]");
+}
+
+#[test]
+fn print_writes_nested_strings_bare_and_debug_string_quotes_them() {
+    let source = "let v = [\"a\", 1, \"1\", {k: \"say \\\"hi\\\"\", n: [\"x\"]}, 'c']; v;";
+    let value = common::eval(source);
+    assert_eq!(output(&format!("{} println(v);", source)), "[a, 1, 1, {k: say \"hi\", n: [x]}, c]\n");
+    assert_eq!(value.debug_string(), "[\"a\", 1, \"1\", {k: \"say \\\"hi\\\"\", n: [\"x\"]}, \"c\"]");
+}
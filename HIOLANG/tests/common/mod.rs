@@ -0,0 +1,57 @@
+// Helpers shared by the integration tests. Each test file uses a different subset.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use hiolang::{Interpreter, Value};
+
+// The value of the program's last statement; the test fails if the program does
+pub fn eval(source: &str) -> Value {
+    hiolang::eval(source).unwrap_or_else(|e| panic!("{:?} failed: {}", source, e))
+}
+
+// The printed form of the program's last value, which keeps array and set tests short
+pub fn eval_str(source: &str) -> String {
+    eval(source).to_string()
+}
+
+// The error the program stopped with; the test fails if it succeeded
+pub fn eval_err(source: &str) -> String {
+    match hiolang::eval(source) {
+        Ok(value) => panic!("{:?} should fail, got {}", source, value.debug_string()),
+        Err(e) => e,
+    }
+}
+
+// A writer the test keeps a handle to after the interpreter owns it
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Runs the program and returns its result along with everything it wrote to the output
+pub fn run_capturing(source: &str) -> (Result<Value, String>, String) {
+    let capture = Capture::default();
+    let mut interpreter = Interpreter::with_output(capture.clone());
+    let result = hiolang::parse(source).and_then(|program| interpreter.interpret(&program));
+    let output = String::from_utf8(capture.0.take()).expect("output is UTF-8");
+    (result, output)
+}
+
+// What the program wrote; the test fails if the program does
+pub fn output(source: &str) -> String {
+    let (result, output) = run_capturing(source);
+    if let Err(e) = result {
+        panic!("{:?} failed: {}", source, e);
+    }
+    output
+}
@@ -0,0 +1,228 @@
+// Bytecode the compiler produces, checked through its debug listing
+
+mod common;
+
+use hiolang::compiler::verify;
+use hiolang::{BytecodeOp, Compiler, Value};
+
+fn compile(source: &str, optimize: bool) -> Vec<String> {
+    let mut compiler = Compiler::new();
+    compiler.set_optimize(optimize);
+    let module = compiler.compile_program(&hiolang::parse(source).unwrap())
+        .unwrap_or_else(|e| panic!("{:?} failed to compile: {}", source, e));
+    module.main.iter().map(|op| format!("{:?}", op)).collect()
+}
+
+// The push instruction the compiler would use for a literal of this value
+fn push_for(value: &Value) -> String {
+    let op = match value {
+        Value::Number(n) => BytecodeOp::PushNumber(*n),
+        Value::Float(f) => BytecodeOp::PushFloat(*f),
+        Value::Boolean(b) => BytecodeOp::PushBool(*b),
+        Value::String(s) => BytecodeOp::PushString(s.clone()),
+        other => panic!("no literal for {:?}", other),
+    };
+    format!("{:?}", op)
+}
+
+#[test]
+fn folded_constants_match_the_interpreter() {
+    let expressions = [
+        "2 + 3 * 4", "7 / 2", "7 // 2", "-7 % 3", "1 < 2", "2 <= 1", "\"a\" + \"b\"",
+        "0.1 + 0.2 == 0.3", "0.1 + 0.2 != 0.3", "1 == 0.9999999999999999", "1.5 * 2", "!0",
+    ];
+    for expr in expressions {
+        let folded = compile(&format!("let x = {};", expr), true);
+        let unfolded = compile(&format!("let x = {};", expr), false);
+        let expected = push_for(&common::eval(&format!("{};", expr)));
+        assert_eq!(folded, [expected, "SetGlobal(\"x\")".to_string(), "Halt".to_string()], "{}", expr);
+        assert!(unfolded.len() > folded.len(), "{} was folded without optimization: {:?}", expr, unfolded);
+    }
+}
+
+#[test]
+fn no_opt_keeps_the_arithmetic() {
+    assert_eq!(compile("let x = 2 + 3;", false), ["PushNumber(2)", "PushNumber(3)", "Add", "SetGlobal(\"x\")", "Halt"]);
+    assert_eq!(compile("let x = 2 + 3;", true), ["PushNumber(5)", "SetGlobal(\"x\")", "Halt"]);
+}
+
+#[test]
+fn division_by_a_literal_zero_is_left_to_runtime() {
+    for expr in ["1 / 0", "1 % 0", "1.0 / 0.0", "5 // 0.0"] {
+        let ops = compile(&format!("let x = {};", expr), true);
+        assert_eq!(ops.len(), 5, "{} should not fold: {:?}", expr, ops);
+    }
+}
+
+// The linked body of `name`, from its Enter up to the next function or the end
+fn function_ops(source: &str, name: &str) -> Vec<String> {
+    let module = Compiler::new().compile_program(&hiolang::parse(source).unwrap()).unwrap();
+    module.functions[name].iter().map(|op| format!("{:?}", op)).collect()
+}
+
+fn warnings(source: &str) -> Vec<String> {
+    let mut compiler = Compiler::new();
+    compiler.compile_program(&hiolang::parse(source).unwrap()).unwrap();
+    compiler.get_warnings().to_vec()
+}
+
+#[test]
+fn code_after_return_is_not_compiled() {
+    let source = "function f() { return 1; print(2); let y = 3; }";
+    let ops = function_ops(source, "f");
+    assert_eq!(ops, ["Enter(0, 0)", "PushNumber(1)", "Return", "Return"]);
+    assert!(!ops.iter().any(|op| op.contains("Print") || op.contains("PushNumber(2)")));
+    assert_eq!(warnings(source), ["Unreachable code: 2 statement(s) after return removed"]);
+}
+
+#[test]
+fn code_after_an_if_whose_branches_all_return_is_dead() {
+    let source = "function g(x) { if (x) { return 1; } else { return 2; } print(3); }";
+    let ops = function_ops(source, "g");
+    assert!(!ops.iter().any(|op| op == "PushNumber(3)"), "{:?}", ops);
+    assert_eq!(warnings(source).len(), 1);
+    
+    // One branch falling through keeps the code after the if
+    let ops = function_ops("function h(x) { if (x) { return 1; } print(3); }", "h");
+    assert!(ops.iter().any(|op| op == "PushNumber(3)"), "{:?}", ops);
+}
+
+#[test]
+fn code_after_break_and_continue_is_not_compiled() {
+    let ops = compile("while (1) { break; print(4); } for (let i = 0; i < 3; i++) { continue; print(5); }", true);
+    assert!(!ops.iter().any(|op| op == "PushNumber(4)" || op == "PushNumber(5)"), "{:?}", ops);
+    // --no-opt turns the removal off along with the other optimizations
+    assert!(compile("while (1) { break; print(4); }", false).iter().any(|op| op == "PushNumber(4)"));
+}
+
+#[test]
+fn dead_code_removal_does_not_change_the_interpreter() {
+    assert_eq!(common::output("function f() { println(1); return 0; println(2); } f();"), "1\n");
+}
+
+#[test]
+fn while_loop_break_and_continue_jump_to_the_end_and_the_condition() {
+    let source = "let i = 0; while (i < 10) { i++; if (i == 3) { continue; } if (i == 5) { break; } }";
+    let ops = compile(source, true);
+    assert_eq!(ops[2..6], ["GetGlobal(\"i\")", "PushNumber(10)", "Less", "JumpIfFalse(23)"]);
+    // `continue` re-checks the condition, which starts at 2; `break` leaves for 23, just past the loop
+    assert_eq!(ops[13..17], ["PushNumber(3)", "Equal", "JumpIfFalse(17)", "Jump(2)"]);
+    assert_eq!(ops[18..], ["PushNumber(5)", "Equal", "JumpIfFalse(22)", "Jump(23)", "Jump(2)", "Halt"]);
+}
+
+#[test]
+fn labeled_break_leaves_the_outer_loop() {
+    let ops = compile("outer: while (1) { while (1) { break outer; } }", true);
+    assert_eq!(ops, ["PushNumber(1)", "JumpIfFalse(7)", "PushNumber(1)", "JumpIfFalse(6)", "Jump(7)", "Jump(2)", "Jump(0)", "Halt"]);
+}
+
+#[test]
+fn for_loop_continue_jumps_to_the_increment() {
+    let ops = compile("for (let j = 0; j < 3; j++) { if (j == 1) { continue; } if (j == 2) { break; } }", true);
+    assert_eq!(ops[3..7], ["GetLocal(0)", "PushNumber(3)", "Less", "JumpIfFalse(24)"]);
+    // `continue` skips to the increment at 17 rather than the condition; `break` leaves for 24
+    assert_eq!(ops[8..12], ["PushNumber(1)", "Equal", "JumpIfFalse(12)", "Jump(17)"]);
+    assert_eq!(ops[13..17], ["PushNumber(2)", "Equal", "JumpIfFalse(17)", "Jump(24)"]);
+    assert_eq!(ops[17..], ["GetLocal(0)", "GetLocal(0)", "PushNumber(1)", "Add", "SetLocal(0)", "Pop", "Jump(3)", "Halt"]);
+}
+
+#[test]
+fn compiled_programs_pass_the_verifier() {
+    let source = "function f(n) { if (n < 2) { return n; } return f(n - 1) + 1; } let i = 0; while (i < 3) { i = f(i) + 1; }";
+    let module = Compiler::new().compile_program(&hiolang::parse(source).unwrap()).unwrap();
+    assert_eq!(verify(&module.main), Ok(()));
+}
+
+#[test]
+fn verifier_rejects_corrupted_bytecode() {
+    use BytecodeOp::*;
+    assert_eq!(verify(&[]).unwrap_err(), "Invalid bytecode: no instructions");
+    assert_eq!(
+        verify(&[Jump(5), Halt]).unwrap_err(),
+        "Invalid bytecode at instruction 0 (Jump(5)): target 5 is outside the code (length 2)",
+    );
+    assert_eq!(
+        verify(&[PushNumber(1), Pop, Pop, Halt]).unwrap_err(),
+        "Invalid bytecode at instruction 2 (Pop): needs 1 value(s) but the stack holds 0",
+    );
+    assert_eq!(
+        verify(&[PushNumber(1), Halt]).unwrap_err(),
+        "Invalid bytecode at instruction 1 (Halt): program ends with 1 value(s) left on the stack",
+    );
+    // One branch pushes a value before the paths join again
+    assert_eq!(
+        verify(&[PushBool(true), JumpIfFalse(4), PushNumber(1), Jump(4), Pop, Halt]).unwrap_err(),
+        "Invalid bytecode at instruction 4 (Pop): reached with stack depth 1 and 0",
+    );
+    assert!(verify(&[PushNumber(1), Pop]).unwrap_err().contains("execution runs past the last instruction"));
+}
+
+#[test]
+fn compile_errors_name_the_source_line() {
+    let program = hiolang::parse("let x = 1;\n\nlet y = [x][0](1);").unwrap();
+    let error = Compiler::new().compile_program(&program).unwrap_err();
+    assert_eq!(error, "Cannot compile call of a non-identifier expression at line 3");
+}
+
+#[test]
+fn the_compiler_rejects_assigning_to_a_const() {
+    let program = hiolang::parse("const x = 1; x = 2;").unwrap();
+    assert_eq!(Compiler::new().compile_program(&program).unwrap_err(), "Cannot assign to constant 'x'");
+}
+
+#[test]
+fn the_peephole_pass_shrinks_a_sample_program() {
+    let source = "let a = 2; 1; \"unused\"; a; if (a > 1) { a = 3; }";
+    let plain = compile(source, false);
+    let optimized = compile(source, true);
+    // The three expression statements each compile to a push and a Pop, and the `if`
+    // without an else ends in a jump to the very next instruction
+    assert_eq!(plain.len() - optimized.len(), 7, "{:?}", plain);
+    assert_eq!(optimized, [
+        "PushNumber(2)", "SetGlobal(\"a\")", "GetGlobal(\"a\")", "PushNumber(1)", "Greater", "JumpIfFalse(8)",
+        "PushNumber(3)", "SetGlobal(\"a\")", "Halt",
+    ]);
+}
+
+#[test]
+fn defined_functions_are_listed_in_the_module() {
+    let source = "function add(a, b) { return a + b; } function add(a) { return a; } function unused() {} add(1, 2);";
+    let module = Compiler::new().compile_program(&hiolang::parse(source).unwrap()).unwrap();
+    let mut names: Vec<&str> = module.functions.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, ["add", "add/1", "unused"]);
+    for name in names {
+        assert!(matches!(module.main[module.symbols[name]], BytecodeOp::Enter(..)), "{} does not start at its symbol", name);
+    }
+}
+
+#[test]
+fn nested_functions_number_their_own_parameter_slots() {
+    let source = "function outer(a, b) {
+        function inner(x, y, z) { let w = z; return x + w; }
+        let t = b;
+        return inner(a, t, 1);
+    }
+    outer(1, 2);";
+    // x, y, z take slots 0-2 and the local w slot 3
+    assert_eq!(function_ops(source, "inner"), [
+        "Enter(3, 4)", "GetLocal(2)", "SetLocal(3)", "GetLocal(0)", "GetLocal(3)", "Add", "Return", "Return",
+    ]);
+    // inner's slots don't shift outer's: a and b are 0 and 1, t is 2
+    let outer = function_ops(source, "outer");
+    assert_eq!(outer[..5], ["Enter(2, 3)", "GetLocal(1)", "SetLocal(2)", "GetLocal(0)", "GetLocal(2)"]);
+}
+
+#[test]
+fn and_and_or_jump_past_the_right_operand() {
+    // Either operand being false jumps straight to the false result at 6
+    assert_eq!(compile("let x = a && b;", false), [
+        "GetGlobal(\"a\")", "JumpIfFalse(6)", "GetGlobal(\"b\")", "JumpIfFalse(6)",
+        "PushBool(true)", "Jump(7)", "PushBool(false)", "SetGlobal(\"x\")", "Halt",
+    ]);
+    // `||` tests the negated operands, so either one being true jumps to the true result at 8
+    assert_eq!(compile("let y = a || b;", false), [
+        "GetGlobal(\"a\")", "Not", "JumpIfFalse(8)", "GetGlobal(\"b\")", "Not", "JumpIfFalse(8)",
+        "PushBool(false)", "Jump(9)", "PushBool(true)", "SetGlobal(\"y\")", "Halt",
+    ]);
+}
@@ -0,0 +1,31 @@
+/* Generated by hiolang transpile */
+#include <stdio.h>
+#include <string.h>
+
+long long square(long long n);
+
+long long square(long long n) {
+    return (n * n);
+}
+
+int main(void) {
+    long long total = 0LL;
+    {
+        long long i = 1LL;
+        for (; (i <= 4LL); (i++)) {
+            total = (total + square(i));
+        }
+    }
+    double ratio = ((double)total / 4.0);
+    const char * label = "sum of squares";
+    if ((total > 20LL)) {
+        printf("%s %lld %g\n", label, total, ratio);
+    } else {
+        printf("%s", "small");
+    }
+    while ((total > 0LL)) {
+        total = (total - 7LL);
+    }
+    printf("%s\n", ((total == -5LL) ? "true" : "false"));
+    return 0;
+}
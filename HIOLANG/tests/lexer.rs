@@ -0,0 +1,142 @@
+// Tokens the lexer produces, especially around comments and interpolated strings
+
+mod common;
+
+use common::output;
+use hiolang::lexer::StringPart;
+use hiolang::{tokenize, Span, Token};
+
+fn string(s: &str) -> Token {
+    Token::String(s.to_string())
+}
+
+#[test]
+fn single_quoted_strings_may_start_with_a_star() {
+    assert_eq!(tokenize("'*'"), vec![string("*"), Token::Eof]);
+    assert_eq!(tokenize("'*ptr' + '**'"), vec![string("*ptr"), Token::Plus, string("**"), Token::Eof]);
+    assert_eq!(output("print('*');"), "*");
+}
+
+#[test]
+fn block_comments_span_lines_and_nest() {
+    let source = "'* outer\n   '* inner *'\n   still outer *'\nprint(1);";
+    assert_eq!(output(source), "1");
+    assert_eq!(tokenize("'*\n*' 7"), vec![Token::Number(7), Token::Eof]);
+}
+
+#[test]
+fn a_block_comment_can_hold_star_strings() {
+    assert_eq!(tokenize("'* print('*x'); *' 1"), vec![Token::Number(1), Token::Eof]);
+    assert_eq!(tokenize("'* print('*'); *' 1"), vec![Token::Number(1), Token::Eof]);
+    assert_eq!(tokenize("'* let s = 'a*'; *' 1"), vec![Token::Number(1), Token::Eof]);
+    assert_eq!(hiolang::eval("'* print('*'); *' 1;"), Ok(hiolang::Value::Number(1)));
+}
+
+#[test]
+fn an_unterminated_block_comment_is_an_error() {
+    assert_eq!(tokenize("'* open '* nested *'"), vec![Token::Error("Unterminated block comment".to_string()), Token::Eof]);
+}
+
+#[test]
+fn line_comments_are_unchanged() {
+    assert_eq!(tokenize("1 '' '* not a block\n2"), vec![Token::Number(1), Token::Number(2), Token::Eof]);
+}
+
+#[test]
+fn interpolated_strings_split_into_text_and_code() {
+    let parts = vec![
+        StringPart::Text("a ".to_string()),
+        StringPart::Code("x + 1".to_string(), Span { line: 1, column: 6 }),
+        StringPart::Text("!".to_string()),
+    ];
+    assert_eq!(tokenize("\"a ${x + 1}!\""), vec![Token::InterpolatedString(parts), Token::Eof]);
+    assert_eq!(tokenize("\"cost \\${x} $5\""), vec![string("cost ${x} $5"), Token::Eof]);
+    assert_eq!(tokenize("r\"${x}\""), vec![string("${x}"), Token::Eof]);
+}
+
+#[test]
+fn interpolation_evaluates_nested_expressions() {
+    let source = "let name = \"Ada\"; let xs = [1, 2];
+        println(\"Hi ${name}, ${len(xs)} items, sum ${xs[0] + xs[1] * 10}, ${\"inner ${name}\"}\");";
+    assert_eq!(output(source), "Hi Ada, 2 items, sum 21, inner Ada\n");
+    assert_eq!(output("println(\"${{a: [1, 2]}} ${1 < 2} ${nil}\");"), "{a: [1, 2]} true null\n");
+}
+
+#[test]
+fn an_unterminated_interpolation_is_an_error() {
+    assert_eq!(tokenize("\"a ${x\""), vec![Token::Error("Unterminated interpolation in string".to_string()), Token::Eof]);
+}
+
+#[test]
+fn a_shebang_first_line_is_skipped() {
+    let script = "#!/usr/bin/env hiolang run\nlet x = 2;\nprintln(x * 21);";
+    assert_eq!(output(script), "42\n");
+    assert_eq!(tokenize("#!/bin/hiolang\n7"), vec![Token::Number(7), Token::Eof]);
+}
+
+#[test]
+fn a_shebang_anywhere_else_is_an_error() {
+    assert_eq!(tokenize("1 #!x")[1], Token::Unknown('#'));
+    assert!(hiolang::eval("\n#!/usr/bin/env hiolang run\n1;").unwrap_err().contains("Unexpected character '#'"));
+}
+
+#[test]
+fn an_em_dash_lexes_to_a_dash_arrow() {
+    assert_eq!(tokenize("\u{2014}}"), vec![Token::DashArrow, Token::RightBrace, Token::Eof]);
+    let tokens = tokenize("pub {\n  1;\n\u{2014}}");
+    assert_eq!(tokens[tokens.len() - 3..], [Token::DashArrow, Token::RightBrace, Token::Eof]);
+}
+
+#[test]
+fn a_pub_block_may_end_with_an_em_dash_or_an_ascii_arrow() {
+    assert_eq!(output("pub;{;com 'interpretation'};{\n  println(1);\n\u{2014}}"), "1\n");
+    assert_eq!(output("pub {\n  println(2);\n->}"), "2\n");
+}
+
+#[test]
+fn unknown_characters_become_unknown_tokens() {
+    assert_eq!(tokenize("@#"), vec![Token::Unknown('@'), Token::Unknown('#'), Token::Eof]);
+}
+
+#[test]
+fn the_parser_reports_the_first_unknown_character_with_its_position() {
+    assert_eq!(hiolang::parse("@#").unwrap_err(), "Unexpected character '@' at line 1:1");
+    assert_eq!(hiolang::parse("let x = 1;\n  x @# 2;").unwrap_err(), "Unexpected character '@' at line 2:5");
+}
+
+#[test]
+fn exponents_make_float_literals() {
+    assert_eq!(tokenize("1e3 2.5e-3 1E2 1.5e+2"), vec![
+        Token::Float(1000.0), Token::Float(0.0025), Token::Float(100.0), Token::Float(150.0), Token::Eof,
+    ]);
+    // Without digits after it the `e` is a separate identifier
+    assert_eq!(tokenize("1e"), vec![Token::Number(1), Token::Identifier("e".to_string()), Token::Eof]);
+}
+
+#[test]
+fn integer_literals_past_the_largest_number_are_lexer_errors() {
+    assert_eq!(tokenize("9223372036854775807"), vec![Token::Number(i64::MAX), Token::Eof]);
+    assert_eq!(tokenize("9223372036854775809"), vec![Token::Error("Integer literal out of range".to_string()), Token::Eof]);
+    // One past the largest is read as the smallest, for the parser to accept only after a `-`
+    assert_eq!(tokenize("-9223372036854775808"), vec![Token::Minus, Token::Number(i64::MIN), Token::Eof]);
+}
+
+#[test]
+fn raw_strings_keep_escapes_as_written() {
+    assert_eq!(tokenize(r#"r"a\nb""#), vec![string("a\\nb"), Token::Eof]);
+    assert_eq!(output(r#"let s = r"a\nb"; println(len(s), s);"#), "4 a\\nb\n");
+    assert_eq!(output(r##"println(r#"C:\new "dir"\t${x}"#);"##), "C:\\new \"dir\"\\t${x}\n");
+    // The same text in a normal string is three characters with a real newline
+    assert_eq!(output(r#"let s = "a\nb"; println(len(s));"#), "3\n");
+}
+
+#[test]
+fn single_ampersand_and_bar_are_not_logical_operators() {
+    let a = || Token::Identifier("a".to_string());
+    let b = || Token::Identifier("b".to_string());
+    assert_eq!(tokenize("a && b"), vec![a(), Token::And, b(), Token::Eof]);
+    assert_eq!(tokenize("a & b"), vec![a(), Token::Error("Unexpected '&', use '&&' for logical and".to_string()), b(), Token::Eof]);
+    assert_eq!(tokenize("a || b"), vec![a(), Token::Or, b(), Token::Eof]);
+    assert_eq!(tokenize("a | b"), vec![a(), Token::Error("Unexpected '|', use '||' for logical or".to_string()), b(), Token::Eof]);
+    assert_eq!(hiolang::eval("let a = 1; a & 2;").unwrap_err(), "Lexer error: Unexpected '&', use '&&' for logical and at line 1:14");
+}
@@ -0,0 +1,68 @@
+// The library manager and the JSON form libraries are saved in
+
+use hiolang::library::{
+    create_stdlib_c, create_stdlib_cpp, create_stdlib_go, create_stdlib_rust, HioCLibrary, LibraryFunction, LibraryManager,
+};
+
+fn stdlib_manager() -> LibraryManager {
+    let mut manager = LibraryManager::new();
+    for lib in [create_stdlib_c(), create_stdlib_cpp(), create_stdlib_rust(), create_stdlib_go()] {
+        manager.register_library(lib);
+    }
+    manager
+}
+
+#[test]
+fn function_count_covers_each_stdlib() {
+    let manager = stdlib_manager();
+    assert_eq!(manager.function_count("stdlib_c"), Some(2));
+    assert_eq!(manager.function_count("stdlib_cpp"), Some(1));
+    assert_eq!(manager.function_count("stdlib_rust"), Some(1));
+    assert_eq!(manager.function_count("stdlib_go"), Some(1));
+    assert_eq!(manager.function_count("stdlib_lua"), None);
+}
+
+#[test]
+fn find_function_matches_substrings_across_libraries_in_order() {
+    let manager = stdlib_manager();
+    let found: Vec<(String, String)> = manager.find_function("str")
+        .into_iter()
+        .map(|(lib, func)| (lib, func.name.clone()))
+        .collect();
+    let expected = [
+        ("stdlib_c", "strcpy"), ("stdlib_c", "strlen"), ("stdlib_cpp", "string_length"),
+        ("stdlib_go", "bytes_to_string"), ("stdlib_rust", "string_reverse"),
+    ];
+    assert_eq!(found, expected.map(|(l, f)| (l.to_string(), f.to_string())));
+    assert_eq!(manager.find_function("strlen")[0].1.params, ["str"]);
+    assert!(manager.find_function("no_such_function").is_empty());
+}
+
+#[test]
+fn remove_library_reports_whether_it_was_registered() {
+    let mut manager = stdlib_manager();
+    assert!(manager.remove_library("stdlib_c"));
+    assert!(!manager.remove_library("stdlib_c"));
+    assert_eq!(manager.function_count("stdlib_c"), None);
+    assert!(manager.find_function("strlen").is_empty());
+}
+
+#[test]
+fn descriptions_with_quotes_and_newlines_survive_a_json_round_trip() {
+    let description = "Says \"hi\"\nthen\tleaves \\ quietly";
+    let mut lib = HioCLibrary::new("greet".to_string(), "1.0".to_string(), description.to_string(), "C".to_string());
+    lib.add_function(LibraryFunction {
+        name: "hello".to_string(),
+        params: vec!["who".to_string()],
+        return_type: "void".to_string(),
+        implementation_language: "C".to_string(),
+        source_code: "void hello(const char* who) {\n    printf(\"hi %s\\n\", who);\n}".to_string(),
+    });
+    let json = lib.export_to_json();
+    assert!(json.contains(r#""description": "Says \"hi\"\nthen\tleaves \\ quietly""#), "{}", json);
+    let back = HioCLibrary::from_json(&json).unwrap();
+    assert_eq!(back.description, description);
+    let hello = back.get_function("hello").unwrap();
+    assert_eq!(hello.source_code, lib.get_function("hello").unwrap().source_code);
+    assert_eq!(hello.params, ["who"]);
+}
@@ -0,0 +1,313 @@
+// Binary and unary operators as the interpreter evaluates them
+
+mod common;
+
+use common::{eval, eval_err, eval_str};
+use hiolang::{Interpreter, Value};
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+#[test]
+fn plus_stringifies_a_number_on_either_side_of_a_string() {
+    assert_eq!(eval("\"x\" + 5;"), string("x5"));
+    assert_eq!(eval("5 + \"x\";"), string("5x"));
+    assert_eq!(eval("\"n=\" + 1.5 + [1, 2];"), string("n=1.5[1, 2]"));
+}
+
+#[test]
+fn other_arithmetic_on_a_string_and_a_number_still_fails() {
+    assert!(eval_err("\"x\" - 5;").starts_with("Invalid binary operation"));
+    assert!(eval_err("5 - \"x\";").starts_with("Invalid binary operation"));
+    assert!(eval_err("\"x\" / 5;").starts_with("Invalid binary operation"));
+}
+
+#[test]
+fn strict_mode_turns_off_plus_coercion() {
+    let program = hiolang::parse("\"x\" + 5;").unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_string_coercion(false);
+    assert!(interpreter.interpret(&program).is_err());
+    assert_eq!(eval("\"x\" + \"y\";"), string("xy"));
+}
+
+#[test]
+fn slices_take_omitted_bounds_from_the_ends() {
+    assert_eq!(eval_str("let a = [1, 2, 3, 4]; [a[1:3], a[:2], a[2:], a[:]];"), "[[2, 3], [1, 2], [3, 4], [1, 2, 3, 4]]");
+    assert_eq!(eval("\"hello\"[1:4];"), string("ell"));
+    assert_eq!(eval("\"héllo\"[:2];"), string("hé"));
+}
+
+#[test]
+fn slices_clamp_out_of_range_bounds() {
+    assert_eq!(eval_str("[1, 2, 3][1:99];"), "[2, 3]");
+    assert_eq!(eval("\"abc\"[2:50];"), string("c"));
+}
+
+#[test]
+fn empty_slices() {
+    assert_eq!(eval_str("[1, 2, 3][2:2];"), "[]");
+    assert_eq!(eval_str("[1, 2, 3][3:1];"), "[]");
+    assert_eq!(eval_str("[1, 2, 3][10:];"), "[]");
+    assert_eq!(eval_str("[][:];"), "[]");
+    assert_eq!(eval("\"abc\"[5:9];"), string(""));
+}
+
+#[test]
+fn three_term_comparison_chains() {
+    assert_eq!(eval("let x = 5; 0 < x < 10;"), Value::Boolean(true));
+    assert_eq!(eval("let x = 15; 0 < x < 10;"), Value::Boolean(false));
+    assert_eq!(eval("let x = 10; 0 < x <= 10;"), Value::Boolean(true));
+    assert_eq!(eval("3 > 2 > 1;"), Value::Boolean(true));
+}
+
+#[test]
+fn four_term_comparison_chains() {
+    assert_eq!(eval("1 < 2 < 3 < 4;"), Value::Boolean(true));
+    assert_eq!(eval("1 < 2 < 3 > 4;"), Value::Boolean(false));
+    assert_eq!(eval("1 <= 1 < 2 >= 2;"), Value::Boolean(true));
+}
+
+#[test]
+fn chained_middle_operands_are_evaluated_once() {
+    let source = "let calls = 0; function mid() { calls++; return 5; } let r = 0 < mid() < 10 < 20; [r, calls];";
+    assert_eq!(eval_str(source), "[true, 1]");
+    // A false link stops the chain before later operands run
+    let source = "let calls = 0; function f() { calls++; return 1; } let r = 5 < 1 < f(); [r, calls];";
+    assert_eq!(eval_str(source), "[false, 0]");
+}
+
+#[test]
+fn and_skips_its_right_side_when_the_left_is_false() {
+    assert_eq!(eval("let x = 0; x != 0 && 10 / x > 1;"), Value::Boolean(false));
+    assert_eq!(eval("0 && undefined_function();"), Value::Boolean(false));
+    assert!(eval_err("1 && 1 / 0;").contains("Division by zero"));
+}
+
+#[test]
+fn or_skips_its_right_side_when_the_left_is_true() {
+    assert_eq!(eval("let x = 0; x == 0 || 10 / x > 1;"), Value::Boolean(true));
+    assert_eq!(eval("1 || missing[3];"), Value::Boolean(true));
+    assert!(eval_err("0 || missing;").contains("missing"));
+}
+
+#[test]
+fn strings_and_arrays_repeat_in_either_order() {
+    assert_eq!(eval("\"-\" * 0;"), string(""));
+    assert_eq!(eval("\"-\" * 1;"), string("-"));
+    assert_eq!(eval("3 * \"ab\";"), string("ababab"));
+    assert_eq!(eval_str("[[0] * 0, [0] * 1, 3 * [1, 2]];"), "[[], [0], [1, 2, 1, 2, 1, 2]]");
+}
+
+#[test]
+fn repetition_rejects_negative_and_huge_counts() {
+    assert_eq!(eval_err("\"ab\" * -1;"), "Repetition count must not be negative, got -1");
+    assert_eq!(eval_err("[1, 2] * -3;"), "Repetition count must not be negative, got -3");
+    let too_large = "Repetition result too large: 2 x 9223372036854775807 exceeds the limit of 10000000";
+    assert_eq!(eval_err("\"ab\" * 9223372036854775807;"), too_large);
+    assert_eq!(eval_err("[1, 2] * 9223372036854775807;"), too_large);
+}
+
+#[test]
+fn prefix_increment_returns_the_new_value() {
+    assert_eq!(eval_str("let i = 5; let r = ++i; [r, i];"), "[6, 6]");
+    assert_eq!(eval_str("let i = 5; let r = --i; [r, i];"), "[4, 4]");
+}
+
+#[test]
+fn postfix_increment_returns_the_old_value() {
+    assert_eq!(eval_str("let i = 5; let r = i++; [r, i];"), "[5, 6]");
+    assert_eq!(eval_str("let i = 5; let r = i--; [r, i];"), "[5, 4]");
+    assert_eq!(eval_str("let f = 1.5; let r = f--; [r, f];"), "[1.5, 0.5]");
+}
+
+#[test]
+fn increment_needs_a_numeric_variable() {
+    assert_eq!(eval_err("5++;"), "Operator ++ requires a variable");
+    assert_eq!(eval_err("let s = \"a\"; s++;"), "Operator ++ requires a number or float, got string");
+    assert_eq!(eval_err("let xs = [1]; --xs;"), "Operator -- requires a number or float, got array");
+}
+
+#[test]
+fn plus_concatenates_arrays_into_a_new_array() {
+    assert_eq!(eval_str("let a = [1, 2]; let b = [3]; let c = a + b; [c, a, b];"), "[[1, 2, 3], [1, 2], [3]]");
+    assert_eq!(eval_str("[] + [];"), "[]");
+    assert!(eval_err("[1] + 2;").starts_with("Invalid binary operation"));
+}
+
+#[test]
+fn arrays_compare_element_by_element() {
+    assert_eq!(eval("[1, [2, \"x\"]] == [1, [2, \"x\"]];"), Value::Boolean(true));
+    assert_eq!(eval("[1, 2] == [1, 2, 3];"), Value::Boolean(false));
+    assert_eq!(eval("[1, 2] != [2, 1];"), Value::Boolean(true));
+    assert_eq!(eval("[1] == [1.0];"), Value::Boolean(true));
+}
+
+#[test]
+fn arithmetic_overflow_is_an_error() {
+    assert_eq!(eval_err("9223372036854775807 + 1;"), "Arithmetic overflow: 9223372036854775807 + 1");
+    assert_eq!(eval_err("-9223372036854775807 - 2;"), "Arithmetic overflow: -9223372036854775807 - 2");
+    assert_eq!(eval_err("9223372036854775807 * 2;"), "Arithmetic overflow: 9223372036854775807 * 2");
+    assert_eq!(eval_err("let i = 9223372036854775807; i++;"), "Arithmetic overflow: 9223372036854775807 + 1");
+}
+
+#[test]
+fn integer_literals_must_fit_in_a_number() {
+    assert_eq!(eval("9223372036854775807;"), Value::Number(i64::MAX));
+    assert_eq!(eval_err("9223372036854775808;"), "Integer literal out of range at line 1:1");
+    assert_eq!(eval_err("9223372036854775809;"), "Lexer error: Integer literal out of range at line 1:1");
+    assert_eq!(eval_err("let x =\n  99999999999999999999;"), "Lexer error: Integer literal out of range at line 2:3");
+    // Floats have the range to hold it
+    assert_eq!(eval("9223372036854775808.0;"), Value::Float(9223372036854775808.0));
+}
+
+#[test]
+fn the_smallest_number_can_be_written_as_a_negative_literal() {
+    assert_eq!(eval("-9223372036854775808;"), Value::Number(i64::MIN));
+    assert_eq!(eval("let m = -9223372036854775808; m == -9223372036854775807 - 1;"), Value::Boolean(true));
+    // Only a `-` directly in front makes the magnitude fit
+    assert_eq!(eval_err("1 - 9223372036854775808;"), "Integer literal out of range at line 1:5");
+    assert_eq!(eval_err("let o = {9223372036854775808: 1};"), "Integer literal out of range at line 1:10");
+    assert_eq!(eval_err("- -9223372036854775808;"), "Arithmetic overflow: -(-9223372036854775808)");
+}
+
+#[test]
+fn the_smallest_number_overflows_when_negated_or_divided_by_minus_one() {
+    let min = "let m = -9223372036854775807 - 1;";
+    assert_eq!(eval_str(&format!("{} m;", min)), "-9223372036854775808");
+    assert_eq!(eval_err(&format!("{} -m;", min)), "Arithmetic overflow: -(-9223372036854775808)");
+    assert_eq!(eval_err(&format!("{} m / -1;", min)), "Arithmetic overflow: -9223372036854775808 / -1");
+    assert_eq!(eval_err(&format!("{} m // -1;", min)), "Arithmetic overflow: -9223372036854775808 // -1");
+    assert_eq!(eval_str(&format!("{} m % -1;", min)), "0");
+}
+
+#[test]
+fn negative_float_zero_prints_as_zero() {
+    assert_eq!(eval_str("-0.0;"), "0");
+    assert_eq!(eval_str("0.0 * -1.0;"), "0");
+}
+
+#[test]
+fn nan_is_unequal_to_everything_and_unordered() {
+    let nan = "let n = pow(-1, 0.5);";
+    assert_eq!(eval_str(&format!("{} [n == n, n != n, [n] == [n], n < 1, n > 1, n >= n];", nan)), "[false, true, false, false, false, false]");
+    assert_eq!(eval_str(&format!("{} [contains([n], n), index_of([n], n)];", nan)), "[false, -1]");
+    assert_eq!(eval_err(&format!("{} sort([2, n]);", nan)), "sort() cannot order NaN");
+    assert_eq!(eval_str(&format!("{} [is_nan(n), is_nan(1.5), is_nan(1)];", nan)), "[true, false, false]");
+}
+
+#[test]
+fn numbers_and_floats_compare_with_the_float_epsilon_everywhere() {
+    let tiny = "0.00000000000000001";
+    assert_eq!(eval(&format!("0 == {};", tiny)), Value::Boolean(true));
+    // Equality inside arrays, searches and sets follows the same rule as ==
+    assert_eq!(eval(&format!("[0, [1]] == [{}, [1.0]];", tiny)), Value::Boolean(true));
+    assert_eq!(eval(&format!("contains([0], {});", tiny)), Value::Boolean(true));
+    assert_eq!(eval_str(&format!("let s = {{0, {}}}; s;", tiny)), "{0}");
+    assert_eq!(eval("[1] == [1.5];"), Value::Boolean(false));
+}
+
+#[test]
+fn strings_index_and_measure_by_character() {
+    let word = "let s = \"héllo→😀\";";
+    assert_eq!(eval(&format!("{} len(s);", word)), Value::Number(7));
+    assert_eq!(eval_str(&format!("{} [s[1], s[5], s[6], s[len(s) - 1]];", word)), "[é, →, 😀, 😀]");
+    assert_eq!(eval_str(&format!("{} type(s[1]);", word)), "char");
+    assert_eq!(eval(&format!("{} s[4:len(s)];", word)), string("o→😀"));
+    assert_eq!(eval_str("[char_code(\"é\"[0]), from_char_code(233)];"), "[233, é]");
+    assert!(eval_err(&format!("{} s[7];", word)).contains("out of bounds"));
+}
+
+#[test]
+fn wrapping_builtins_wrap_in_either_mode() {
+    assert_eq!(eval_str("[wrapping_add(9223372036854775807, 1), wrapping_sub(-9223372036854775807, 2), wrapping_mul(4611686018427387904, 4)];"), "[-9223372036854775808, 9223372036854775807, 0]");
+    assert_eq!(eval_str("wrapping_add(2, 3);"), "5");
+    assert_eq!(eval_err("wrapping_add(1, 1.5);"), "wrapping_add() requires two numbers, got float");
+}
+
+#[test]
+fn wrap_mode_makes_operators_wrap_until_checked_mode_returns() {
+    let source = "set_int_mode(\"wrap\"); let m = 9223372036854775807; let i = m; i++;
+        let r = [m + 1, m * 2, -(m + 1), (m + 1) / -1, i];";
+    assert_eq!(eval_str(&format!("{} r;", source)), "[-9223372036854775808, -2, -9223372036854775808, -9223372036854775808, -9223372036854775808]");
+    assert_eq!(eval_err(&format!("{} set_int_mode(\"checked\"); m + 1;", source)), "Arithmetic overflow: 9223372036854775807 + 1");
+    // abs still reports overflow in wrap mode
+    assert!(eval_err("set_int_mode(\"wrap\"); abs(-9223372036854775807 - 1);").contains("overflow"));
+}
+
+#[test]
+fn set_int_mode_only_accepts_wrap_or_checked() {
+    assert_eq!(eval_err("set_int_mode(\"saturate\");"), "set_int_mode() mode must be \"wrap\" or \"checked\", got \"saturate\"");
+    assert_eq!(eval_err("set_int_mode(1);"), "set_int_mode() requires a string, got number");
+}
+
+#[test]
+fn float_division_and_modulo_by_zero_are_errors() {
+    assert_eq!(eval_err("1.5 / 0.0;"), "Division by zero");
+    assert_eq!(eval_err("0.0 / 0.0;"), "Division by zero");
+    assert_eq!(eval_err("1 / 0.0;"), "Division by zero");
+    assert_eq!(eval_err("2.5 / 0;"), "Division by zero");
+    assert_eq!(eval_err("1.0 // 0.0;"), "Integer division by zero");
+    assert_eq!(eval_err("7 % 0.0;"), "Modulo by zero");
+    assert_eq!(eval_err("7.5 % 0;"), "Modulo by zero");
+}
+
+#[test]
+fn negative_zero_is_a_zero_divisor_too() {
+    assert_eq!(eval_err("1.0 / -0.0;"), "Division by zero");
+    assert_eq!(eval_err("5.5 % -0.0;"), "Modulo by zero");
+    // A float result that overflows is still an infinity rather than an error
+    assert_eq!(eval_str("1e308 * 10;"), "inf");
+}
+
+#[test]
+fn nested_ternaries_group_to_the_right() {
+    let grade = |n: i64| format!("let n = {}; n >= 90 ? \"A\" : n >= 80 ? \"B\" : n >= 70 ? \"C\" : \"F\";", n);
+    assert_eq!(eval(&grade(95)), string("A"));
+    assert_eq!(eval(&grade(85)), string("B"));
+    assert_eq!(eval(&grade(72)), string("C"));
+    assert_eq!(eval(&grade(10)), string("F"));
+    assert_eq!(eval("1 ? 2 > 1 ? \"yes\" : \"no\" : \"outer\";"), string("yes"));
+}
+
+#[test]
+fn a_ternary_evaluates_only_the_branch_it_takes() {
+    assert_eq!(eval("let x = 0; x == 0 ? 0 : 10 / x;"), Value::Number(0));
+    assert_eq!(eval("1 > 2 ? missing() : 1 ? 7 : missing();"), Value::Number(7));
+}
+
+#[test]
+fn values_compare_structurally_in_rust_too() {
+    assert_eq!(eval("[1, [\"a\"], {k: 2}];"), eval("[1, [\"a\"], {k: 2}];"));
+    assert_ne!(eval("[1, 2];"), eval("[2, 1];"));
+    assert_eq!(eval("let o = {a: 1, b: [2]}; o;"), eval("let o = {b: [2], a: 1}; o;"));
+    assert_ne!(eval("let o = {a: 1}; o;"), eval("let o = {a: 1, b: 2}; o;"));
+    assert_eq!(eval("let s = {1, 2, 3}; s;"), eval("let s = {3, 2, 1}; s;"));
+    assert_ne!(eval("let s = {1, 2}; s;"), eval("let s = {1, 2, 3}; s;"));
+    assert_eq!(Value::Array(vec![string("x")]), eval("[\"x\"];"));
+}
+
+#[test]
+fn true_and_false_are_boolean_literals() {
+    assert_eq!(eval("true;"), Value::Boolean(true));
+    assert_eq!(eval("[type(false), !true, {true: 1, false: 0}];").to_string(), "[boolean, false, {true: 1, false: 0}]");
+    assert_eq!(hiolang::tokenize("true false"), [hiolang::Token::True, hiolang::Token::False, hiolang::Token::Eof]);
+}
+
+#[test]
+fn booleans_are_equal_only_to_the_same_boolean() {
+    assert_eq!(eval("true == true;"), Value::Boolean(true));
+    assert_eq!(eval("true == false;"), Value::Boolean(false));
+    assert_eq!(eval("false != true;"), Value::Boolean(true));
+    assert_eq!(eval("(1 < 2) == true;"), Value::Boolean(true));
+    assert_eq!(eval("true == 1;"), Value::Boolean(false));
+    assert_eq!(eval("[true, false] == [true, false];"), Value::Boolean(true));
+    assert_eq!(eval_str("let flag = 3 > 2; let seen = 0; if (flag == true) { seen = 1; } if (flag != false) { seen = seen + 1; } seen;"), "2");
+}
+
+#[test]
+fn booleans_have_no_order() {
+    assert_eq!(eval_err("true < false;"), "Cannot compare booleans with '<'");
+    assert_eq!(eval_err("false >= false;"), "Cannot compare booleans with '>='");
+}
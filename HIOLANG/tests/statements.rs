@@ -0,0 +1,403 @@
+// Statements run by the interpreter: loops, functions, error handling, defer and with
+
+mod common;
+
+use common::{eval, eval_err, eval_str, output, run_capturing};
+use hiolang::{Interpreter, Value};
+
+#[test]
+fn while_else_runs_when_the_condition_ends_the_loop() {
+    let source = "let i = 0; while (i < 3) { i = i + 1; } else { println(\"done\", i); }";
+    assert_eq!(output(source), "done 3\n");
+}
+
+#[test]
+fn while_else_is_skipped_after_break() {
+    let source = "let i = 0; while (i < 3) { if (i == 1) { break; } i = i + 1; } else { println(\"done\"); } i;";
+    assert_eq!(output(source), "");
+    assert_eq!(eval(source), Value::Number(1));
+}
+
+#[test]
+fn for_else_distinguishes_break_from_natural_exit() {
+    let search = |target: i64| format!(
+        "let found = \"no\"; for (let i = 0; i < 5; i++) {{ if (i == {}) {{ found = i; break; }} }} else {{ found = \"else\"; }} found;",
+        target,
+    );
+    assert_eq!(eval_str(&search(2)), "2");
+    assert_eq!(eval_str(&search(9)), "else");
+}
+
+#[test]
+fn loop_else_runs_when_the_body_never_runs() {
+    assert_eq!(output("while (1 > 2) { println(\"body\"); } else { println(\"else\"); }"), "else\n");
+    assert_eq!(output("for (let i = 0; i < 0; i++) { println(\"body\"); } else { println(\"else\"); }"), "else\n");
+}
+
+#[test]
+fn continue_does_not_skip_loop_else() {
+    let source = "for (let i = 0; i < 3; i++) { continue; } else { println(\"else\"); }";
+    assert_eq!(output(source), "else\n");
+}
+
+#[test]
+fn array_destructuring_binds_names_in_order() {
+    assert_eq!(eval_str("let [a, b, c] = [1, \"two\", [3]]; [c, b, a];"), "[[3], two, 1]");
+}
+
+#[test]
+fn array_destructuring_requires_the_exact_length() {
+    assert_eq!(eval_err("let [a, b, c] = [1, 2];"), "Cannot destructure array of length 2 into 3 names");
+    assert_eq!(eval_err("let [a] = [1, 2];"), "Cannot destructure array of length 2 into 1 names");
+    assert_eq!(eval_err("let [a, b] = 5;"), "Cannot destructure number as an array");
+}
+
+#[test]
+fn a_rest_pattern_collects_the_remaining_elements() {
+    assert_eq!(eval_str("let [first, ...rest] = [1, 2, 3]; [first, rest];"), "[1, [2, 3]]");
+    assert_eq!(eval_str("let [a, b, ...rest] = [1, 2]; rest;"), "[]");
+    assert_eq!(eval_str("let [...all] = [4, 5]; all;"), "[4, 5]");
+    assert_eq!(eval_err("let [a, b, ...rest] = [1];"), "Cannot destructure array of length 1 into 2 names");
+}
+
+#[test]
+fn catch_binds_a_thrown_value_as_an_error() {
+    assert_eq!(eval("try { throw \"bad\"; } catch (e) { e; }"), Value::Error { message: "bad".to_string() });
+    assert_eq!(eval_str("try { throw 42; } catch (e) { type(e); }"), "error");
+    assert_eq!(eval_str("try { 1; } catch (e) { 2; }"), "1");
+}
+
+#[test]
+fn division_by_zero_is_catchable() {
+    assert_eq!(eval("try { 1 / 0; } catch (e) { e; }"), Value::Error { message: "Division by zero".to_string() });
+}
+
+#[test]
+fn throw_unwinds_through_function_calls() {
+    let source = "function inner() { throw \"deep\"; } function outer() { inner(); return 1; }
+        try { outer(); } catch (e) { println(e); }";
+    assert_eq!(output(source), "Error: deep\n");
+    assert_eq!(eval_err("throw \"uncaught\";"), "uncaught");
+}
+
+fn run_with_call_depth(source: &str, depth: usize) -> Result<Value, String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_call_depth(depth);
+    interpreter.interpret(&hiolang::parse(source).unwrap())
+}
+
+#[test]
+fn runaway_recursion_hits_the_call_depth_limit() {
+    let source = "function down(n) { return down(n + 1); } down(0);";
+    // The message is followed by the call trace
+    assert!(run_with_call_depth(source, 50).unwrap_err().starts_with("Stack overflow: recursion limit exceeded (50 nested calls)\n  at down()"));
+}
+
+#[test]
+fn the_default_limits_trip_before_a_normal_thread_runs_out_of_stack() {
+    // Test threads get the 2 MiB stack Rust gives any spawned thread
+    let sources = [
+        "function down(n) { return down(n + 1); } down(0);",
+        "function deep(n) { if (n > 0) { while (n > 0) { for (let i = 0; i < 1; i++) { try { return [{v: 1 + deep(n - 1)}]; } catch (e) { throw e; } } } } return 0; } deep(100000);",
+    ];
+    for source in sources {
+        let error = eval_err(source);
+        assert!(error.starts_with("Stack overflow: recursion limit exceeded ("), "{}", error);
+    }
+}
+
+#[test]
+fn a_larger_stack_allowance_reaches_the_call_depth_limit() {
+    let error = std::thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(|| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_max_stack_size(192 * 1024 * 1024);
+            interpreter.interpret(&hiolang::parse("function down(n) { return down(n + 1); } down(0);").unwrap()).unwrap_err()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(error.starts_with("Stack overflow: recursion limit exceeded (1000 nested calls)"));
+}
+
+#[test]
+fn recursion_within_the_limit_succeeds() {
+    // depth(n) makes n + 1 nested calls
+    let source = "function depth(n) { if (n == 0) { return 0; } return 1 + depth(n - 1); } depth(49);";
+    assert_eq!(run_with_call_depth(source, 50), Ok(Value::Number(49)));
+    assert!(run_with_call_depth(source, 49).is_err());
+}
+
+#[test]
+fn the_call_depth_unwinds_after_a_caught_overflow() {
+    let source = "function down(n) { return down(n + 1); }
+        function depth(n) { if (n == 0) { return 0; } return 1 + depth(n - 1); }
+        let caught = 0;
+        try { down(0); } catch (e) { caught = 1; }
+        [caught, depth(40)];";
+    assert_eq!(run_with_call_depth(source, 50).map(|v| v.to_string()), Ok("[1, 40]".to_string()));
+}
+
+fn statements_executed(source: &str) -> u64 {
+    let mut interpreter = Interpreter::new();
+    interpreter.interpret(&hiolang::parse(source).unwrap()).unwrap();
+    interpreter.statements_executed()
+}
+
+#[test]
+fn the_statement_counter_counts_each_loop_iteration() {
+    let looped = |n: i64| format!("let i = 0; while (i < {}) {{ i = i + 1; }}", n);
+    // The let and the while, then the body's one statement per iteration
+    assert_eq!(statements_executed(&looped(0)), 2);
+    assert_eq!(statements_executed(&looped(3)), 5);
+    assert_eq!(statements_executed(&looped(10)) - statements_executed(&looped(3)), 7);
+}
+
+#[test]
+fn calls_pick_the_overload_with_a_matching_arity() {
+    let source = "function area(r) { return 3 * r * r; } function area(w, h) { return w * h; }";
+    assert_eq!(eval_str(&format!("{} [area(2), area(2, 5)];", source)), "[12, 10]");
+    // A function value keeps every overload
+    assert_eq!(eval_str(&format!("{} let g = area; [g(2), g(2, 5)];", source)), "[12, 10]");
+    assert_eq!(eval_err(&format!("{} area(1, 2, 3);", source)), "area() has no overload taking 3 arguments (defined for 1, 2)");
+}
+
+#[test]
+fn a_definition_with_the_same_arity_replaces_the_earlier_one() {
+    assert_eq!(eval("function f(a) { return 1; } function f(b) { return 2; } f(0);"), Value::Number(2));
+}
+
+#[test]
+fn deferred_statements_run_last_in_first_out_when_the_call_returns() {
+    let source = "function f() { defer println(\"first\"); defer println(\"second\"); println(\"body\"); } f(); println(\"after\");";
+    assert_eq!(output(source), "body\nsecond\nfirst\nafter\n");
+}
+
+#[test]
+fn defer_runs_after_an_early_return_and_sees_current_values() {
+    let source = "function f(n) { let state = \"start\"; defer println(\"cleanup\", state);
+            if (n > 0) { state = \"early\"; return n; }
+            state = \"late\"; return 0; }
+        println(f(1)); println(f(0));";
+    assert_eq!(output(source), "cleanup early\n1\ncleanup late\n0\n");
+}
+
+#[test]
+fn defer_runs_when_the_call_fails() {
+    let source = "function f() { defer println(\"cleanup\"); throw \"boom\"; }
+        try { f(); } catch (e) { println(\"caught\", e.message); }";
+    assert_eq!(output(source), "cleanup\ncaught boom\n");
+}
+
+#[test]
+fn top_level_defers_run_when_the_program_ends() {
+    assert_eq!(output("defer println(\"end\"); println(\"start\");"), "start\nend\n");
+}
+
+#[test]
+fn prelude_functions_are_callable_without_a_definition() {
+    assert_eq!(eval_str("[square(4), clamp(15, 0, 10), clamp(-3, 0, 10), clamp(5, 0, 10), is_even(4), is_odd(4), is_odd(-3)];"), "[16, 10, 0, 5, true, false, true]");
+    assert_eq!(eval_str("type(square);"), "function");
+}
+
+#[test]
+fn a_program_can_replace_or_overload_a_prelude_function() {
+    assert_eq!(eval("function square(x) { return 0; } square(4);"), Value::Number(0));
+    assert_eq!(eval_str("function square(x, y) { return x * y; } [square(3), square(2, 5)];"), "[9, 10]");
+}
+
+#[test]
+fn without_prelude_only_the_native_builtins_exist() {
+    let program = hiolang::parse("square(3);").unwrap();
+    let mut bare = Interpreter::without_prelude();
+    assert!(bare.interpret(&program).unwrap_err().contains("square"));
+    assert_eq!(bare.interpret(&hiolang::parse("len(\"abc\");").unwrap()), Ok(Value::Number(3)));
+}
+
+// A path in the temp directory, quoted as a Hiolang string literal
+fn temp_file(name: &str) -> (std::path::PathBuf, String) {
+    let path = std::env::temp_dir().join(name);
+    let literal = format!("{:?}", path.display().to_string());
+    (path, literal)
+}
+
+#[test]
+fn with_closes_the_file_when_the_block_ends() {
+    let (path, file) = temp_file("hiolang_with_block.txt");
+    let source = format!(
+        "let h = nil; with (f = open({0}, \"w\")) {{ h = f; write(f, \"one\"); }}
+        let text = \"\"; with (f = open({0}, \"r\")) {{ text = read(f); }}
+        println(text); write(h, \"two\");",
+        file,
+    );
+    let (result, printed) = common::run_capturing(&source);
+    assert_eq!(printed, "one\n");
+    assert_eq!(result.unwrap_err(), format!("File is closed: {}", path.display()));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn with_closes_the_file_after_an_error_or_early_return() {
+    let (path, file) = temp_file("hiolang_with_error.txt");
+    let source = format!(
+        "function early() {{ with (f = open({0}, \"a\")) {{ write(f, \"a\"); return 1; }} }}
+        early(); let caught = nil;
+        try {{ with (f = open({0}, \"a\")) {{ write(f, \"b\"); throw \"stop\"; }} }} catch (e) {{ caught = e.message; }}
+        caught;",
+        file,
+    );
+    let mut interpreter = Interpreter::new();
+    let caught = interpreter.interpret(&hiolang::parse(&source).unwrap()).unwrap();
+    assert_eq!(caught.to_string(), "stop");
+    assert_eq!(interpreter.open_files(), 0);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "ab");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn with_requires_a_file_and_scopes_its_name_to_the_block() {
+    assert_eq!(eval_err("with (f = 5) { }"), "'with' requires a file, got number");
+    let (path, file) = temp_file("hiolang_with_scope.txt");
+    let error = eval_err(&format!("with (f = open({}, \"w\")) {{ }} f;", file));
+    assert!(error.contains("Undefined variable"), "{}", error);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_block_let_shadows_the_outer_variable_only_inside_the_block() {
+    assert_eq!(output("let x = 1; if (1) { let x = 2; println(x); } println(x);"), "2\n1\n");
+    assert_eq!(eval("function f() { let x = 9; if (1) { let x = 10; } return x; } f();"), Value::Number(9));
+}
+
+#[test]
+fn assignment_in_a_block_updates_the_outer_variable() {
+    assert_eq!(eval("let y = 1; { y = 5; } y;"), Value::Number(5));
+    assert_eq!(eval("let n = 0; for (let i = 0; i < 3; i++) { n = n + i; } n;"), Value::Number(3));
+}
+
+#[test]
+fn variables_declared_in_a_block_do_not_leak_out() {
+    assert_eq!(eval_err("if (1) { let inner = 3; } inner;"), "Undefined variable: inner");
+    assert_eq!(eval_err("while (1) { let w = 1; break; } w;"), "Undefined variable: w");
+    assert_eq!(eval_err("for (let i = 0; i < 2; i++) { } i;"), "Undefined variable: i");
+}
+
+#[test]
+fn an_uncaught_error_reports_a_trace_from_the_innermost_call() {
+    let source = "function inner(x) {\n  return x - [1];\n}\nfunction outer() {\n  return inner(1) * 2;\n}\nouter();";
+    assert_eq!(
+        eval_err(source),
+        "Invalid binary operation: Number(1) Subtract Array([Number(1)])\n  at inner() line 2, called from line 5\n  at outer() called from line 7",
+    );
+    assert_eq!(eval_err("function f() {\n  throw \"deep\";\n}\nf();"), "deep\n  at f() line 2, called from line 4");
+}
+
+#[test]
+fn do_while_runs_its_body_once_when_the_condition_starts_false() {
+    assert_eq!(output("do { println(\"once\"); } while (1 > 2);"), "once\n");
+    assert_eq!(eval("let n = 10; do { n++; } while (n < 5); n;"), Value::Number(11));
+}
+
+#[test]
+fn do_while_repeats_until_the_condition_fails_and_honors_break_and_continue() {
+    assert_eq!(eval("let n = 0; do { n++; } while (n < 5); n;"), Value::Number(5));
+    let source = "let seen = \"\"; let i = 0; do { i++; if (i == 2) { continue; } if (i == 4) { break; } seen = seen + i; } while (i < 10); seen;";
+    assert_eq!(eval_str(source), "13");
+}
+
+#[test]
+fn reassigning_a_const_fails() {
+    assert_eq!(eval_err("const x = 1; x = 2;"), "Cannot assign to constant 'x'");
+    assert_eq!(eval_err("const x = 1; x++;"), "Cannot assign to constant 'x'");
+    assert_eq!(eval_err("const limit = 3; function f() { limit = 4; } f();").lines().next(), Some("Cannot assign to constant 'limit'"));
+}
+
+#[test]
+fn a_block_may_shadow_a_const_with_its_own_variable() {
+    assert_eq!(eval_str("const x = 1; let seen = 0; if (1) { let x = 2; x = 3; seen = x; } [seen, x];"), "[3, 1]");
+}
+
+#[test]
+fn a_space_body_can_start_with_let_without_the_name_word() {
+    let program = hiolang::parse("space Foo { let x = 1; println(x); } end make;").unwrap();
+    match program.statements.as_slice() {
+        [hiolang::ast::Stmt::Space { name, body }] => {
+            assert_eq!(name, "Foo");
+            assert!(matches!(body.first(), Some(hiolang::ast::Stmt::Let { .. })), "{:?}", body);
+        }
+        other => panic!("expected one space, got {:?}", other),
+    }
+    assert_eq!(output("space Foo { let x = 1; println(x); } end make;"), "1\n");
+    // The optional word still works, and a variable called `name` isn't mistaken for it
+    assert_eq!(output("space Bar name { let name = 2; println(name); } end make;"), "2\n");
+}
+
+#[test]
+fn inspace_sees_pub_names_everywhere_and_subpub_names_only_from_the_enclosing_space() {
+    let spaces = "space Outer {
+        space Math {
+            pub { function square(n) { return n * n; } }
+            subpub { let helper = 42; }
+            let secret = 7;
+        } end make;
+        inspace Math { println(square(4), helper); }
+    } end make;
+    space Top {
+        pub { const limit = 10; }
+        subpub { let shared = 5; }
+        let hidden = 1;
+    } end make;";
+    let visible = format!("{} inspace Math {{ println(square(3)); }} inspace Top {{ println(limit, shared); }}", spaces);
+    assert_eq!(output(&visible), "16 42\n9\n10 5\n");
+    for (body, name) in [("Math", "helper"), ("Math", "secret"), ("Top", "hidden")] {
+        let hidden = format!("{} inspace {} {{ {}; }}", spaces, body, name);
+        assert_eq!(run_capturing(&hidden).0, Err(format!("Undefined variable: {}", name)));
+    }
+    // Nothing leaks out of a space without inspace
+    assert_eq!(run_capturing(&format!("{} limit;", spaces)).0, Err("Undefined variable: limit".to_string()));
+}
+
+#[test]
+fn profiling_counts_every_call_of_each_function() {
+    let source = "function step(n) { return n + 1; } function twice(n) { return step(step(n)); }
+        let total = 0; for (let i = 0; i < 10; i++) { total = step(total); } twice(total);";
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.profile_report().is_empty());
+    interpreter.enable_profiling();
+    interpreter.interpret(&hiolang::parse(source).unwrap()).unwrap();
+    assert_eq!(interpreter.profile_report(), [("step".to_string(), 12), ("twice".to_string(), 1)]);
+}
+
+#[test]
+fn an_imported_file_exports_only_its_pub_names() {
+    let dir = std::env::temp_dir().join("hiolang_import_test");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/shapes.hio"), "let unit = 2;
+        pub { function square(x) { return x * x; } function scaled(x) { return x * unit; } }").unwrap();
+    let main = dir.join("main.hio");
+    let run = |source: &str| {
+        std::fs::write(&main, source).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_source_path(&main);
+        interpreter.interpret(&hiolang::parse(source).unwrap())
+    };
+    assert_eq!(run("import \"lib/shapes.hio\"; [square(3), scaled(5)];").map(|v| v.to_string()), Ok("[9, 10]".to_string()));
+    assert_eq!(run("import \"lib/shapes.hio\"; unit;"), Err("Undefined variable: unit".to_string()));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn exit_called_through_a_variable_and_a_function_sets_the_exit_code() {
+    let source = "let reached = 0; let quit = exit;
+        function stop(f, code) { try { f(code); } catch (e) { reached = -1; } reached = -2; }
+        stop(quit, 3); reached = 1;";
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.exit_code(), None);
+    let result = interpreter.interpret(&hiolang::parse(source).unwrap());
+    assert!(result.unwrap_err().starts_with("exit(3)"));
+    assert_eq!(interpreter.exit_code(), Some(3));
+    // Neither the catch nor anything after the call ran
+    let reached = interpreter.globals_snapshot().into_iter().find(|(name, _)| name == "reached");
+    assert_eq!(reached, Some(("reached".to_string(), Value::Number(0))));
+}
@@ -0,0 +1,34 @@
+// C output of the transpiler, compared against checked-in golden files
+
+use hiolang::transpiler::Transpiler;
+
+// Fixtures are stored with CRLF line endings like the rest of the repository
+fn read_fixture(name: &str) -> String {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/").to_string() + name;
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path, e))
+        .replace("\r\n", "\n")
+}
+
+fn transpile(source: &str) -> Result<String, String> {
+    Transpiler::new().transpile(&hiolang::parse(source)?)
+}
+
+#[test]
+fn small_program_matches_its_golden_c_file() {
+    let output = transpile(&read_fixture("transpile_small.hio")).unwrap();
+    let expected = read_fixture("transpile_small.c");
+    assert_eq!(output, expected, "transpiler output changed; if intended, regenerate tests/fixtures/transpile_small.c");
+}
+
+#[test]
+fn unsupported_constructs_are_reported() {
+    assert_eq!(transpile("let xs = [1, 2];").unwrap_err(), "Arrays cannot be transpiled to C");
+    assert_eq!(transpile("for (x in y) {}").unwrap_err(), "For-in loops cannot be transpiled to C");
+}
+
+#[test]
+fn the_smallest_number_is_written_without_an_out_of_range_c_literal() {
+    let output = transpile("let m = -9223372036854775808;").unwrap();
+    assert!(output.contains("(-9223372036854775807LL - 1)"), "{}", output);
+}